@@ -0,0 +1,278 @@
+//! generic convert for any `embedded_hal::timer::CountDown` counter, given
+//! its tick frequency(FREQ) and bit width(LEN).
+//!
+//! the timeout is rounded to counter ticks by [Rounding], a timeout longer than
+//! the counter can count is split into several counts, a timeout that does
+//! not fit into u64 ticks is refused with [CountDownError::TooLong].
+//
+//
+use super::{DelayToTicksTrait, FromTimerErrorTrait};
+use embedded_hal::timer::CountDown;
+
+/// The error of the counter converts
+///
+/// embedded-hal 0.2 CountDown::wait can not fail, only the timeout can be
+/// out of range.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CountDownError {
+    /// the timeout does not fit into u64 counter ticks
+    TooLong,
+}
+
+/// the max ticks of a LEN-bit counter, a LEN out of 1-32 fails to compile
+pub(crate) struct CounterLen<const LEN: u32>;
+
+impl<const LEN: u32> CounterLen<LEN> {
+    pub(crate) const MAX_TICKS: u64 = {
+        assert!(0 < LEN && LEN <= 32, "the counter's LEN must be 1-32");
+        (u32::MAX >> (32 - LEN)) as u64
+    };
+}
+
+/// how a timeout that is not a whole number of ticks is rounded
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    /// round down, a timeout shorter than a tick is not waited. it may
+    /// shorten a driver's minimum timing(DIR setup, STEP high)
+    Floor,
+    /// round to the nearest tick, it may also shorten a minimum timing
+    Nearest,
+    /// round up, never wait less than the timeout
+    Ceil,
+}
+
+/// CountDown::Time that can be created from counter ticks
+///
+/// implemented for raw ticks(`u32`, e.g. nrf-hal) and fugit durations(e.g.
+/// stm32-hal's `TimerDurationU32<FREQ>`).
+pub trait CounterTicksTrait {
+    fn from_counter_ticks(ticks: u32) -> Self;
+}
+
+impl CounterTicksTrait for u32 {
+    fn from_counter_ticks(ticks: u32) -> Self {
+        ticks
+    }
+}
+
+impl<const NOM: u32, const DENOM: u32> CounterTicksTrait for fugit::Duration<u32, NOM, DENOM> {
+    fn from_counter_ticks(ticks: u32) -> Self {
+        Self::from_ticks(ticks)
+    }
+}
+
+/// counter wrapper implement [DelayToTicksTrait]
+/// TIMx should be a counter instance, the FREQ must be eq to the counter's FREQ
+/// the LEN(1-32) must be eq to the counter's bit width, it is checked at
+/// compile time.
+///
+/// e.g. a 16-bit counter at 1MHz
+///  let convert = CountDownWrapper::<_, 16, 1_000_000>::new(counter);
+pub struct CountDownWrapper<TIMx, const LEN: u32, const FREQ: u32> {
+    counter: TIMx,
+    rounding: Rounding,
+}
+
+impl<TIMx, const LEN: u32, const FREQ: u32> CountDownWrapper<TIMx, LEN, FREQ> {
+    /// the timeout is rounded up, so the minimum timings are never shortened
+    pub fn new(counter: TIMx) -> Self {
+        Self::with_rounding(counter, Rounding::Ceil)
+    }
+
+    pub fn with_rounding(counter: TIMx, rounding: Rounding) -> Self {
+        Self { counter, rounding }
+    }
+
+    pub fn release(self) -> TIMx {
+        self.counter
+    }
+
+    /// the timeout in counter ticks, `None` if it does not fit into u64
+    pub fn ticks(&self, timeout: &fugit::NanosDurationU64) -> Option<u64> {
+        let nanos = timeout.ticks() as u128 * FREQ as u128;
+        let ticks = match self.rounding {
+            Rounding::Floor => nanos / 1_000_000_000,
+            Rounding::Nearest => (nanos + 500_000_000) / 1_000_000_000,
+            Rounding::Ceil => nanos.div_ceil(1_000_000_000),
+        };
+        u64::try_from(ticks).ok()
+    }
+}
+
+/// start ---> closure ---> wait `total_ticks`, the counts are split by
+/// `max_ticks`(e.g. the counter's max). the remainder goes first, so the
+/// closure overlaps the shortest count. `time` converts counter ticks to the
+/// CountDown::Time.
+pub(crate) fn wait_ticks<TIMx, E>(
+    counter: &mut TIMx,
+    total_ticks: u64,
+    max_ticks: u64,
+    time: impl Fn(u32) -> TIMx::Time,
+    mut closure: impl FnMut() -> Result<(), E>,
+) -> Result<(), E>
+where
+    TIMx: CountDown,
+    E: FromTimerErrorTrait<CountDownError>,
+{
+    if total_ticks < 1 {
+        //we think this scenario means dont need wait timeout, maybe you
+        // can provide yourself wrapper use like "cortex_m::asm::nop()" to do a
+        // approximate timeout
+        return closure();
+    }
+
+    let max = max_ticks;
+    let first = match total_ticks % max {
+        0 => max,
+        rem => rem,
+    };
+    counter.start(time(first as u32));
+    // Invoke closure
+    closure()?;
+    // embedded-hal 0.2 CountDown::wait can not fail
+    nb::block!(counter.wait()).unwrap_or_else(|void| match void {});
+
+    for _ in 0..(total_ticks - first) / max {
+        counter.start(time(max as u32));
+        nb::block!(counter.wait()).unwrap_or_else(|void| match void {});
+    }
+    Ok(())
+}
+
+impl<TIMx, const LEN: u32, const FREQ: u32> DelayToTicksTrait for CountDownWrapper<TIMx, LEN, FREQ>
+where
+    TIMx: CountDown,
+    TIMx::Time: CounterTicksTrait,
+{
+    type Error = CountDownError;
+
+    fn wait<E: FromTimerErrorTrait<Self::Error>>(
+        &mut self,
+        timeout: &fugit::NanosDurationU64,
+        closure: impl FnMut() -> Result<(), E>,
+    ) -> Result<(), E> {
+        let total_ticks = self
+            .ticks(timeout)
+            .ok_or_else(|| E::from_timer_error(CountDownError::TooLong))?;
+        wait_ticks(
+            &mut self.counter,
+            total_ticks,
+            CounterLen::<LEN>::MAX_TICKS,
+            TIMx::Time::from_counter_ticks,
+            closure,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MotionError;
+    use fugit::ExtU64;
+
+    type Error = MotionError<(), CountDownError>;
+
+    #[derive(Default)]
+    struct FakeCountDown {
+        starts: Vec<u32>,
+    }
+
+    impl CountDown for FakeCountDown {
+        type Time = u32;
+
+        fn start<T>(&mut self, count: T)
+        where
+            T: Into<Self::Time>,
+        {
+            self.starts.push(count.into());
+        }
+
+        fn wait(&mut self) -> nb::Result<(), void::Void> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn wait_should_split_at_16_bit_wrap() {
+        let mut convert = CountDownWrapper::<_, 16, 1_000_000>::new(FakeCountDown::default());
+        let mut called = 0;
+        convert
+            .wait::<Error>(&200.millis(), || {
+                called += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(called, 1);
+        // 200_000 = 3 * 65535 + 3395
+        assert_eq!(convert.counter.starts, [3395, 65535, 65535, 65535]);
+
+        // exact multiple, no zero count
+        convert.counter.starts.clear();
+        convert.wait::<Error>(&131_070.micros(), || Ok(())).unwrap();
+        assert_eq!(convert.counter.starts, [65535, 65535]);
+    }
+
+    #[test]
+    fn wait_should_split_at_32_bit_wrap() {
+        let mut convert = CountDownWrapper::<_, 32, 1_000_000>::new(FakeCountDown::default());
+        convert.wait::<Error>(&10.micros(), || Ok(())).unwrap();
+        assert_eq!(convert.counter.starts, [10]);
+
+        // 5000s = 5_000_000_000 ticks = 4294967295 + 705032705
+        convert.counter.starts.clear();
+        convert.wait::<Error>(&5000.secs(), || Ok(())).unwrap();
+        assert_eq!(convert.counter.starts, [705_032_705, u32::MAX]);
+    }
+
+    #[test]
+    fn ticks_should_follow_rounding() {
+        let floor = CountDownWrapper::<_, 16, 1_000_000>::with_rounding((), Rounding::Floor);
+        let nearest = CountDownWrapper::<_, 16, 1_000_000>::with_rounding((), Rounding::Nearest);
+        let ceil = CountDownWrapper::<_, 16, 1_000_000>::new(());
+        let timeout = 1_600.nanos();
+        assert_eq!(floor.ticks(&timeout), Some(1));
+        assert_eq!(nearest.ticks(&timeout), Some(2));
+        assert_eq!(ceil.ticks(&timeout), Some(2));
+        // a 400ns DIR setup is still waited by default
+        assert_eq!(floor.ticks(&400.nanos()), Some(0));
+        assert_eq!(nearest.ticks(&400.nanos()), Some(0));
+        assert_eq!(ceil.ticks(&400.nanos()), Some(1));
+
+        // shorter than a tick, only the closure
+        let mut convert = CountDownWrapper::<_, 16, 1_000_000>::with_rounding(
+            FakeCountDown::default(),
+            Rounding::Floor,
+        );
+        let mut called = false;
+        convert
+            .wait::<Error>(&400.nanos(), || {
+                called = true;
+                Ok(())
+            })
+            .unwrap();
+        assert!(called);
+        assert!(convert.counter.starts.is_empty());
+    }
+
+    #[test]
+    fn wait_should_refuse_a_timeout_out_of_u64_ticks() {
+        let mut convert = CountDownWrapper::<_, 32, 4_000_000_000>::new(FakeCountDown::default());
+        assert_eq!(convert.ticks(&u64::MAX.nanos()), None);
+
+        let mut called = false;
+        let result = convert.wait::<Error>(&u64::MAX.nanos(), || {
+            called = true;
+            Ok(())
+        });
+        assert_eq!(result, Err(MotionError::Timer(CountDownError::TooLong)));
+        assert!(!called);
+        assert!(convert.counter.starts.is_empty());
+    }
+
+    #[test]
+    fn counter_len_should_give_the_max_ticks() {
+        assert_eq!(CounterLen::<1>::MAX_TICKS, 1);
+        assert_eq!(CounterLen::<16>::MAX_TICKS, 65535);
+        assert_eq!(CounterLen::<32>::MAX_TICKS, u32::MAX as u64);
+    }
+}
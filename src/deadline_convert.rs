@@ -0,0 +1,186 @@
+//! convert that schedules the step periods against absolute deadlines of a
+//! free-running timestamp source([TimestampTrait]).
+//!
+//! a chained relative wait(start ---> closure ---> wait) adds the time of
+//! the pin toggles and the loop to every step, e.g. 2500ns high/low gives
+//! 154.7kHz instead of 200kHz. here each step period ends at the previous
+//! deadline plus the period, the overhead is absorbed as long as it is
+//! shorter than the STEP low, so the step periods dont drift.
+//!
+//! only the step periods([DelayToTicksTrait::wait_period]) are scheduled so.
+//! the minimum timings([DelayToTicksTrait::wait], e.g. DIR setup, STEP high)
+//! start when the closure's edge happened, they are never shortened.
+//!
+//! MontionCtrl does not read the timestamp itself, it tells the periods from
+//! the minimum timings by the two waits. so a plain counter convert without a
+//! timestamp works as before, and the deadlines are kept in one place.
+//
+//
+use core::convert::Infallible;
+
+use super::{DelayToTicksTrait, FromTimerErrorTrait, TimestampTrait};
+
+/// deadline convert implement [DelayToTicksTrait]
+///
+/// the deadlines are chained while the steps follow each other, e.g. the
+/// steps of a move. the minimum timings in between(e.g. the STEP high) are
+/// counted in the period. when a period starts later than its deadline(e.g.
+/// the app was idle between moves), it starts from now instead of catching
+/// up.
+///
+///  let convert = DeadlineConvert::new(clock);
+///  let mut ctrl = MontionCtrl::new(driver, convert);
+pub struct DeadlineConvert<Clock> {
+    clock: Clock,
+    deadline: Option<fugit::NanosDurationU64>,
+    // the minimum timings since the last deadline
+    elapsed: fugit::NanosDurationU64,
+}
+
+impl<Clock: TimestampTrait> DeadlineConvert<Clock> {
+    pub fn new(clock: Clock) -> Self {
+        Self {
+            clock,
+            deadline: None,
+            elapsed: fugit::NanosDurationU64::from_ticks(0),
+        }
+    }
+
+    pub fn release(self) -> Clock {
+        self.clock
+    }
+
+    /// forget the last deadline, the next period starts from now
+    pub fn resync(&mut self) {
+        self.deadline = None;
+        self.elapsed = fugit::NanosDurationU64::from_ticks(0);
+    }
+}
+
+impl<Clock: TimestampTrait> TimestampTrait for DeadlineConvert<Clock> {
+    fn now(&mut self) -> fugit::NanosDurationU64 {
+        self.clock.now()
+    }
+}
+
+impl<Clock: TimestampTrait> DelayToTicksTrait for DeadlineConvert<Clock> {
+    /// the timestamp source can not fail
+    type Error = Infallible;
+
+    /// closure ---> wait from the edge, the timeout is never shortened
+    fn wait<E: FromTimerErrorTrait<Self::Error>>(
+        &mut self,
+        timeout: &fugit::NanosDurationU64,
+        mut closure: impl FnMut() -> Result<(), E>,
+    ) -> Result<(), E> {
+        if let Err(error) = closure() {
+            // the chain is broken
+            self.resync();
+            return Err(error);
+        }
+
+        let end = self.clock.now() + *timeout;
+        while self.clock.now() < end {}
+        self.elapsed += *timeout;
+        Ok(())
+    }
+
+    /// closure ---> wait until the deadline of the step period
+    fn wait_period<E: FromTimerErrorTrait<Self::Error>>(
+        &mut self,
+        timeout: &fugit::NanosDurationU64,
+        mut closure: impl FnMut() -> Result<(), E>,
+    ) -> Result<(), E> {
+        let now = self.clock.now();
+        let period = self.elapsed + *timeout;
+        let deadline = match self.deadline {
+            Some(deadline) if now <= deadline + period => deadline + period,
+            _ => now + *timeout,
+        };
+
+        if let Err(error) = closure() {
+            self.resync();
+            return Err(error);
+        }
+
+        while self.clock.now() < deadline {}
+        self.deadline = Some(deadline);
+        self.elapsed = fugit::NanosDurationU64::from_ticks(0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MotionError;
+    use core::cell::Cell;
+    use fugit::ExtU64;
+
+    /// each read takes `cost` ns
+    struct FakeClock<'a> {
+        now: &'a Cell<u64>,
+        cost: u64,
+    }
+
+    impl TimestampTrait for FakeClock<'_> {
+        fn now(&mut self) -> fugit::NanosDurationU64 {
+            self.now.set(self.now.get() + self.cost);
+            self.now.get().nanos()
+        }
+    }
+
+    #[test]
+    fn wait_period_should_not_drift_by_the_overhead() {
+        let now = Cell::new(0);
+        let mut convert = DeadlineConvert::new(FakeClock {
+            now: &now,
+            cost: 10,
+        });
+
+        // the closure(pin toggle) takes 300ns
+        let toggle = || {
+            now.set(now.get() + 300);
+            Ok::<(), MotionError<(), Infallible>>(())
+        };
+        // 2500ns high, 2500ns low
+        convert.wait(&2500.nanos(), toggle).unwrap();
+        convert.wait_period(&2500.nanos(), toggle).unwrap();
+        let start = now.get() - 5000;
+        for _ in 0..99 {
+            convert.wait(&2500.nanos(), toggle).unwrap();
+            convert.wait_period(&2500.nanos(), toggle).unwrap();
+        }
+        // late by at most one read of the clock, not 100 * 600ns
+        let late = now.get() - (start + 100 * 5000);
+        assert!(late <= 10, "late {}ns", late);
+
+        // idle between moves, start from now
+        now.set(now.get() + 1_000_000);
+        let before = now.get();
+        convert.wait_period(&2500.nanos(), toggle).unwrap();
+        assert!(now.get() - before >= 2500);
+    }
+
+    #[test]
+    fn wait_should_start_from_the_edge() {
+        let now = Cell::new(0);
+        let mut convert = DeadlineConvert::new(FakeClock {
+            now: &now,
+            cost: 10,
+        });
+
+        // the toggle takes longer than the 200ns setup time
+        let edge = Cell::new(0);
+        let toggle = || {
+            now.set(now.get() + 300);
+            edge.set(now.get());
+            Ok::<(), MotionError<(), Infallible>>(())
+        };
+        convert.wait_period(&2500.nanos(), toggle).unwrap();
+        for _ in 0..10 {
+            convert.wait(&200.nanos(), toggle).unwrap();
+            assert!(now.get() - edge.get() >= 200);
+        }
+    }
+}
@@ -12,6 +12,7 @@ use embedded_hal::digital::v2::{OutputPin, PinState};
 use crate::interfaces::{SetDirectionTrait, SetStepModeTrait, StepTrait,EnableDirectionControlTrait,
         EnableStepControlTrait, EnableStepModeControlTrait,
         EnableResetControlTrait,ResetTrait,
+        EnableOutputControlTrait, OutputEnableTrait, EnableSleepControlTrait, SleepTrait,
 };
 
 
@@ -46,10 +47,16 @@ impl A4988<(), (), (), (), (), (), (), ()> {
     }
 }
 
+impl Default for A4988<(), (), (), (), (), (), (), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
+
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
     EnableStepModeControlTrait<( Ms1, Ms2, Ms3)>
-    for A4988<(), (),Reset, (),  (), (), Step, Dir>
+    for A4988<Enable, Sleep, Reset, (), (), (), Step, Dir>
 where
     Reset: OutputPin<Error = OutputPinError>,
     Ms3: OutputPin<Error = OutputPinError>,
@@ -57,7 +64,7 @@ where
     Ms2: OutputPin<Error = OutputPinError>,
 {
     type WithStepModeControl =
-        A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, Dir>;
+        A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>;
 
     fn enable_step_mode_control(
         self,
@@ -76,8 +83,8 @@ where
     }
 }
 
-impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> SetStepModeTrait
-    for A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, Dir>
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> SetStepModeTrait
+    for A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>
 
     where
     Reset: OutputPin<Error = OutputPinError>,
@@ -119,14 +126,14 @@ impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> SetStepModeTrait
 
 }
 
-impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
     EnableDirectionControlTrait<Dir>
-    for A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, ()>
+    for A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, ()>
 where
     Dir: OutputPin<Error = OutputPinError>,
 {
     type WithDirectionControl =
-        A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, Dir>;
+        A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>;
 
     fn enable_direction_control(self, dir: Dir) -> Self::WithDirectionControl {
         A4988 {
@@ -143,8 +150,8 @@ where
 }
 
 
-impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> SetDirectionTrait
-    for A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, Dir>
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> SetDirectionTrait
+    for A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>
 where
     Dir: OutputPin<Error = OutputPinError>,
 {
@@ -166,14 +173,14 @@ where
     }    
 }
 
-impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
     EnableStepControlTrait<Step>
-    for A4988< (), (), Reset, Ms1, Ms2, Ms3, (), Dir>
+    for A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, (), Dir>
 where
     Step: OutputPin<Error = OutputPinError>,
 {
     type WithStepControl =
-        A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, Dir>;
+        A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>;
 
     fn enable_step_control(self, step: Step) -> Self::WithStepControl {
         A4988 {
@@ -189,8 +196,8 @@ where
     }
 }
 
-impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> StepTrait
-    for A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, Dir>
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> StepTrait
+    for A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>
 where
     Step: OutputPin<Error = OutputPinError>,
 {
@@ -213,14 +220,14 @@ where
 }
 
 //////////////////////////////////////////////////
-impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
     EnableResetControlTrait<Reset>
-    for A4988< (), (), (), Ms1, Ms2, Ms3, Step, Dir>
+    for A4988<Enable, Sleep, (), Ms1, Ms2, Ms3, Step, Dir>
 where
     Reset: OutputPin<Error = OutputPinError>,
 {
     type WithResetControl =
-        A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, Dir>;
+        A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>;
 
     fn enable_reset_control(self, reset: Reset) -> Self::WithResetControl {
         A4988 {
@@ -236,8 +243,8 @@ where
     }
 }
 
-impl<Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> ResetTrait
-    for A4988< (), (), Reset, Ms1, Ms2, Ms3, Step, Dir>
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> ResetTrait
+    for A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>
 where
     Reset: OutputPin<Error = OutputPinError>,
 {
@@ -252,3 +259,88 @@ where
     }
 
 }
+
+//////////////////////////////////////////////////
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
+    EnableOutputControlTrait<Enable>
+    for A4988<(), Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>
+where
+    Enable: OutputPin<Error = OutputPinError>,
+{
+    type WithOutputControl =
+        A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>;
+
+    fn enable_output_control(self, enable: Enable) -> Self::WithOutputControl {
+        A4988 {
+            enable,
+            sleep: self.sleep,
+            reset: self.reset,
+            ms1: self.ms1,
+            ms2: self.ms2,
+            ms3: self.ms3,
+            step: self.step,
+            dir: self.dir,
+        }
+    }
+}
+
+/// ENABLE is active low
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> OutputEnableTrait
+    for A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>
+where
+    Enable: OutputPin<Error = OutputPinError>,
+{
+    // the datasheet gives none, use the setup time of other inputs
+    const ENABLE_SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(200);
+
+    type Error = OutputPinError;
+
+    fn enable_output(&mut self) -> Result<(), Self::Error> {
+        self.enable.set_low()
+    }
+    fn disable_output(&mut self) -> Result<(), Self::Error> {
+        self.enable.set_high()
+    }
+}
+
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError>
+    EnableSleepControlTrait<Sleep>
+    for A4988<Enable, (), Reset, Ms1, Ms2, Ms3, Step, Dir>
+where
+    Sleep: OutputPin<Error = OutputPinError>,
+{
+    type WithSleepControl =
+        A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>;
+
+    fn enable_sleep_control(self, sleep: Sleep) -> Self::WithSleepControl {
+        A4988 {
+            enable: self.enable,
+            sleep,
+            reset: self.reset,
+            ms1: self.ms1,
+            ms2: self.ms2,
+            ms3: self.ms3,
+            step: self.step,
+            dir: self.dir,
+        }
+    }
+}
+
+/// SLEEP is active low
+impl<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir, OutputPinError> SleepTrait
+    for A4988<Enable, Sleep, Reset, Ms1, Ms2, Ms3, Step, Dir>
+where
+    Sleep: OutputPin<Error = OutputPinError>,
+{
+    // "provide a delay of 1 ms before issuing a Step command" after sleep
+    const WAKE_UP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::millis(1);
+
+    type Error = OutputPinError;
+
+    fn sleep(&mut self) -> Result<(), Self::Error> {
+        self.sleep.set_low()
+    }
+    fn wake_up(&mut self) -> Result<(), Self::Error> {
+        self.sleep.set_high()
+    }
+}
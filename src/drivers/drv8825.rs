@@ -2,11 +2,13 @@
 //!
 // use core::convert::Infallible;
 
-use embedded_hal::digital::v2::{OutputPin, PinState};
+use embedded_hal::digital::v2::{InputPin, OutputPin, PinState};
 
 use crate::interfaces::{SetDirectionTrait, SetStepModeTrait, StepTrait,EnableDirectionControlTrait,
     EnableStepControlTrait, EnableStepModeControlTrait,
     EnableResetControlTrait,ResetTrait,
+    EnableOutputControlTrait, OutputEnableTrait, EnableSleepControlTrait, SleepTrait,
+    EnableFaultControlTrait, FaultTrait,
 };
 
 
@@ -43,10 +45,16 @@ impl DRV8825<(), (), (), (), (), (), (), (), ()> {
     }
 }
 
+impl Default for DRV8825<(), (), (), (), (), (), (), (), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 
-impl<Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
     EnableStepModeControlTrait<( Mode0, Mode1, Mode2)>
-    for DRV8825<(), (), (), Reset, (), (), (), Step, Dir>
+    for DRV8825<Enable, Fault, Sleep, Reset, (), (), (), Step, Dir>
 where
     Reset: OutputPin<Error = OutputPinError>,
     Mode0: OutputPin<Error = OutputPinError>,
@@ -54,7 +62,7 @@ where
     Mode2: OutputPin<Error = OutputPinError>,
 {
     type WithStepModeControl =
-        DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>;
+        DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>;
 
     fn enable_step_mode_control(
         self,
@@ -74,8 +82,8 @@ where
     }
 }
 
-impl<Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> SetStepModeTrait
-    for DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> SetStepModeTrait
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
 where
     Reset: OutputPin<Error = OutputPinError>,
     Mode0: OutputPin<Error = OutputPinError>,
@@ -118,14 +126,14 @@ where
 
 }
 
-impl<Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
     EnableDirectionControlTrait<Dir>
-    for DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, ()>
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, ()>
 where
     Dir: OutputPin<Error = OutputPinError>,
 {
     type WithDirectionControl =
-        DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>;
+        DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>;
 
     fn enable_direction_control(self, dir: Dir) -> Self::WithDirectionControl {
         DRV8825 {
@@ -143,8 +151,8 @@ where
 }
 
 
-impl<Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> SetDirectionTrait
-    for DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> SetDirectionTrait
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
 where
     Dir: OutputPin<Error = OutputPinError>,
 {
@@ -168,14 +176,14 @@ where
     }    
 }
 
-impl<Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
     EnableStepControlTrait<Step>
-    for DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, (), Dir>
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, (), Dir>
 where
     Step: OutputPin<Error = OutputPinError>,
 {
     type WithStepControl =
-        DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>;
+        DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>;
 
     fn enable_step_control(self, step: Step) -> Self::WithStepControl {
         DRV8825 {
@@ -192,8 +200,8 @@ where
     }
 }
 
-impl<Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> StepTrait
-    for DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> StepTrait
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
 where
     Step: OutputPin<Error = OutputPinError>,
 {
@@ -218,14 +226,14 @@ where
 
 
 //////////////////////////////////////////////////
-impl<Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
     EnableResetControlTrait<Reset>
-    for DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>
+    for DRV8825<Enable, Fault, Sleep, (), Mode0, Mode1, Mode2, Step, Dir>
 where
     Reset: OutputPin<Error = OutputPinError>,
 {
     type WithResetControl =
-        DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>;
+        DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>;
 
     fn enable_reset_control(self, reset: Reset) -> Self::WithResetControl {
         DRV8825 {
@@ -242,8 +250,8 @@ where
     }
 }
 
-impl<Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> ResetTrait
-    for DRV8825<(), (), (), Reset, Mode0, Mode1, Mode2, Step, Dir>
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> ResetTrait
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
 where
     Reset: OutputPin<Error = OutputPinError>,
 {
@@ -260,3 +268,128 @@ where
     }
 
 }
+
+//////////////////////////////////////////////////
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
+    EnableOutputControlTrait<Enable>
+    for DRV8825<(), Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
+where
+    Enable: OutputPin<Error = OutputPinError>,
+{
+    type WithOutputControl =
+        DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>;
+
+    fn enable_output_control(self, enable: Enable) -> Self::WithOutputControl {
+        DRV8825 {
+            enable,
+            fault: self.fault,
+            sleep: self.sleep,
+            reset: self.reset,
+            mode0: self.mode0,
+            mode1: self.mode1,
+            mode2: self.mode2,
+            step: self.step,
+            dir: self.dir,
+        }
+    }
+}
+
+/// nENBL is active low
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> OutputEnableTrait
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
+where
+    Enable: OutputPin<Error = OutputPinError>,
+{
+    // the datasheet gives none, use the setup time of other inputs
+    const ENABLE_SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(650);
+
+    type Error = OutputPinError;
+
+    fn enable_output(&mut self) -> Result<(), Self::Error> {
+        self.enable.set_low()
+    }
+    fn disable_output(&mut self) -> Result<(), Self::Error> {
+        self.enable.set_high()
+    }
+}
+
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError>
+    EnableSleepControlTrait<Sleep>
+    for DRV8825<Enable, Fault, (), Reset, Mode0, Mode1, Mode2, Step, Dir>
+where
+    Sleep: OutputPin<Error = OutputPinError>,
+{
+    type WithSleepControl =
+        DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>;
+
+    fn enable_sleep_control(self, sleep: Sleep) -> Self::WithSleepControl {
+        DRV8825 {
+            enable: self.enable,
+            fault: self.fault,
+            sleep,
+            reset: self.reset,
+            mode0: self.mode0,
+            mode1: self.mode1,
+            mode2: self.mode2,
+            step: self.step,
+            dir: self.dir,
+        }
+    }
+}
+
+/// nSLEEP is active low
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, OutputPinError> SleepTrait
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
+where
+    Sleep: OutputPin<Error = OutputPinError>,
+{
+    // 7.6 Timing Requirements (page 7), tWAKE
+    // https://www.ti.com/lit/ds/symlink/drv8825.pdf
+    const WAKE_UP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::micros(1700);
+
+    type Error = OutputPinError;
+
+    fn sleep(&mut self) -> Result<(), Self::Error> {
+        self.sleep.set_low()
+    }
+    fn wake_up(&mut self) -> Result<(), Self::Error> {
+        self.sleep.set_high()
+    }
+}
+
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, InputPinError>
+    EnableFaultControlTrait<Fault>
+    for DRV8825<Enable, (), Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
+where
+    Fault: InputPin<Error = InputPinError>,
+{
+    type WithFaultControl =
+        DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>;
+
+    fn enable_fault_control(self, fault: Fault) -> Self::WithFaultControl {
+        DRV8825 {
+            enable: self.enable,
+            fault,
+            sleep: self.sleep,
+            reset: self.reset,
+            mode0: self.mode0,
+            mode1: self.mode1,
+            mode2: self.mode2,
+            step: self.step,
+            dir: self.dir,
+        }
+    }
+}
+
+/// nFAULT is open-drain, pulled low on fault
+impl<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir, InputPinError> FaultTrait
+    for DRV8825<Enable, Fault, Sleep, Reset, Mode0, Mode1, Mode2, Step, Dir>
+where
+    Fault: InputPin<Error = InputPinError>,
+{
+    type Error = InputPinError;
+
+    fn is_fault(&mut self) -> Result<bool, Self::Error> {
+        self.fault.is_low()
+    }
+}
@@ -0,0 +1,32 @@
+//! DRV8834 Driver
+//!
+//! M0 is a tri-state pin(low, high, floating), M1 is a normal output. the
+//! indexer reads them at the next STEP, no reset is needed.
+//!
+//! ```rust,ignore
+//!   let driver = DRV8834::new()
+//!       .enable_step_mode_control((m0_tri_state_pin, m1_pin))
+//!       .enable_step_control(step_pin)
+//!       .enable_direction_control(dir_pin);
+//! ```
+
+tri_state_driver! {
+    /// The DRV8834 driver API
+    DRV8834,
+    step_mode: StepMode32,
+    // 6.6 Timing Requirements, tSU/tH of Mx to STEP, tSU of DIR to STEP,
+    // tWH/tWL of STEP
+    // https://www.ti.com/lit/ds/symlink/drv8834.pdf
+    mode_setup: 200,
+    mode_hold: 200,
+    dir_setup: 200,
+    pulse_length: 1900,
+    modes: {
+        Full => (Low, Low),
+        M2 => (High, Low),
+        M4 => (Floating, Low),
+        M8 => (Low, High),
+        M16 => (High, High),
+        M32 => (Floating, High),
+    }
+}
@@ -0,0 +1,33 @@
+//! DRV8880 Driver
+//!
+//! M0 is a tri-state pin(low, high, floating), M1 is a normal output. the
+//! indexer reads them at the next STEP, no reset is needed. the non-circular
+//! 1/2 step(M1 low, M0 high) is not used, M2 is the circular one.
+//!
+//! ```rust,ignore
+//!   let driver = DRV8880::new()
+//!       .enable_step_mode_control((m0_tri_state_pin, m1_pin))
+//!       .enable_step_control(step_pin)
+//!       .enable_direction_control(dir_pin);
+//! ```
+
+tri_state_driver! {
+    /// The DRV8880 driver API
+    DRV8880,
+    step_mode: StepMode16,
+    // 7.6 Timing Requirements, tSU/tH of Mx to STEP, tSU of DIR to STEP,
+    // tWH/tWL of STEP
+    // https://www.ti.com/lit/ds/symlink/drv8880.pdf
+    mode_setup: 200,
+    mode_hold: 200,
+    dir_setup: 200,
+    pulse_length: 970,
+    modes: {
+        // 71% current
+        Full => (Low, Low),
+        M2 => (Low, High),
+        M4 => (High, High),
+        M8 => (Floating, Low),
+        M16 => (Floating, High),
+    }
+}
@@ -0,0 +1,513 @@
+//! H-bridge Driver
+//!
+//! bipolar motors driven by two H-bridges directly, e.g. L298N or TB6612.
+//! the MCU commutates the four direction pins(AIN1/AIN2 of coil A, BIN1/BIN2
+//! of coil B) by software, a step moves the electrical angle of the rotor.
+//!
+//! ```rust,ignore
+//!   // full/half step, ENA/ENB(PWMA/PWMB) tied high
+//!   let driver = HBRIDGE::new().enable_step_control((ain1, ain2, bin1, bin2));
+//!   // sine/cosine microstepping up to 1/32, by the PWM of ENA/ENB
+//!   let driver = HBRIDGE::new()
+//!       .enable_step_control((ain1, ain2, bin1, bin2))
+//!       .enable_step_mode_control((pwm_a, pwm_b));
+//! ```
+//!
+//! the current of coil A is cos, coil B is sin of the electrical angle, one
+//! fullstep is 90 degrees. the sign is the polarity of the direction pins, the
+//! magnitude is the PWM duty. without PWM only the sign applies, which is the
+//! classic commutation:
+//!
+//! ```text
+//!   angle     0   45   90  135  180  225  270  315
+//!   coil A    +    +    0    -    -    -    0    +
+//!   coil B    0    +    +    +    0    -    -    -
+//!   Full           *         *         *         *
+//!   M2        *    *    *    *    *    *    *    *
+//! ```
+//!
+//! the sine table is generated at compile time, refer [SINE].
+
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::PwmPin;
+
+use super::NoPin;
+use crate::interfaces::{
+    EnableStepControlTrait, EnableStepModeControlTrait, OutputEnableTrait, SetDirectionTrait,
+    SetStepModeTrait, StepModeResetTrait, StepTrait,
+};
+use crate::step_mode::{StepMode2, StepMode32};
+use crate::Direction;
+
+/// microsteps per fullstep(90 degrees) of the angle
+const QUARTER: u32 = 32;
+/// microsteps per electrical cycle
+const CYCLE: u32 = 4 * QUARTER;
+
+const ONE: i64 = 1 << 30;
+/// pi in Q30
+const PI: i64 = 3_373_259_426;
+
+/// sin(i * 90 / 32 degrees) of the first quarter, 65535 is 1
+pub const SINE: [u16; QUARTER as usize + 1] = sine_table();
+
+/// taylor series of sin in Q30, it is accurate to 1e-7 in 0..pi/2
+const fn sine_table() -> [u16; QUARTER as usize + 1] {
+    let mut table = [0u16; QUARTER as usize + 1];
+    let mut i = 0;
+    while i < table.len() {
+        let x = PI * i as i64 / (2 * QUARTER as i64);
+        let x2 = (x * x) >> 30;
+        let mut t = ONE - x2 / 110;
+        t = ONE - ((x2 * t) >> 30) / 72;
+        t = ONE - ((x2 * t) >> 30) / 42;
+        t = ONE - ((x2 * t) >> 30) / 20;
+        t = ONE - ((x2 * t) >> 30) / 6;
+        let sin = (x * t) >> 30;
+        let value = (sin * 65535 + ONE / 2) >> 30;
+        table[i] = if value > 65535 { 65535 } else { value as u16 };
+        i += 1;
+    }
+    table
+}
+
+/// sin of the angle(unit is 1/32 fullstep), as (positive, magnitude)
+fn sin(angle: u32) -> (bool, u16) {
+    let angle = angle % CYCLE;
+    let r = angle % QUARTER;
+    match angle / QUARTER {
+        0 => (true, SINE[r as usize]),
+        1 => (true, SINE[(QUARTER - r) as usize]),
+        2 => (false, SINE[r as usize]),
+        _ => (false, SINE[(QUARTER - r) as usize]),
+    }
+}
+
+/// Set the current of the coils, implemented for the PWM pins of the
+/// bridges `(PwmA, PwmB)`, and `()` if the bridges are always at full current
+pub trait CoilCurrentTrait {
+    /// current of coil A and B, 0-65535 is 0-100%
+    fn set_current(&mut self, a: u16, b: u16);
+}
+
+impl CoilCurrentTrait for () {
+    fn set_current(&mut self, _a: u16, _b: u16) {}
+}
+
+impl<PwmA, PwmB> CoilCurrentTrait for (PwmA, PwmB)
+where
+    PwmA: PwmPin<Duty = u16>,
+    PwmB: PwmPin<Duty = u16>,
+{
+    fn set_current(&mut self, a: u16, b: u16) {
+        let duty = |max: u16, current: u16| (max as u32 * current as u32 / 65535) as u16;
+        let max = self.0.get_max_duty();
+        self.0.set_duty(duty(max, a));
+        let max = self.1.get_max_duty();
+        self.1.set_duty(duty(max, b));
+    }
+}
+
+/// The H-bridge driver API
+///
+/// the default step mode is fullstep.
+pub struct HBRIDGE<Pins, Pwm> {
+    pins: Pins,
+    pwm: Pwm,
+    /// electrical angle, unit is 1/32 fullstep
+    angle: u32,
+    /// microsteps of the step mode
+    microsteps: u32,
+    direction: Direction,
+    no_pin: NoPin,
+}
+
+impl HBRIDGE<(), ()> {
+    /// Create a new instance of `HBRIDGE`
+    pub fn new() -> Self {
+        Self {
+            pins: (),
+            pwm: (),
+            angle: 0,
+            microsteps: 1,
+            direction: Direction::Forward,
+            no_pin: NoPin,
+        }
+    }
+}
+
+impl Default for HBRIDGE<(), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<AIn1, AIn2, BIn1, BIn2, OutputPinError> EnableStepControlTrait<(AIn1, AIn2, BIn1, BIn2)>
+    for HBRIDGE<(), ()>
+where
+    AIn1: OutputPin<Error = OutputPinError>,
+    AIn2: OutputPin<Error = OutputPinError>,
+    BIn1: OutputPin<Error = OutputPinError>,
+    BIn2: OutputPin<Error = OutputPinError>,
+{
+    type WithStepControl = HBRIDGE<(AIn1, AIn2, BIn1, BIn2), ()>;
+
+    fn enable_step_control(self, pins: (AIn1, AIn2, BIn1, BIn2)) -> Self::WithStepControl {
+        HBRIDGE {
+            pins,
+            pwm: self.pwm,
+            angle: self.angle,
+            microsteps: self.microsteps,
+            direction: self.direction,
+            no_pin: self.no_pin,
+        }
+    }
+}
+
+impl<AIn1, AIn2, BIn1, BIn2, PwmA, PwmB, OutputPinError> EnableStepModeControlTrait<(PwmA, PwmB)>
+    for HBRIDGE<(AIn1, AIn2, BIn1, BIn2), ()>
+where
+    AIn1: OutputPin<Error = OutputPinError>,
+    AIn2: OutputPin<Error = OutputPinError>,
+    BIn1: OutputPin<Error = OutputPinError>,
+    BIn2: OutputPin<Error = OutputPinError>,
+    PwmA: PwmPin<Duty = u16>,
+    PwmB: PwmPin<Duty = u16>,
+{
+    type WithStepModeControl = HBRIDGE<(AIn1, AIn2, BIn1, BIn2), (PwmA, PwmB)>;
+
+    fn enable_step_mode_control(
+        self,
+        (mut pwm_a, mut pwm_b): (PwmA, PwmB),
+    ) -> Self::WithStepModeControl {
+        pwm_a.set_duty(0);
+        pwm_b.set_duty(0);
+        pwm_a.enable();
+        pwm_b.enable();
+        HBRIDGE {
+            pins: self.pins,
+            pwm: (pwm_a, pwm_b),
+            angle: self.angle,
+            microsteps: self.microsteps,
+            direction: self.direction,
+            no_pin: self.no_pin,
+        }
+    }
+}
+
+impl<AIn1, AIn2, BIn1, BIn2, Pwm, OutputPinError> HBRIDGE<(AIn1, AIn2, BIn1, BIn2), Pwm>
+where
+    AIn1: OutputPin<Error = OutputPinError>,
+    AIn2: OutputPin<Error = OutputPinError>,
+    BIn1: OutputPin<Error = OutputPinError>,
+    BIn2: OutputPin<Error = OutputPinError>,
+    Pwm: CoilCurrentTrait,
+{
+    /// current electrical angle, unit is 1/32 fullstep(90 degrees)
+    pub fn angle(&self) -> u32 {
+        self.angle
+    }
+
+    pub fn release(self) -> ((AIn1, AIn2, BIn1, BIn2), Pwm) {
+        (self.pins, self.pwm)
+    }
+
+    /// whether the angle is a position of the step mode, fullstep is at the
+    /// odd multiples of 45 degrees, the others at the multiples of 90/microsteps
+    // is_multiple_of needs rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn is_position(&self, angle: u32) -> bool {
+        if self.microsteps == 1 {
+            angle % QUARTER == QUARTER / 2
+        } else {
+            angle % (QUARTER / self.microsteps) == 0
+        }
+    }
+
+    /// drive both coils according to the angle
+    fn write(&mut self) -> Result<(), OutputPinError> {
+        let (b_positive, b) = sin(self.angle);
+        let (a_positive, a) = sin(self.angle + QUARTER);
+        self.pwm.set_current(a, b);
+
+        // a coil without current is off, both pins low
+        let (a1, a2) = (a != 0 && a_positive, a != 0 && !a_positive);
+        let (b1, b2) = (b != 0 && b_positive, b != 0 && !b_positive);
+        self.pins.0.set_state(a1.into())?;
+        self.pins.1.set_state(a2.into())?;
+        self.pins.2.set_state(b1.into())?;
+        self.pins.3.set_state(b2.into())
+    }
+
+    /// turn both coils off
+    fn coast(&mut self) -> Result<(), OutputPinError> {
+        self.pwm.set_current(0, 0);
+        self.pins.0.set_low()?;
+        self.pins.1.set_low()?;
+        self.pins.2.set_low()?;
+        self.pins.3.set_low()
+    }
+}
+
+impl<AIn1, AIn2, BIn1, BIn2, OutputPinError> SetStepModeTrait
+    for HBRIDGE<(AIn1, AIn2, BIn1, BIn2), ()>
+where
+    AIn1: OutputPin<Error = OutputPinError>,
+    AIn2: OutputPin<Error = OutputPinError>,
+    BIn1: OutputPin<Error = OutputPinError>,
+    BIn2: OutputPin<Error = OutputPinError>,
+{
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+    const HOLD_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Error = OutputPinError;
+    type StepMode = StepMode2;
+
+    /// it takes effect at the next step, which may move less than a step
+    /// to reach the positions of the new mode
+    fn apply_mode_config(&mut self, step_mode: Self::StepMode) -> Result<(), Self::Error> {
+        self.microsteps = step_mode as u32;
+        Ok(())
+    }
+}
+
+impl<AIn1, AIn2, BIn1, BIn2, PwmA, PwmB, OutputPinError> SetStepModeTrait
+    for HBRIDGE<(AIn1, AIn2, BIn1, BIn2), (PwmA, PwmB)>
+where
+    AIn1: OutputPin<Error = OutputPinError>,
+    AIn2: OutputPin<Error = OutputPinError>,
+    BIn1: OutputPin<Error = OutputPinError>,
+    BIn2: OutputPin<Error = OutputPinError>,
+    PwmA: PwmPin<Duty = u16>,
+    PwmB: PwmPin<Duty = u16>,
+{
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+    const HOLD_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Error = OutputPinError;
+    type StepMode = StepMode32;
+
+    /// it takes effect at the next step, which may move less than a step
+    /// to reach the positions of the new mode
+    fn apply_mode_config(&mut self, step_mode: Self::StepMode) -> Result<(), Self::Error> {
+        self.microsteps = step_mode as u32;
+        Ok(())
+    }
+}
+
+// the mode applies at the next step, no reset
+impl<Pins, Pwm> StepModeResetTrait for HBRIDGE<Pins, Pwm> {
+    type ModeResetError = core::convert::Infallible;
+}
+
+impl<AIn1, AIn2, BIn1, BIn2, Pwm, OutputPinError> SetDirectionTrait
+    for HBRIDGE<(AIn1, AIn2, BIn1, BIn2), Pwm>
+where
+    AIn1: OutputPin<Error = OutputPinError>,
+    AIn2: OutputPin<Error = OutputPinError>,
+    BIn1: OutputPin<Error = OutputPinError>,
+    BIn2: OutputPin<Error = OutputPinError>,
+    Pwm: CoilCurrentTrait,
+{
+    // the direction applies to the next step, no signal to set up
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Dir = NoPin;
+    type Error = OutputPinError;
+
+    fn set_backward(&mut self) -> Result<(), Self::Error> {
+        self.direction = Direction::Backward;
+        Ok(())
+    }
+    fn set_forward(&mut self) -> Result<(), Self::Error> {
+        self.direction = Direction::Forward;
+        Ok(())
+    }
+    #[inline(always)]
+    fn dir_pin(&mut self) -> &mut Self::Dir {
+        &mut self.no_pin
+    }
+}
+
+impl<AIn1, AIn2, BIn1, BIn2, Pwm, OutputPinError> StepTrait
+    for HBRIDGE<(AIn1, AIn2, BIn1, BIn2), Pwm>
+where
+    AIn1: OutputPin<Error = OutputPinError>,
+    AIn2: OutputPin<Error = OutputPinError>,
+    BIn1: OutputPin<Error = OutputPinError>,
+    BIn2: OutputPin<Error = OutputPinError>,
+    Pwm: CoilCurrentTrait,
+{
+    // the coils are switched at the rising edge, the rest of the step period
+    // is the low time
+    const PULSE_LENGTH: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(1000);
+
+    type Step = NoPin;
+    type Error = OutputPinError;
+
+    /// move the angle to the next position of the step mode, and drive the coils
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let delta = match self.direction {
+            Direction::Forward => 1,
+            Direction::Backward => CYCLE - 1,
+        };
+        let mut angle = (self.angle + delta) % CYCLE;
+        while !self.is_position(angle) {
+            angle = (angle + delta) % CYCLE;
+        }
+        self.angle = angle;
+        self.write()
+    }
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    fn setp_pin(&mut self) -> &mut Self::Step {
+        &mut self.no_pin
+    }
+}
+
+impl<AIn1, AIn2, BIn1, BIn2, Pwm, OutputPinError> OutputEnableTrait
+    for HBRIDGE<(AIn1, AIn2, BIn1, BIn2), Pwm>
+where
+    AIn1: OutputPin<Error = OutputPinError>,
+    AIn2: OutputPin<Error = OutputPinError>,
+    BIn1: OutputPin<Error = OutputPinError>,
+    BIn2: OutputPin<Error = OutputPinError>,
+    Pwm: CoilCurrentTrait,
+{
+    const ENABLE_SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Error = OutputPinError;
+
+    /// drive the coils of the current angle to hold the position
+    fn enable_output(&mut self) -> Result<(), Self::Error> {
+        self.write()
+    }
+
+    /// turn both coils off, the motor coasts
+    fn disable_output(&mut self) -> Result<(), Self::Error> {
+        self.coast()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    struct MockPin<'a>(&'a Cell<u8>, u8);
+
+    impl OutputPin for MockPin<'_> {
+        type Error = Infallible;
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.set(self.0.get() & !(1 << self.1));
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.set(self.0.get() | (1 << self.1));
+            Ok(())
+        }
+    }
+
+    struct MockPwm(u16);
+
+    impl PwmPin for MockPwm {
+        type Duty = u16;
+        fn disable(&mut self) {}
+        fn enable(&mut self) {}
+        fn get_duty(&self) -> u16 {
+            self.0
+        }
+        fn get_max_duty(&self) -> u16 {
+            1000
+        }
+        fn set_duty(&mut self, duty: u16) {
+            self.0 = duty;
+        }
+    }
+
+    fn pins(state: &Cell<u8>) -> (MockPin<'_>, MockPin<'_>, MockPin<'_>, MockPin<'_>) {
+        (
+            MockPin(state, 0),
+            MockPin(state, 1),
+            MockPin(state, 2),
+            MockPin(state, 3),
+        )
+    }
+
+    #[test]
+    fn sine_table_should_match_sin() {
+        for (i, &value) in SINE.iter().enumerate() {
+            let expected = (i as f64 * core::f64::consts::FRAC_PI_2 / 32.0).sin() * 65535.0;
+            assert!((value as f64 - expected).abs() <= 1.0, "{} {}", i, value);
+        }
+        assert_eq!(SINE[0], 0);
+        assert_eq!(SINE[32], 65535);
+    }
+
+    #[test]
+    fn step_should_commutate_full_and_half_step() {
+        let state = Cell::new(0);
+        let mut driver = HBRIDGE::new().enable_step_control(pins(&state));
+        // bits: AIN1, AIN2, BIN1, BIN2
+        let mut seen = [0u8; 4];
+        for pins in seen.iter_mut() {
+            driver.set_high().unwrap();
+            *pins = state.get();
+        }
+        assert_eq!(seen, [0b0101, 0b0110, 0b1010, 0b1001]);
+
+        driver.apply_mode_config(StepMode2::M2).unwrap();
+        driver.set_backward().unwrap();
+        for pins in seen.iter_mut() {
+            driver.set_high().unwrap();
+            *pins = state.get();
+        }
+        assert_eq!(seen, [0b1000, 0b1010, 0b0010, 0b0110]);
+    }
+
+    #[test]
+    fn step_should_set_sine_current_with_pwm() {
+        let state = Cell::new(0);
+        let mut driver = HBRIDGE::new()
+            .enable_step_control(pins(&state))
+            .enable_step_mode_control((MockPwm(0), MockPwm(0)));
+        driver.apply_mode_config(StepMode32::M8).unwrap();
+
+        // from 0, the first position of 1/8 is 11.25 degrees
+        driver.set_high().unwrap();
+        assert_eq!(driver.angle(), 4);
+        assert_eq!(((driver.pwm.0).0, (driver.pwm.1).0), (980, 195));
+        assert_eq!(state.get(), 0b0101);
+
+        driver.set_backward().unwrap();
+        driver.set_high().unwrap();
+        driver.set_high().unwrap();
+        assert_eq!(driver.angle(), CYCLE - 4);
+        assert_eq!(((driver.pwm.0).0, (driver.pwm.1).0), (980, 195));
+        assert_eq!(state.get(), 0b1001);
+
+        driver.disable_output().unwrap();
+        assert_eq!(((driver.pwm.0).0, (driver.pwm.1).0, state.get()), (0, 0, 0));
+    }
+
+    #[test]
+    fn set_step_mode_should_work_through_montionctrl() {
+        use crate::sim::Sim;
+        use crate::{MontionCtrl, MotionControlStepModeTrait};
+
+        let state = Cell::new(0);
+        let driver = HBRIDGE::new().enable_step_control(pins(&state));
+        let mut ctrl = MontionCtrl::new(driver, Sim::new().convert());
+        ctrl.set_step_mode(StepMode2::M2).unwrap();
+        assert_eq!(ctrl.driver_mut().microsteps, 2);
+
+        let driver = HBRIDGE::new()
+            .enable_step_control(pins(&state))
+            .enable_step_mode_control((MockPwm(0), MockPwm(0)));
+        let mut ctrl = MontionCtrl::new(driver, Sim::new().convert());
+        ctrl.set_step_mode(StepMode32::M16).unwrap();
+        assert_eq!(ctrl.driver_mut().microsteps, 16);
+    }
+}
@@ -0,0 +1,189 @@
+//! LV8729 Driver
+//!
+//! ON Semi LV8729, up to 1/128 microstep. its mode pins MD1-MD3 are normal
+//! two-level inputs, unlike the tri-state ones of DRV8834/DRV8880.
+//!
+//! ```rust,ignore
+//!   let driver = LV8729::new()
+//!       .enable_step_mode_control((md1_pin, md2_pin, md3_pin))
+//!       .enable_step_control(step_pin)
+//!       .enable_direction_control(fr_pin);
+//! ```
+
+use embedded_hal::digital::v2::{OutputPin, PinState};
+
+use crate::interfaces::{
+    EnableDirectionControlTrait, EnableStepControlTrait, EnableStepModeControlTrait,
+    SetDirectionTrait, SetStepModeTrait, StepModeResetTrait, StepTrait,
+};
+
+/// The LV8729 driver API
+pub struct LV8729<Mode1, Mode2, Mode3, Step, Dir> {
+    mode1: Mode1,
+    mode2: Mode2,
+    mode3: Mode3,
+    step: Step,
+    dir: Dir,
+}
+
+impl LV8729<(), (), (), (), ()> {
+    /// Create a new instance of `LV8729`
+    pub fn new() -> Self {
+        Self {
+            mode1: (),
+            mode2: (),
+            mode3: (),
+            step: (),
+            dir: (),
+        }
+    }
+}
+
+impl Default for LV8729<(), (), (), (), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Mode1, Mode2, Mode3, Step, Dir, OutputPinError>
+    EnableStepModeControlTrait<(Mode1, Mode2, Mode3)> for LV8729<(), (), (), Step, Dir>
+where
+    Mode1: OutputPin<Error = OutputPinError>,
+    Mode2: OutputPin<Error = OutputPinError>,
+    Mode3: OutputPin<Error = OutputPinError>,
+{
+    type WithStepModeControl = LV8729<Mode1, Mode2, Mode3, Step, Dir>;
+
+    fn enable_step_mode_control(
+        self,
+        (mode1, mode2, mode3): (Mode1, Mode2, Mode3),
+    ) -> Self::WithStepModeControl {
+        LV8729 {
+            mode1,
+            mode2,
+            mode3,
+            step: self.step,
+            dir: self.dir,
+        }
+    }
+}
+
+impl<Mode1, Mode2, Mode3, Step, Dir, OutputPinError> SetStepModeTrait
+    for LV8729<Mode1, Mode2, Mode3, Step, Dir>
+where
+    Mode1: OutputPin<Error = OutputPinError>,
+    Mode2: OutputPin<Error = OutputPinError>,
+    Mode3: OutputPin<Error = OutputPinError>,
+{
+    // the datasheet gives no mode pin timing, hold them a STEP pulse length
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(1000);
+    const HOLD_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(1000);
+
+    type Error = OutputPinError;
+    type StepMode = crate::step_mode::StepMode128;
+
+    fn apply_mode_config(&mut self, step_mode: Self::StepMode) -> Result<(), Self::Error> {
+        use crate::step_mode::StepMode128::*;
+        use PinState::*;
+        let (mode1, mode2, mode3) = match step_mode {
+            Full => (Low, Low, Low),
+            M2 => (High, Low, Low),
+            M4 => (Low, High, Low),
+            M8 => (High, High, Low),
+            M16 => (Low, Low, High),
+            M32 => (High, Low, High),
+            M64 => (Low, High, High),
+            M128 => (High, High, High),
+        };
+
+        self.mode1.set_state(mode1)?;
+        self.mode2.set_state(mode2)?;
+        self.mode3.set_state(mode3)
+    }
+}
+
+// the mode pins apply without reset
+impl<Mode1, Mode2, Mode3, Step, Dir> StepModeResetTrait for LV8729<Mode1, Mode2, Mode3, Step, Dir> {
+    type ModeResetError = core::convert::Infallible;
+}
+
+impl<Mode1, Mode2, Mode3, Step, Dir, OutputPinError> EnableDirectionControlTrait<Dir>
+    for LV8729<Mode1, Mode2, Mode3, Step, ()>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    type WithDirectionControl = LV8729<Mode1, Mode2, Mode3, Step, Dir>;
+
+    fn enable_direction_control(self, dir: Dir) -> Self::WithDirectionControl {
+        LV8729 {
+            mode1: self.mode1,
+            mode2: self.mode2,
+            mode3: self.mode3,
+            step: self.step,
+            dir,
+        }
+    }
+}
+
+impl<Mode1, Mode2, Mode3, Step, Dir, OutputPinError> SetDirectionTrait
+    for LV8729<Mode1, Mode2, Mode3, Step, Dir>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    // FR to STEP setup time
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(500);
+
+    type Dir = Dir;
+    type Error = OutputPinError;
+
+    fn set_backward(&mut self) -> Result<(), Self::Error> {
+        self.dir.set_high()
+    }
+    fn set_forward(&mut self) -> Result<(), Self::Error> {
+        self.dir.set_low()
+    }
+    #[inline(always)]
+    fn dir_pin(&mut self) -> &mut Self::Dir {
+        &mut self.dir
+    }
+}
+
+impl<Mode1, Mode2, Mode3, Step, Dir, OutputPinError> EnableStepControlTrait<Step>
+    for LV8729<Mode1, Mode2, Mode3, (), Dir>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    type WithStepControl = LV8729<Mode1, Mode2, Mode3, Step, Dir>;
+
+    fn enable_step_control(self, step: Step) -> Self::WithStepControl {
+        LV8729 {
+            mode1: self.mode1,
+            mode2: self.mode2,
+            mode3: self.mode3,
+            step,
+            dir: self.dir,
+        }
+    }
+}
+
+impl<Mode1, Mode2, Mode3, Step, Dir, OutputPinError> StepTrait
+    for LV8729<Mode1, Mode2, Mode3, Step, Dir>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    // STEP min pulse width
+    const PULSE_LENGTH: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(1000);
+
+    type Step = Step;
+    type Error = OutputPinError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.step.set_high()
+    }
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.step.set_low()
+    }
+    fn setp_pin(&mut self) -> &mut Self::Step {
+        &mut self.step
+    }
+}
@@ -1,5 +1,53 @@
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::OutputPin;
+
+#[macro_use]
+mod tri_state;
 
 pub mod drv8825;
+pub mod drv8834;
+pub mod drv8880;
+pub mod hbridge;
+pub mod lv8729;
+pub mod puldirena;
 pub mod stspin220;
 pub mod a4988;
-pub mod soft;
\ No newline at end of file
+pub mod soft;
+pub mod tmc2209;
+pub mod tmc5160;
+pub mod uln2003;
+
+/// STEP/DIR pin of the drivers that have no such pin, e.g. the ones
+/// commutated by software. it does nothing
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoPin;
+
+impl OutputPin for NoPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Output pin of the inverted sense, e.g. a signal wired to the cathode(-)
+/// of the opto coupler of an industrial driver. `set_high` drives it low
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Inverted<Pin>(pub Pin);
+
+impl<Pin: OutputPin> OutputPin for Inverted<Pin> {
+    type Error = Pin::Error;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low()
+    }
+}
@@ -0,0 +1,255 @@
+//! PUL/DIR/ENA Driver
+//!
+//! industrial drivers with opto-isolated inputs, e.g. TB6600, DM542, DM432C.
+//! it is [SOFT] plus the ENA input, the timings are const generics:
+//!
+//! - SETUP: DIR ahead of PUL, unit is ns
+//! - PULSE: PUL high/low width, unit is ns
+//! - ENABLE: ENA ahead of PUL(and DIR), unit is ns
+//!
+//! the sense of a signal depends on the wiring of its opto coupler. the
+//! driver sets PUL/DIR high for a pulse/backward and ENA high to disable,
+//! which is right when the MCU drives the anode(+) and the cathode(-) is at
+//! GND. wrap the pin with [Inverted](crate::Inverted) if the anode is at VCC
+//! and the MCU drives the cathode(-):
+//!
+//! ```rust,ignore
+//!   let driver = DM542::new()
+//!       .enable_step_control(Inverted(pul_pin))
+//!       .enable_direction_control(dir_pin)
+//!       .enable_output_control(Inverted(ena_pin));
+//! ```
+//!
+//! the presets are from the timing diagrams of their manuals, use
+//! `PULDIRENA::<_, _, _, SETUP, PULSE, ENABLE>` for others.
+
+use embedded_hal::digital::v2::OutputPin;
+
+use super::soft::SOFT;
+use crate::interfaces::{
+    EnableDirectionControlTrait, EnableOutputControlTrait, EnableStepControlTrait,
+    OutputEnableTrait, SetDirectionTrait, StepTrait,
+};
+
+/// TB6600: DIR 5us, PUL 2.5us, ENA 5us
+pub type TB6600<Step, Dir, Ena> = PULDIRENA<Step, Dir, Ena, 5000, 2500, 5000>;
+/// DM542: DIR 5us, PUL 2.5us, ENA 5us
+pub type DM542<Step, Dir, Ena> = PULDIRENA<Step, Dir, Ena, 5000, 2500, 5000>;
+/// DM432C: DIR 5us, PUL 2.5us, ENA 5us
+pub type DM432C<Step, Dir, Ena> = PULDIRENA<Step, Dir, Ena, 5000, 2500, 5000>;
+
+/// The PUL/DIR/ENA driver API, refer the module doc
+pub struct PULDIRENA<Step, Dir, Ena, const SETUP: u32, const PULSE: u32, const ENABLE: u32> {
+    soft: SOFT<Step, Dir, SETUP, PULSE>,
+    ena: Ena,
+}
+
+impl<const SETUP: u32, const PULSE: u32, const ENABLE: u32>
+    PULDIRENA<(), (), (), SETUP, PULSE, ENABLE>
+{
+    /// Create a new instance of `PULDIRENA`
+    pub fn new() -> Self {
+        Self {
+            soft: SOFT::new(),
+            ena: (),
+        }
+    }
+}
+
+impl<const SETUP: u32, const PULSE: u32, const ENABLE: u32> Default
+    for PULDIRENA<(), (), (), SETUP, PULSE, ENABLE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Step, Dir, Ena, OutputPinError, const SETUP: u32, const PULSE: u32, const ENABLE: u32>
+    EnableStepControlTrait<Step> for PULDIRENA<(), Dir, Ena, SETUP, PULSE, ENABLE>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    type WithStepControl = PULDIRENA<Step, Dir, Ena, SETUP, PULSE, ENABLE>;
+
+    fn enable_step_control(self, step: Step) -> Self::WithStepControl {
+        PULDIRENA {
+            soft: self.soft.enable_step_control(step),
+            ena: self.ena,
+        }
+    }
+}
+
+impl<Step, Dir, Ena, OutputPinError, const SETUP: u32, const PULSE: u32, const ENABLE: u32>
+    StepTrait for PULDIRENA<Step, Dir, Ena, SETUP, PULSE, ENABLE>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    const PULSE_LENGTH: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(PULSE as u64);
+
+    type Step = Step;
+    type Error = OutputPinError;
+
+    #[inline(always)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.soft.set_high()
+    }
+    #[inline(always)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.soft.set_low()
+    }
+    #[inline(always)]
+    fn setp_pin(&mut self) -> &mut Self::Step {
+        self.soft.setp_pin()
+    }
+}
+
+impl<Step, Dir, Ena, OutputPinError, const SETUP: u32, const PULSE: u32, const ENABLE: u32>
+    EnableDirectionControlTrait<Dir> for PULDIRENA<Step, (), Ena, SETUP, PULSE, ENABLE>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    type WithDirectionControl = PULDIRENA<Step, Dir, Ena, SETUP, PULSE, ENABLE>;
+
+    fn enable_direction_control(self, dir: Dir) -> Self::WithDirectionControl {
+        PULDIRENA {
+            soft: self.soft.enable_direction_control(dir),
+            ena: self.ena,
+        }
+    }
+}
+
+impl<Step, Dir, Ena, OutputPinError, const SETUP: u32, const PULSE: u32, const ENABLE: u32>
+    SetDirectionTrait for PULDIRENA<Step, Dir, Ena, SETUP, PULSE, ENABLE>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(SETUP as u64);
+
+    type Dir = Dir;
+    type Error = OutputPinError;
+
+    fn set_backward(&mut self) -> Result<(), Self::Error> {
+        self.soft.set_backward()
+    }
+    fn set_forward(&mut self) -> Result<(), Self::Error> {
+        self.soft.set_forward()
+    }
+    #[inline(always)]
+    fn dir_pin(&mut self) -> &mut Self::Dir {
+        self.soft.dir_pin()
+    }
+}
+
+impl<Step, Dir, Ena, OutputPinError, const SETUP: u32, const PULSE: u32, const ENABLE: u32>
+    EnableOutputControlTrait<Ena> for PULDIRENA<Step, Dir, (), SETUP, PULSE, ENABLE>
+where
+    Ena: OutputPin<Error = OutputPinError>,
+{
+    type WithOutputControl = PULDIRENA<Step, Dir, Ena, SETUP, PULSE, ENABLE>;
+
+    fn enable_output_control(self, ena: Ena) -> Self::WithOutputControl {
+        PULDIRENA {
+            soft: self.soft,
+            ena,
+        }
+    }
+}
+
+impl<Step, Dir, Ena, OutputPinError, const SETUP: u32, const PULSE: u32, const ENABLE: u32>
+    OutputEnableTrait for PULDIRENA<Step, Dir, Ena, SETUP, PULSE, ENABLE>
+where
+    Ena: OutputPin<Error = OutputPinError>,
+{
+    const ENABLE_SETUP_TIME: fugit::NanosDurationU64 =
+        fugit::NanosDurationU64::from_ticks(ENABLE as u64);
+
+    type Error = OutputPinError;
+
+    /// release ENA, the opto coupler is off
+    fn enable_output(&mut self) -> Result<(), Self::Error> {
+        self.ena.set_low()
+    }
+
+    /// assert ENA, the driver de-energizes the motor
+    fn disable_output(&mut self) -> Result<(), Self::Error> {
+        self.ena.set_high()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim, SimPin};
+    use crate::{Inverted, MontionCtrl, MotionControlTrait, Num};
+    use embedded_hal::digital::v2::PinState;
+
+    fn accel() -> Num {
+        Num::from_num(10_000)
+    }
+
+    fn velocity() -> Num {
+        Num::from_num(1000)
+    }
+
+    fn levels(sim: &Sim, signal: Signal) -> Vec<PinState> {
+        sim.transitions_of(signal).iter().map(|t| t.level).collect()
+    }
+
+    #[test]
+    fn tb6600_should_hold_ena_and_dir_ahead_of_pul() {
+        let sim = Sim::new();
+        let driver: TB6600<SimPin, SimPin, SimPin> = TB6600::new()
+            .enable_step_control(sim.pin(Signal::Step))
+            .enable_direction_control(sim.pin(Signal::Dir))
+            .enable_output_control(sim.pin(Signal::Enable));
+        let mut ctrl = MontionCtrl::new(driver, sim.convert());
+
+        ctrl.enable_motor().unwrap();
+        ctrl.move_to_position(accel(), velocity(), 3).unwrap();
+        ctrl.move_to_position(accel(), velocity(), 1).unwrap();
+        ctrl.disable_motor().unwrap();
+
+        let ena = sim.transitions_of(Signal::Enable);
+        let dir = sim.transitions_of(Signal::Dir);
+        let pul = sim.pulses(Signal::Step);
+        assert_eq!(
+            levels(&sim, Signal::Enable),
+            [PinState::Low, PinState::High]
+        );
+        assert_eq!(levels(&sim, Signal::Dir), [PinState::Low, PinState::High]);
+        assert_eq!(pul.len(), 5);
+
+        // ENA 5us ahead of DIR, DIR 5us ahead of PUL
+        assert!(dir[0].time - ena[0].time >= 5000);
+        assert!(pul[0].0 - dir[0].time >= 5000);
+        let reversed = pul.iter().find(|&&(rise, _)| rise > dir[1].time).unwrap();
+        assert!(reversed.0 - dir[1].time >= 5000);
+        assert!(pul.iter().all(|&(_, width)| width >= 2500));
+        assert_eq!(sim.position(), 1);
+    }
+
+    #[test]
+    fn inverted_should_flip_the_sense() {
+        let sim = Sim::new();
+        let driver: DM542<Inverted<SimPin>, Inverted<SimPin>, Inverted<SimPin>> = DM542::new()
+            .enable_step_control(Inverted(sim.pin(Signal::Step)))
+            .enable_direction_control(Inverted(sim.pin(Signal::Dir)))
+            .enable_output_control(Inverted(sim.pin(Signal::Enable)));
+        let mut ctrl = MontionCtrl::new(driver, sim.convert());
+
+        ctrl.enable_motor().unwrap();
+        ctrl.move_to_position(accel(), velocity(), 2).unwrap();
+        ctrl.disable_motor().unwrap();
+
+        assert_eq!(
+            levels(&sim, Signal::Enable),
+            [PinState::High, PinState::Low]
+        );
+        assert_eq!(levels(&sim, Signal::Dir), [PinState::High]);
+        // each pulse is a low level
+        assert_eq!(
+            levels(&sim, Signal::Step),
+            [PinState::Low, PinState::High, PinState::Low, PinState::High]
+        );
+    }
+}
@@ -27,6 +27,12 @@ impl<const STEUP: u32, const PULLEN: u32> SOFT<(), (), STEUP, PULLEN> {
     }
 }
 
+impl<const STEUP: u32, const PULLEN: u32> Default for SOFT<(), (), STEUP, PULLEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<Step, Dir, OutputPinError, const STEUP: u32, const PULLEN: u32>
     EnableDirectionControlTrait<Dir> for SOFT<Step, (), STEUP, PULLEN>
 where
@@ -105,3 +111,183 @@ where
         &mut self.step
     }    
 }
+
+/// The SOFT driver with timing set at runtime, e.g. tuned with an
+/// oscilloscope or loaded from EEPROM
+///
+/// the motion control reads the timing by [SetDirectionTrait::setup_time] and
+/// [StepTrait::pulse_length], the associated consts are 0 and not used.
+///
+/// ```rust,ignore
+///   let driver = SOFTRT::new(5000.nanos(), 2500.nanos())
+///       .enable_step_control(step_pin)
+///       .enable_direction_control(dir_pin);
+/// ```
+pub struct SOFTRT<Step, Dir> {
+    step: Step,
+    dir: Dir,
+    setup_time: fugit::NanosDurationU64,
+    pulse_length: fugit::NanosDurationU64,
+}
+
+impl SOFTRT<(), ()> {
+    /// Create a new instance of `SOFTRT`, setup_time is the DIR setup time,
+    /// pulse_length is the PULSE length
+    pub fn new(setup_time: fugit::NanosDurationU64, pulse_length: fugit::NanosDurationU64) -> Self {
+        Self {
+            step: (),
+            dir: (),
+            setup_time,
+            pulse_length,
+        }
+    }
+}
+
+impl<Step, Dir> SOFTRT<Step, Dir> {
+    /// change the timing, it applies to the next step
+    pub fn set_timing(
+        &mut self,
+        setup_time: fugit::NanosDurationU64,
+        pulse_length: fugit::NanosDurationU64,
+    ) {
+        self.setup_time = setup_time;
+        self.pulse_length = pulse_length;
+    }
+}
+
+impl<Step, Dir, OutputPinError> EnableDirectionControlTrait<Dir> for SOFTRT<Step, ()>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    type WithDirectionControl = SOFTRT<Step, Dir>;
+
+    fn enable_direction_control(self, dir: Dir) -> Self::WithDirectionControl {
+        SOFTRT {
+            step: self.step,
+            dir,
+            setup_time: self.setup_time,
+            pulse_length: self.pulse_length,
+        }
+    }
+}
+
+impl<Step, Dir, OutputPinError> SetDirectionTrait for SOFTRT<Step, Dir>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Dir = Dir;
+    type Error = OutputPinError;
+
+    fn set_backward(&mut self) -> Result<(), Self::Error> {
+        self.dir.set_high()
+    }
+    fn set_forward(&mut self) -> Result<(), Self::Error> {
+        self.dir.set_low()
+    }
+    #[inline(always)]
+    fn dir_pin(&mut self) -> &mut Self::Dir {
+        &mut self.dir
+    }
+    fn setup_time(&self) -> fugit::NanosDurationU64 {
+        self.setup_time
+    }
+}
+
+impl<Step, Dir, OutputPinError> EnableStepControlTrait<Step> for SOFTRT<(), Dir>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    type WithStepControl = SOFTRT<Step, Dir>;
+
+    fn enable_step_control(self, step: Step) -> Self::WithStepControl {
+        SOFTRT {
+            step,
+            dir: self.dir,
+            setup_time: self.setup_time,
+            pulse_length: self.pulse_length,
+        }
+    }
+}
+
+impl<Step, Dir, OutputPinError> StepTrait for SOFTRT<Step, Dir>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    const PULSE_LENGTH: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Step = Step;
+    type Error = OutputPinError;
+
+    #[inline(always)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.step.set_high()
+    }
+    #[inline(always)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.step.set_low()
+    }
+    #[inline(always)]
+    fn setp_pin(&mut self) -> &mut Self::Step {
+        &mut self.step
+    }
+    fn pulse_length(&self) -> fugit::NanosDurationU64 {
+        self.pulse_length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim};
+    use crate::{MontionCtrl, MotionControlTrait, MultiAxisCtrl, Num};
+    use fugit::ExtU64;
+
+    fn accel() -> Num {
+        Num::from_num(10_000)
+    }
+
+    fn velocity() -> Num {
+        Num::from_num(1000)
+    }
+
+    #[test]
+    fn softrt_should_use_the_runtime_timing() {
+        let sim = Sim::new();
+        let driver = SOFTRT::new(3000.nanos(), 1500.nanos())
+            .enable_step_control(sim.pin(Signal::Step))
+            .enable_direction_control(sim.pin(Signal::Dir));
+        let mut ctrl = MontionCtrl::new(driver, sim.convert());
+
+        ctrl.move_to_position(accel(), velocity(), 2).unwrap();
+        let dir = sim.transitions_of(Signal::Dir)[0];
+        let pulses = sim.pulses(Signal::Step);
+        assert!(pulses[0].0 - dir.time >= 3000);
+        assert!(pulses.iter().all(|&(_, width)| width == 1500));
+
+        sim.clear();
+        ctrl.driver_mut().set_timing(6000.nanos(), 4000.nanos());
+        ctrl.move_to_position(accel(), velocity(), 0).unwrap();
+        let dir = sim.transitions_of(Signal::Dir)[0];
+        let pulses = sim.pulses(Signal::Step);
+        assert!(pulses[0].0 - dir.time >= 6000);
+        assert!(pulses.iter().all(|&(_, width)| width == 4000));
+    }
+
+    #[test]
+    fn multi_axis_should_use_the_longest_runtime_timing() {
+        let sim_x = Sim::new();
+        let sim_y = sim_x.axis();
+        let y = SOFTRT::new(8000.nanos(), 5000.nanos())
+            .enable_step_control(sim_y.pin(Signal::Step))
+            .enable_direction_control(sim_y.pin(Signal::Dir));
+        let mut ctrl = MultiAxisCtrl::new((sim_x.a4988(), y), sim_x.convert());
+
+        ctrl.move_to_position(accel(), velocity(), [2, 2]).unwrap();
+        let dir = sim_y.transitions_of(Signal::Dir)[0];
+        let pulses = sim_x.pulses(Signal::Step);
+        assert!(pulses[0].0 - dir.time >= 8000);
+        assert!(pulses.iter().all(|&(_, width)| width == 5000));
+    }
+}
@@ -2,11 +2,12 @@
 //!
 // use core::convert::Infallible;
 
-use embedded_hal::digital::v2::{OutputPin, PinState};
+use embedded_hal::digital::v2::{InputPin, OutputPin, PinState};
 
 use crate::interfaces::{
-    EnableDirectionControlTrait, EnableResetControlTrait, EnableStepControlTrait,
-    EnableStepModeControlTrait, ResetTrait, SetDirectionTrait, SetStepModeTrait, StepTrait,
+    EnableDirectionControlTrait, EnableOutputControlTrait, EnableResetControlTrait,
+    EnableStepControlTrait, EnableStepModeControlTrait, FaultTrait, OutputEnableTrait, ResetTrait,
+    SetDirectionTrait, SetStepModeTrait, StepTrait,
 };
 
 /// The STSPIN220 driver API
@@ -34,6 +35,12 @@ impl STSPIN220<(), (), (), (), (), ()> {
     }
 }
 
+impl Default for STSPIN220<(), (), (), (), (), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<EnableFault, StandbyReset, Mode1, Mode2, StepMode3, DirMode4, OutputPinError>
     EnableStepModeControlTrait<( Mode1, Mode2)>
     for STSPIN220<EnableFault, StandbyReset, (), (), StepMode3, DirMode4>
@@ -229,3 +236,83 @@ where
     }
 
 }
+
+//////////////////////////////////////////////////
+/// The EN/FAULT pin of STSPIN220
+///
+/// it is a bidirectional open-drain pin: the app pulls it low to disable the
+/// power stage, the chip pulls it low on overcurrent/thermal shutdown. so the
+/// pin must be configured open-drain with pull-up, implementing both
+/// `OutputPin` and `InputPin`. a low level is regarded as fault only while the
+/// power stage is enabled by [OutputEnableTrait::enable_output].
+pub struct EnableFaultPin<Pin> {
+    pin: Pin,
+    enabled: bool,
+}
+
+impl<Pin> EnableFaultPin<Pin> {
+    pub fn release(self) -> Pin {
+        self.pin
+    }
+}
+
+impl<EnableFault, StandbyReset, Mode1, Mode2, StepMode3, DirMode4, PinError>
+    EnableOutputControlTrait<EnableFault>
+    for STSPIN220<(), StandbyReset, Mode1, Mode2, StepMode3, DirMode4>
+where
+    EnableFault: OutputPin<Error = PinError> + InputPin<Error = PinError>,
+{
+    type WithOutputControl =
+        STSPIN220<EnableFaultPin<EnableFault>, StandbyReset, Mode1, Mode2, StepMode3, DirMode4>;
+
+    fn enable_output_control(self, enable_fault: EnableFault) -> Self::WithOutputControl {
+        STSPIN220 {
+            enable_fault: EnableFaultPin {
+                pin: enable_fault,
+                enabled: false,
+            },
+            standby_reset: self.standby_reset,
+            mode1: self.mode1,
+            mode2: self.mode2,
+            step_mode3: self.step_mode3,
+            dir_mode4: self.dir_mode4,
+        }
+    }
+}
+
+impl<EnableFault, StandbyReset, Mode1, Mode2, StepMode3, DirMode4, PinError> OutputEnableTrait
+    for STSPIN220<EnableFaultPin<EnableFault>, StandbyReset, Mode1, Mode2, StepMode3, DirMode4>
+where
+    EnableFault: OutputPin<Error = PinError> + InputPin<Error = PinError>,
+{
+    // the datasheet gives none, use the setup time of mode inputs
+    const ENABLE_SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(1_000);
+
+    type Error = PinError;
+
+    fn enable_output(&mut self) -> Result<(), Self::Error> {
+        // release the open-drain line, the pull-up makes it high
+        self.enable_fault.pin.set_high()?;
+        self.enable_fault.enabled = true;
+        Ok(())
+    }
+    fn disable_output(&mut self) -> Result<(), Self::Error> {
+        self.enable_fault.enabled = false;
+        self.enable_fault.pin.set_low()
+    }
+}
+
+impl<EnableFault, StandbyReset, Mode1, Mode2, StepMode3, DirMode4, PinError> FaultTrait
+    for STSPIN220<EnableFaultPin<EnableFault>, StandbyReset, Mode1, Mode2, StepMode3, DirMode4>
+where
+    EnableFault: OutputPin<Error = PinError> + InputPin<Error = PinError>,
+{
+    type Error = PinError;
+
+    fn is_fault(&mut self) -> Result<bool, Self::Error> {
+        if !self.enable_fault.enabled {
+            return Ok(false);
+        }
+        self.enable_fault.pin.is_low()
+    }
+}
@@ -0,0 +1,549 @@
+//! TMC2209 Driver
+//!
+//! STEP/DIR stepping plus the single-wire UART register interface, which sets
+//! the current, the microstep resolution(MRES) and StealthChop, and reads
+//! DRV_STATUS. the UART is `embedded_hal::serial`, 8N1, the TX and RX are
+//! tied to the PDN_UART pin, so every sent byte is echoed back and dropped.
+//!
+//! ```rust,ignore
+//!   let driver = TMC2209::new()
+//!       .enable_step_mode_control((uart, 0))
+//!       .enable_step_control(step_pin)
+//!       .enable_direction_control(dir_pin);
+//! ```
+//!
+//! datagrams(datasheet 4.1), the CRC8 polynomial is x^8 + x^2 + x + 1:
+//!
+//! ```text
+//!   write:        0x05 | node | reg|0x80 | data(4, msb first) | crc
+//!   read request: 0x05 | node | reg      | crc
+//!   read reply:   0x05 | 0xff | reg      | data(4, msb first) | crc
+//! ```
+
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::serial;
+
+use crate::interfaces::{
+    EnableDirectionControlTrait, EnableStepControlTrait, EnableStepModeControlTrait,
+    SetDirectionTrait, SetStepModeTrait, StallTrait, StepModeResetTrait, StepTrait,
+};
+use crate::step_mode::StepMode256;
+
+/// register addresses
+pub mod reg {
+    pub const GCONF: u8 = 0x00;
+    pub const GSTAT: u8 = 0x01;
+    pub const IFCNT: u8 = 0x02;
+    pub const IOIN: u8 = 0x06;
+    pub const IHOLD_IRUN: u8 = 0x10;
+    pub const TPOWERDOWN: u8 = 0x11;
+    pub const TPWMTHRS: u8 = 0x13;
+    pub const TCOOLTHRS: u8 = 0x14;
+    pub const SGTHRS: u8 = 0x40;
+    pub const SG_RESULT: u8 = 0x41;
+    pub const COOLCONF: u8 = 0x42;
+    pub const CHOPCONF: u8 = 0x6C;
+    pub const DRV_STATUS: u8 = 0x6F;
+    pub const PWMCONF: u8 = 0x70;
+}
+
+const SYNC: u8 = 0x05;
+const MASTER_ADDRESS: u8 = 0xFF;
+const WRITE: u8 = 0x80;
+
+// GCONF bits
+const I_SCALE_ANALOG: u32 = 1 << 0;
+const EN_SPREADCYCLE: u32 = 1 << 2;
+const PDN_DISABLE: u32 = 1 << 6;
+const MSTEP_REG_SELECT: u32 = 1 << 7;
+// keep VREF as current reference like reset, and let UART control the
+// PDN_UART pin and the microstep resolution
+const GCONF_DEFAULT: u32 = I_SCALE_ANALOG | PDN_DISABLE | MSTEP_REG_SELECT;
+
+// CHOPCONF reset default of the datasheet, MRES is bits 24-27
+const CHOPCONF_DEFAULT: u32 = 0x1000_0053;
+const MRES_SHIFT: u32 = 24;
+const MRES_MASK: u32 = 0xF << MRES_SHIFT;
+
+/// polls of the UART before a missing reply is regarded as timeout
+const READ_POLLS: u32 = 100_000;
+
+/// Error of the UART register interface
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UartError<E> {
+    /// Error of the serial port
+    Serial(E),
+    /// the reply has bad CRC
+    Crc,
+    /// the reply has bad sync byte, address or register
+    Frame,
+    /// the driver did not reply
+    Timeout,
+}
+
+/// CRC8 of a datagram(without its crc byte), refer datasheet 4.2
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        let mut byte = byte;
+        for _ in 0..8 {
+            crc = if (crc >> 7) ^ (byte & 1) != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+/// the write datagram of a register
+pub fn write_datagram(node: u8, register: u8, value: u32) -> [u8; 8] {
+    let data = value.to_be_bytes();
+    let mut out = [
+        SYNC,
+        node,
+        register | WRITE,
+        data[0],
+        data[1],
+        data[2],
+        data[3],
+        0,
+    ];
+    out[7] = crc8(&out[..7]);
+    out
+}
+
+/// the read request datagram of a register
+pub fn read_datagram(node: u8, register: u8) -> [u8; 4] {
+    let mut out = [SYNC, node, register, 0];
+    out[3] = crc8(&out[..3]);
+    out
+}
+
+/// check the reply datagram of a read request, return the register value
+pub fn parse_reply<E>(register: u8, reply: &[u8; 8]) -> Result<u32, UartError<E>> {
+    if crc8(&reply[..7]) != reply[7] {
+        return Err(UartError::Crc);
+    }
+    if reply[0] & 0x0F != SYNC || reply[1] != MASTER_ADDRESS || reply[2] != register {
+        return Err(UartError::Frame);
+    }
+    Ok(u32::from_be_bytes([reply[3], reply[4], reply[5], reply[6]]))
+}
+
+/// Decoded DRV_STATUS register
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DrvStatus {
+    /// overtemperature prewarning
+    pub otpw: bool,
+    /// overtemperature shutdown
+    pub ot: bool,
+    /// short to ground of phase A/B
+    pub s2ga: bool,
+    pub s2gb: bool,
+    /// low side short of phase A/B
+    pub s2vsa: bool,
+    pub s2vsb: bool,
+    /// open load of phase A/B
+    pub ola: bool,
+    pub olb: bool,
+    /// temperature thresholds 120/143/150/157 C are exceeded
+    pub t120: bool,
+    pub t143: bool,
+    pub t150: bool,
+    pub t157: bool,
+    /// actual current scale, 0-31
+    pub cs_actual: u8,
+    /// the driver runs in StealthChop mode
+    pub stealth: bool,
+    /// standstill
+    pub stst: bool,
+}
+
+impl From<u32> for DrvStatus {
+    fn from(value: u32) -> Self {
+        let bit = |n: u32| value & (1 << n) != 0;
+        Self {
+            otpw: bit(0),
+            ot: bit(1),
+            s2ga: bit(2),
+            s2gb: bit(3),
+            s2vsa: bit(4),
+            s2vsb: bit(5),
+            ola: bit(6),
+            olb: bit(7),
+            t120: bit(8),
+            t143: bit(9),
+            t150: bit(10),
+            t157: bit(11),
+            cs_actual: ((value >> 16) & 0x1F) as u8,
+            stealth: bit(30),
+            stst: bit(31),
+        }
+    }
+}
+
+/// The TMC2209 driver API
+///
+/// the UART is attached with its node address(0-3, set by MS1/MS2 pins) by
+/// [EnableStepModeControlTrait]. the driver keeps a copy of the written
+/// GCONF/CHOPCONF/SGTHRS, so that a field can be changed without reading them
+/// back.
+pub struct TMC2209<Uart, Step, Dir> {
+    uart: Uart,
+    node: u8,
+    gconf: u32,
+    chopconf: u32,
+    sgthrs: u8,
+    step: Step,
+    dir: Dir,
+}
+
+impl TMC2209<(), (), ()> {
+    /// Create a new instance of `TMC2209`
+    pub fn new() -> Self {
+        Self {
+            uart: (),
+            node: 0,
+            gconf: GCONF_DEFAULT,
+            chopconf: CHOPCONF_DEFAULT,
+            sgthrs: 0,
+            step: (),
+            dir: (),
+        }
+    }
+}
+
+impl Default for TMC2209<(), (), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Uart, Step, Dir, E> EnableStepModeControlTrait<(Uart, u8)> for TMC2209<(), Step, Dir>
+where
+    Uart: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+{
+    type WithStepModeControl = TMC2209<Uart, Step, Dir>;
+
+    fn enable_step_mode_control(self, (uart, node): (Uart, u8)) -> Self::WithStepModeControl {
+        TMC2209 {
+            uart,
+            node,
+            gconf: self.gconf,
+            chopconf: self.chopconf,
+            sgthrs: self.sgthrs,
+            step: self.step,
+            dir: self.dir,
+        }
+    }
+}
+
+impl<Uart, Step, Dir, E> TMC2209<Uart, Step, Dir>
+where
+    Uart: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+{
+    fn send(&mut self, datagram: &[u8]) -> Result<(), UartError<E>> {
+        for &byte in datagram {
+            nb::block!(self.uart.write(byte)).map_err(UartError::Serial)?;
+        }
+        nb::block!(self.uart.flush()).map_err(UartError::Serial)?;
+        // drop the echo of the single wire
+        let mut echo = [0u8; 8];
+        self.receive(&mut echo[..datagram.len()])
+    }
+
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<(), UartError<E>> {
+        for byte in buffer.iter_mut() {
+            let mut polls = 0;
+            *byte = loop {
+                match self.uart.read() {
+                    Ok(byte) => break byte,
+                    Err(nb::Error::WouldBlock) if polls < READ_POLLS => polls += 1,
+                    Err(nb::Error::WouldBlock) => return Err(UartError::Timeout),
+                    Err(nb::Error::Other(e)) => return Err(UartError::Serial(e)),
+                }
+            };
+        }
+        Ok(())
+    }
+
+    pub fn write_register(&mut self, register: u8, value: u32) -> Result<(), UartError<E>> {
+        self.send(&write_datagram(self.node, register, value))
+    }
+
+    pub fn read_register(&mut self, register: u8) -> Result<u32, UartError<E>> {
+        self.send(&read_datagram(self.node, register))?;
+        let mut reply = [0u8; 8];
+        self.receive(&mut reply)?;
+        parse_reply(register, &reply)
+    }
+
+    /// set the run/hold current scale(0-31, 31 is full scale), and the
+    /// delay(0-15, unit is 2^18 clocks) to reduce from run to hold current
+    pub fn set_current(&mut self, run: u8, hold: u8, hold_delay: u8) -> Result<(), UartError<E>> {
+        let value = (hold as u32 & 0x1F)
+            | ((run as u32 & 0x1F) << 8)
+            | ((hold_delay as u32 & 0x0F) << 16);
+        self.write_register(reg::IHOLD_IRUN, value)
+    }
+
+    /// StealthChop(quiet) if true, otherwise SpreadCycle
+    pub fn set_stealthchop(&mut self, enable: bool) -> Result<(), UartError<E>> {
+        let gconf = if enable {
+            self.gconf & !EN_SPREADCYCLE
+        } else {
+            self.gconf | EN_SPREADCYCLE
+        };
+        self.write_register(reg::GCONF, gconf)?;
+        self.gconf = gconf;
+        Ok(())
+    }
+
+    pub fn read_drv_status(&mut self) -> Result<DrvStatus, UartError<E>> {
+        self.read_register(reg::DRV_STATUS).map(DrvStatus::from)
+    }
+
+    /// set the StallGuard threshold(SGTHRS), a stall is reported when
+    /// SG_RESULT falls to 2 * SGTHRS, 0 disables it. StallGuard works only in
+    /// StealthChop and above the velocity of TCOOLTHRS.
+    pub fn set_stall_threshold(&mut self, sgthrs: u8) -> Result<(), UartError<E>> {
+        self.write_register(reg::SGTHRS, sgthrs as u32)?;
+        self.sgthrs = sgthrs;
+        Ok(())
+    }
+}
+
+impl<Uart, Step, Dir, E> StallTrait for TMC2209<Uart, Step, Dir>
+where
+    Uart: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+{
+    type Error = UartError<E>;
+
+    /// poll SG_RESULT, like the DIAG output does
+    fn is_stalled(&mut self) -> Result<bool, Self::Error> {
+        if self.sgthrs == 0 {
+            return Ok(false);
+        }
+        let sg_result = self.read_register(reg::SG_RESULT)? & 0x3FF;
+        Ok(sg_result <= 2 * self.sgthrs as u32)
+    }
+}
+
+impl<Uart, Step, Dir, E> SetStepModeTrait for TMC2209<Uart, Step, Dir>
+where
+    Uart: serial::Read<u8, Error = E> + serial::Write<u8, Error = E>,
+{
+    // MRES applies at once, no reset is needed
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+    const HOLD_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Error = UartError<E>;
+    type StepMode = StepMode256;
+
+    fn apply_mode_config(&mut self, step_mode: Self::StepMode) -> Result<(), Self::Error> {
+        // MRES is used only if mstep_reg_select is set
+        self.write_register(reg::GCONF, self.gconf)?;
+
+        use StepMode256::*;
+        let mres = match step_mode {
+            M256 => 0,
+            M128 => 1,
+            M64 => 2,
+            M32 => 3,
+            M16 => 4,
+            M8 => 5,
+            M4 => 6,
+            M2 => 7,
+            Full => 8,
+        };
+        let chopconf = (self.chopconf & !MRES_MASK) | (mres << MRES_SHIFT);
+        self.write_register(reg::CHOPCONF, chopconf)?;
+        self.chopconf = chopconf;
+        Ok(())
+    }
+}
+
+// MRES applies at once, no reset
+impl<Uart, Step, Dir> StepModeResetTrait for TMC2209<Uart, Step, Dir> {
+    type ModeResetError = core::convert::Infallible;
+}
+
+impl<Uart, Step, Dir, OutputPinError> EnableDirectionControlTrait<Dir> for TMC2209<Uart, Step, ()>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    type WithDirectionControl = TMC2209<Uart, Step, Dir>;
+
+    fn enable_direction_control(self, dir: Dir) -> Self::WithDirectionControl {
+        TMC2209 {
+            uart: self.uart,
+            node: self.node,
+            gconf: self.gconf,
+            chopconf: self.chopconf,
+            sgthrs: self.sgthrs,
+            step: self.step,
+            dir,
+        }
+    }
+}
+
+impl<Uart, Step, Dir, OutputPinError> SetDirectionTrait for TMC2209<Uart, Step, Dir>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    // tDSU, DIR to STEP setup time
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(20);
+
+    type Dir = Dir;
+    type Error = OutputPinError;
+
+    fn set_backward(&mut self) -> Result<(), Self::Error> {
+        self.dir.set_high()
+    }
+    fn set_forward(&mut self) -> Result<(), Self::Error> {
+        self.dir.set_low()
+    }
+    #[inline(always)]
+    fn dir_pin(&mut self) -> &mut Self::Dir {
+        &mut self.dir
+    }
+}
+
+impl<Uart, Step, Dir, OutputPinError> EnableStepControlTrait<Step> for TMC2209<Uart, (), Dir>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    type WithStepControl = TMC2209<Uart, Step, Dir>;
+
+    fn enable_step_control(self, step: Step) -> Self::WithStepControl {
+        TMC2209 {
+            uart: self.uart,
+            node: self.node,
+            gconf: self.gconf,
+            chopconf: self.chopconf,
+            sgthrs: self.sgthrs,
+            step,
+            dir: self.dir,
+        }
+    }
+}
+
+impl<Uart, Step, Dir, OutputPinError> StepTrait for TMC2209<Uart, Step, Dir>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    // tSH/tSL, min 100ns
+    const PULSE_LENGTH: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(100);
+
+    type Step = Step;
+    type Error = OutputPinError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.step.set_high()
+    }
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.step.set_low()
+    }
+    fn setp_pin(&mut self) -> &mut Self::Step {
+        &mut self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    /// single-wire uart: echoes what is written, then the reply on flush
+    #[derive(Default)]
+    struct MockUart {
+        written: Vec<u8>,
+        rx: VecDeque<u8>,
+        reply: Vec<u8>,
+    }
+
+    impl serial::Read<u8> for MockUart {
+        type Error = ();
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            self.rx.pop_front().ok_or(nb::Error::WouldBlock)
+        }
+    }
+
+    impl serial::Write<u8> for MockUart {
+        type Error = ();
+        fn write(&mut self, word: u8) -> nb::Result<(), ()> {
+            self.written.push(word);
+            self.rx.push_back(word);
+            Ok(())
+        }
+        fn flush(&mut self) -> nb::Result<(), ()> {
+            self.rx.extend(self.reply.drain(..));
+            Ok(())
+        }
+    }
+
+    fn driver() -> TMC2209<MockUart, (), ()> {
+        TMC2209::new().enable_step_mode_control((MockUart::default(), 0))
+    }
+
+    #[test]
+    fn crc8_should_match_datasheet_examples() {
+        // read request of GCONF and IOIN, node 0
+        assert_eq!(read_datagram(0, reg::GCONF), [0x05, 0x00, 0x00, 0x48]);
+        assert_eq!(read_datagram(0, reg::IOIN), [0x05, 0x00, 0x06, 0x6F]);
+    }
+
+    #[test]
+    fn write_datagram_should_frame_register_and_value() {
+        let datagram = write_datagram(3, reg::IHOLD_IRUN, 0x0006_1F0A);
+        assert_eq!(&datagram[..7], &[0x05, 0x03, 0x90, 0x00, 0x06, 0x1F, 0x0A]);
+        assert_eq!(datagram[7], crc8(&datagram[..7]));
+    }
+
+    #[test]
+    fn read_register_should_drop_echo_and_check_reply() {
+        let mut driver = driver();
+        let mut reply = [0x05, 0xFF, reg::DRV_STATUS, 0x80, 0x1F, 0x00, 0x03, 0];
+        reply[7] = crc8(&reply[..7]);
+
+        driver.uart.reply.extend(reply.iter());
+        let status = driver.read_drv_status().unwrap();
+        assert!(status.stst && status.otpw && status.ot && !status.stealth);
+        assert_eq!(status.cs_actual, 31);
+        assert_eq!(driver.uart.written, read_datagram(0, reg::DRV_STATUS));
+
+        reply[7] ^= 1;
+        driver.uart.reply.extend(reply.iter());
+        assert_eq!(driver.read_register(reg::DRV_STATUS), Err(UartError::Crc));
+
+        // no reply
+        assert_eq!(driver.read_register(reg::GCONF), Err(UartError::Timeout));
+    }
+
+    #[test]
+    fn apply_mode_config_should_write_mres() {
+        let mut driver = driver();
+        driver.apply_mode_config(StepMode256::M16).unwrap();
+
+        let written = &driver.uart.written;
+        assert_eq!(written.len(), 16);
+        assert_eq!(written[..8], write_datagram(0, reg::GCONF, GCONF_DEFAULT));
+        assert_eq!(written[8..], write_datagram(0, reg::CHOPCONF, 0x1400_0053));
+    }
+
+    #[test]
+    fn set_step_mode_should_work_through_montionctrl() {
+        use crate::sim::Sim;
+        use crate::{MontionCtrl, MotionControlStepModeTrait};
+
+        let mut ctrl = MontionCtrl::new(driver(), Sim::new().convert());
+        ctrl.set_step_mode(StepMode256::M16).unwrap();
+
+        let written = &ctrl.driver_mut().uart.written;
+        assert_eq!(written[8..], write_datagram(0, reg::CHOPCONF, 0x1400_0053));
+    }
+}
@@ -0,0 +1,740 @@
+//! TMC5160 / TMC2130 Driver
+//!
+//! STEP/DIR stepping plus the SPI register interface, which sets the current,
+//! the microstep resolution(MRES), CoolStep/StallGuard, and reads DRV_STATUS.
+//! both chips use the same SPI datagram and the same addresses of the
+//! registers here, [TMC2130] is an alias. the SPI is
+//! `embedded_hal::blocking::spi::Transfer`, mode 3, with a CS pin.
+//!
+//! ```rust,ignore
+//!   let driver = TMC5160::new()
+//!       .enable_step_mode_control((spi, cs_pin))
+//!       .enable_step_control(step_pin)
+//!       .enable_direction_control(dir_pin);
+//! ```
+//!
+//! datagram(datasheet 4.1), 40 bits, msb first:
+//!
+//! ```text
+//!   sent:     address(bit 7 set to write) | data(4)
+//!   received: SPI_STATUS                  | data(4) of the previous read
+//! ```
+//!
+//! a read sends the address twice, the data comes with the second datagram.
+//! the SPI_STATUS of each response is decoded to [SpiStatus], refer
+//! [TMC5160::status].
+
+use embedded_hal::blocking::spi::Transfer;
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::interfaces::{
+    EnableDirectionControlTrait, EnableStepControlTrait, EnableStepModeControlTrait,
+    SetDirectionTrait, SetStepModeTrait, StallTrait, StepModeResetTrait, StepTrait,
+};
+use crate::step_mode::StepMode256;
+
+/// register addresses
+pub mod reg {
+    pub const GCONF: u8 = 0x00;
+    pub const GSTAT: u8 = 0x01;
+    pub const IOIN: u8 = 0x04;
+    pub const IHOLD_IRUN: u8 = 0x10;
+    pub const TPOWERDOWN: u8 = 0x11;
+    pub const TSTEP: u8 = 0x12;
+    pub const TPWMTHRS: u8 = 0x13;
+    pub const TCOOLTHRS: u8 = 0x14;
+    pub const THIGH: u8 = 0x15;
+    pub const CHOPCONF: u8 = 0x6C;
+    pub const COOLCONF: u8 = 0x6D;
+    pub const DRV_STATUS: u8 = 0x6F;
+    pub const PWMCONF: u8 = 0x70;
+}
+
+const WRITE: u8 = 0x80;
+
+// CHOPCONF of the datasheet quick start: toff 3, hstrt 4, hend 1, tbl 2,
+// MRES 256. the reset value has toff 0, the power stage is off until written
+const CHOPCONF_DEFAULT: u32 = 0x0001_00C3;
+const MRES_SHIFT: u32 = 24;
+const MRES_MASK: u32 = 0xF << MRES_SHIFT;
+
+/// Error of the SPI register interface
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SpiError<SpiErr, PinErr> {
+    /// Error of the SPI bus
+    Spi(SpiErr),
+    /// Error of the CS pin
+    Cs(PinErr),
+}
+
+/// A register of the typed map, it converts from/to the raw value
+pub trait Register: Copy + From<u32> + Into<u32> {
+    const ADDRESS: u8;
+}
+
+macro_rules! impl_register {
+    ($name:ident, $address:expr) => {
+        impl Register for $name {
+            const ADDRESS: u8 = $address;
+        }
+    };
+}
+
+/// the field of `width` bits at `shift`
+fn field(value: u32, shift: u32, width: u32) -> u32 {
+    (value >> shift) & ((1 << width) - 1)
+}
+
+fn bit(value: u32, n: u32) -> bool {
+    value & (1 << n) != 0
+}
+
+/// SPI_STATUS, the first byte of each response
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SpiStatus {
+    /// the chip was reset since GSTAT was cleared
+    pub reset: bool,
+    /// overtemperature or short, refer [DrvStatus] for detail
+    pub driver_error: bool,
+    /// StallGuard reports a stall
+    pub stall: bool,
+    pub standstill: bool,
+}
+
+impl From<u8> for SpiStatus {
+    fn from(value: u8) -> Self {
+        let value = value as u32;
+        Self {
+            reset: bit(value, 0),
+            driver_error: bit(value, 1),
+            stall: bit(value, 2),
+            standstill: bit(value, 3),
+        }
+    }
+}
+
+/// GCONF, the bits not listed are kept in `other`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Gconf {
+    /// StealthChop, it is used below TPWMTHRS
+    pub en_pwm_mode: bool,
+    /// inverse the motor direction
+    pub shaft: bool,
+    pub diag0_error: bool,
+    pub diag0_otpw: bool,
+    pub diag0_stall: bool,
+    pub diag1_stall: bool,
+    pub other: u32,
+}
+
+impl From<u32> for Gconf {
+    fn from(value: u32) -> Self {
+        Self {
+            en_pwm_mode: bit(value, 2),
+            shaft: bit(value, 4),
+            diag0_error: bit(value, 5),
+            diag0_otpw: bit(value, 6),
+            diag0_stall: bit(value, 7),
+            diag1_stall: bit(value, 8),
+            other: value & !0x1F4,
+        }
+    }
+}
+
+impl From<Gconf> for u32 {
+    fn from(r: Gconf) -> Self {
+        (r.other & !0x1F4)
+            | (r.en_pwm_mode as u32) << 2
+            | (r.shaft as u32) << 4
+            | (r.diag0_error as u32) << 5
+            | (r.diag0_otpw as u32) << 6
+            | (r.diag0_stall as u32) << 7
+            | (r.diag1_stall as u32) << 8
+    }
+}
+
+impl_register!(Gconf, reg::GCONF);
+
+/// IHOLD_IRUN, write only
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct IholdIrun {
+    /// standstill current scale, 0-31
+    pub ihold: u8,
+    /// run current scale, 0-31(full scale)
+    pub irun: u8,
+    /// delay to reduce from run to hold current, 0-15, unit is 2^18 clocks
+    pub iholddelay: u8,
+}
+
+impl From<u32> for IholdIrun {
+    fn from(value: u32) -> Self {
+        Self {
+            ihold: field(value, 0, 5) as u8,
+            irun: field(value, 8, 5) as u8,
+            iholddelay: field(value, 16, 4) as u8,
+        }
+    }
+}
+
+impl From<IholdIrun> for u32 {
+    fn from(r: IholdIrun) -> Self {
+        (r.ihold as u32 & 0x1F) | (r.irun as u32 & 0x1F) << 8 | (r.iholddelay as u32 & 0x0F) << 16
+    }
+}
+
+impl_register!(IholdIrun, reg::IHOLD_IRUN);
+
+/// CHOPCONF, the bits not listed are kept in `other`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Chopconf {
+    /// off time, 0 disables the power stage
+    pub toff: u8,
+    pub hstrt: u8,
+    pub hend: u8,
+    /// constant off time chopper instead of SpreadCycle
+    pub chm: bool,
+    /// blank time
+    pub tbl: u8,
+    /// microstep resolution, 0 is 256 microsteps, 8 is fullstep
+    pub mres: u8,
+    /// interpolation to 256 microsteps
+    pub intpol: bool,
+    /// step on both edges of STEP
+    pub dedge: bool,
+    /// disable the short to GND protection
+    pub diss2g: bool,
+    pub other: u32,
+}
+
+const CHOPCONF_FIELDS: u32 = 0x7F01_C7FF;
+
+impl From<u32> for Chopconf {
+    fn from(value: u32) -> Self {
+        Self {
+            toff: field(value, 0, 4) as u8,
+            hstrt: field(value, 4, 3) as u8,
+            hend: field(value, 7, 4) as u8,
+            chm: bit(value, 14),
+            tbl: field(value, 15, 2) as u8,
+            mres: field(value, 24, 4) as u8,
+            intpol: bit(value, 28),
+            dedge: bit(value, 29),
+            diss2g: bit(value, 30),
+            other: value & !CHOPCONF_FIELDS,
+        }
+    }
+}
+
+impl From<Chopconf> for u32 {
+    fn from(r: Chopconf) -> Self {
+        (r.other & !CHOPCONF_FIELDS)
+            | (r.toff as u32 & 0xF)
+            | (r.hstrt as u32 & 0x7) << 4
+            | (r.hend as u32 & 0xF) << 7
+            | (r.chm as u32) << 14
+            | (r.tbl as u32 & 0x3) << 15
+            | (r.mres as u32 & 0xF) << 24
+            | (r.intpol as u32) << 28
+            | (r.dedge as u32) << 29
+            | (r.diss2g as u32) << 30
+    }
+}
+
+impl_register!(Chopconf, reg::CHOPCONF);
+
+/// COOLCONF, write only
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Coolconf {
+    /// minimum StallGuard value for CoolStep, 0 disables CoolStep
+    pub semin: u8,
+    /// current up step width
+    pub seup: u8,
+    /// StallGuard hysteresis value for CoolStep
+    pub semax: u8,
+    /// current down step speed
+    pub sedn: u8,
+    /// minimum current of CoolStep is 1/4 of IRUN if true, otherwise 1/2
+    pub seimin: bool,
+    /// StallGuard threshold, -64..63, higher is less sensitive
+    pub sgt: i8,
+    /// StallGuard filter, one value per 4 fullsteps
+    pub sfilt: bool,
+}
+
+impl From<u32> for Coolconf {
+    fn from(value: u32) -> Self {
+        // sign extend the 7 bits of sgt
+        let sgt = ((field(value, 16, 7) as u8) << 1) as i8 >> 1;
+        Self {
+            semin: field(value, 0, 4) as u8,
+            seup: field(value, 5, 2) as u8,
+            semax: field(value, 8, 4) as u8,
+            sedn: field(value, 13, 2) as u8,
+            seimin: bit(value, 15),
+            sgt,
+            sfilt: bit(value, 24),
+        }
+    }
+}
+
+impl From<Coolconf> for u32 {
+    fn from(r: Coolconf) -> Self {
+        (r.semin as u32 & 0xF)
+            | (r.seup as u32 & 0x3) << 5
+            | (r.semax as u32 & 0xF) << 8
+            | (r.sedn as u32 & 0x3) << 13
+            | (r.seimin as u32) << 15
+            | (r.sgt as u8 as u32 & 0x7F) << 16
+            | (r.sfilt as u32) << 24
+    }
+}
+
+impl_register!(Coolconf, reg::COOLCONF);
+
+/// DRV_STATUS, read only
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DrvStatus {
+    /// StallGuard result, 0-1023, lower is higher load
+    pub sg_result: u16,
+    /// low side short of phase A/B(TMC5160 only)
+    pub s2vsa: bool,
+    pub s2vsb: bool,
+    /// the driver runs in StealthChop mode
+    pub stealth: bool,
+    /// fullstep mode is active
+    pub fsactive: bool,
+    /// actual current scale, 0-31
+    pub cs_actual: u8,
+    pub stallguard: bool,
+    /// overtemperature shutdown
+    pub ot: bool,
+    /// overtemperature prewarning
+    pub otpw: bool,
+    /// short to ground of phase A/B
+    pub s2ga: bool,
+    pub s2gb: bool,
+    /// open load of phase A/B
+    pub ola: bool,
+    pub olb: bool,
+    /// standstill
+    pub stst: bool,
+}
+
+impl From<u32> for DrvStatus {
+    fn from(value: u32) -> Self {
+        Self {
+            sg_result: field(value, 0, 10) as u16,
+            s2vsa: bit(value, 12),
+            s2vsb: bit(value, 13),
+            stealth: bit(value, 14),
+            fsactive: bit(value, 15),
+            cs_actual: field(value, 16, 5) as u8,
+            stallguard: bit(value, 24),
+            ot: bit(value, 25),
+            otpw: bit(value, 26),
+            s2ga: bit(value, 27),
+            s2gb: bit(value, 28),
+            ola: bit(value, 29),
+            olb: bit(value, 30),
+            stst: bit(value, 31),
+        }
+    }
+}
+
+impl From<DrvStatus> for u32 {
+    fn from(r: DrvStatus) -> Self {
+        (r.sg_result as u32 & 0x3FF)
+            | (r.s2vsa as u32) << 12
+            | (r.s2vsb as u32) << 13
+            | (r.stealth as u32) << 14
+            | (r.fsactive as u32) << 15
+            | (r.cs_actual as u32 & 0x1F) << 16
+            | (r.stallguard as u32) << 24
+            | (r.ot as u32) << 25
+            | (r.otpw as u32) << 26
+            | (r.s2ga as u32) << 27
+            | (r.s2gb as u32) << 28
+            | (r.ola as u32) << 29
+            | (r.olb as u32) << 30
+            | (r.stst as u32) << 31
+    }
+}
+
+impl_register!(DrvStatus, reg::DRV_STATUS);
+
+/// The TMC5160 driver API
+///
+/// the SPI and its CS pin are attached by [EnableStepModeControlTrait]. the
+/// driver keeps a copy of the written CHOPCONF, so that the step mode can be
+/// changed without reading it back.
+pub struct TMC5160<Spi, Cs, Step, Dir> {
+    spi: Spi,
+    cs: Cs,
+    chopconf: u32,
+    status: SpiStatus,
+    step: Step,
+    dir: Dir,
+}
+
+/// TMC2130 has the same SPI interface as TMC5160
+pub type TMC2130<Spi, Cs, Step, Dir> = TMC5160<Spi, Cs, Step, Dir>;
+
+impl TMC5160<(), (), (), ()> {
+    /// Create a new instance of `TMC5160`
+    pub fn new() -> Self {
+        Self {
+            spi: (),
+            cs: (),
+            chopconf: CHOPCONF_DEFAULT,
+            status: SpiStatus::default(),
+            step: (),
+            dir: (),
+        }
+    }
+}
+
+impl Default for TMC5160<(), (), (), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Spi, Cs, Step, Dir, SpiErr, PinErr> EnableStepModeControlTrait<(Spi, Cs)>
+    for TMC5160<(), (), Step, Dir>
+where
+    Spi: Transfer<u8, Error = SpiErr>,
+    Cs: OutputPin<Error = PinErr>,
+{
+    type WithStepModeControl = TMC5160<Spi, Cs, Step, Dir>;
+
+    fn enable_step_mode_control(self, (spi, cs): (Spi, Cs)) -> Self::WithStepModeControl {
+        TMC5160 {
+            spi,
+            cs,
+            chopconf: self.chopconf,
+            status: self.status,
+            step: self.step,
+            dir: self.dir,
+        }
+    }
+}
+
+impl<Spi, Cs, Step, Dir, SpiErr, PinErr> TMC5160<Spi, Cs, Step, Dir>
+where
+    Spi: Transfer<u8, Error = SpiErr>,
+    Cs: OutputPin<Error = PinErr>,
+{
+    /// one datagram, return the data of the response
+    fn transfer(&mut self, address: u8, value: u32) -> Result<u32, SpiError<SpiErr, PinErr>> {
+        let data = value.to_be_bytes();
+        let mut datagram = [address, data[0], data[1], data[2], data[3]];
+
+        self.cs.set_low().map_err(SpiError::Cs)?;
+        let result = self.spi.transfer(&mut datagram).map(|response| {
+            let mut out = [0u8; 5];
+            out.copy_from_slice(&response[..5]);
+            out
+        });
+        // release CS even if the transfer failed
+        self.cs.set_high().map_err(SpiError::Cs)?;
+        let response = result.map_err(SpiError::Spi)?;
+
+        self.status = SpiStatus::from(response[0]);
+        Ok(u32::from_be_bytes([
+            response[1],
+            response[2],
+            response[3],
+            response[4],
+        ]))
+    }
+
+    /// SPI_STATUS of the last response
+    pub fn status(&self) -> SpiStatus {
+        self.status
+    }
+
+    pub fn write_register(
+        &mut self,
+        register: u8,
+        value: u32,
+    ) -> Result<(), SpiError<SpiErr, PinErr>> {
+        self.transfer(register | WRITE, value)?;
+        if register == reg::CHOPCONF {
+            self.chopconf = value;
+        }
+        Ok(())
+    }
+
+    pub fn read_register(&mut self, register: u8) -> Result<u32, SpiError<SpiErr, PinErr>> {
+        self.transfer(register, 0)?;
+        self.transfer(register, 0)
+    }
+
+    pub fn write<R: Register>(&mut self, value: R) -> Result<(), SpiError<SpiErr, PinErr>> {
+        self.write_register(R::ADDRESS, value.into())
+    }
+
+    /// read a register, the write only ones read as 0
+    pub fn read<R: Register>(&mut self) -> Result<R, SpiError<SpiErr, PinErr>> {
+        self.read_register(R::ADDRESS).map(R::from)
+    }
+
+    /// set the run/hold current scale(0-31, 31 is full scale), and the
+    /// delay(0-15, unit is 2^18 clocks) to reduce from run to hold current
+    pub fn set_current(
+        &mut self,
+        run: u8,
+        hold: u8,
+        hold_delay: u8,
+    ) -> Result<(), SpiError<SpiErr, PinErr>> {
+        self.write(IholdIrun {
+            ihold: hold,
+            irun: run,
+            iholddelay: hold_delay,
+        })
+    }
+
+    pub fn read_drv_status(&mut self) -> Result<DrvStatus, SpiError<SpiErr, PinErr>> {
+        self.read()
+    }
+}
+
+impl<Spi, Cs, Step, Dir, SpiErr, PinErr> StallTrait for TMC5160<Spi, Cs, Step, Dir>
+where
+    Spi: Transfer<u8, Error = SpiErr>,
+    Cs: OutputPin<Error = PinErr>,
+{
+    type Error = SpiError<SpiErr, PinErr>;
+
+    /// poll the StallGuard flag of DRV_STATUS, the threshold is `sgt` of
+    /// [Coolconf]. StallGuard works only in SpreadCycle and above the velocity
+    /// of TCOOLTHRS.
+    fn is_stalled(&mut self) -> Result<bool, Self::Error> {
+        self.read_drv_status().map(|status| status.stallguard)
+    }
+}
+
+impl<Spi, Cs, Step, Dir, SpiErr, PinErr> SetStepModeTrait for TMC5160<Spi, Cs, Step, Dir>
+where
+    Spi: Transfer<u8, Error = SpiErr>,
+    Cs: OutputPin<Error = PinErr>,
+{
+    // MRES applies at once, no reset is needed
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+    const HOLD_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Error = SpiError<SpiErr, PinErr>;
+    type StepMode = StepMode256;
+
+    fn apply_mode_config(&mut self, step_mode: Self::StepMode) -> Result<(), Self::Error> {
+        use StepMode256::*;
+        let mres = match step_mode {
+            M256 => 0,
+            M128 => 1,
+            M64 => 2,
+            M32 => 3,
+            M16 => 4,
+            M8 => 5,
+            M4 => 6,
+            M2 => 7,
+            Full => 8,
+        };
+        let chopconf = (self.chopconf & !MRES_MASK) | (mres << MRES_SHIFT);
+        self.write_register(reg::CHOPCONF, chopconf)
+    }
+}
+
+// MRES applies at once, no reset
+impl<Spi, Cs, Step, Dir> StepModeResetTrait for TMC5160<Spi, Cs, Step, Dir> {
+    type ModeResetError = core::convert::Infallible;
+}
+
+impl<Spi, Cs, Step, Dir, OutputPinError> EnableDirectionControlTrait<Dir>
+    for TMC5160<Spi, Cs, Step, ()>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    type WithDirectionControl = TMC5160<Spi, Cs, Step, Dir>;
+
+    fn enable_direction_control(self, dir: Dir) -> Self::WithDirectionControl {
+        TMC5160 {
+            spi: self.spi,
+            cs: self.cs,
+            chopconf: self.chopconf,
+            status: self.status,
+            step: self.step,
+            dir,
+        }
+    }
+}
+
+impl<Spi, Cs, Step, Dir, OutputPinError> SetDirectionTrait for TMC5160<Spi, Cs, Step, Dir>
+where
+    Dir: OutputPin<Error = OutputPinError>,
+{
+    // tDSU, DIR to STEP setup time
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(20);
+
+    type Dir = Dir;
+    type Error = OutputPinError;
+
+    fn set_backward(&mut self) -> Result<(), Self::Error> {
+        self.dir.set_high()
+    }
+    fn set_forward(&mut self) -> Result<(), Self::Error> {
+        self.dir.set_low()
+    }
+    #[inline(always)]
+    fn dir_pin(&mut self) -> &mut Self::Dir {
+        &mut self.dir
+    }
+}
+
+impl<Spi, Cs, Step, Dir, OutputPinError> EnableStepControlTrait<Step>
+    for TMC5160<Spi, Cs, (), Dir>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    type WithStepControl = TMC5160<Spi, Cs, Step, Dir>;
+
+    fn enable_step_control(self, step: Step) -> Self::WithStepControl {
+        TMC5160 {
+            spi: self.spi,
+            cs: self.cs,
+            chopconf: self.chopconf,
+            status: self.status,
+            step,
+            dir: self.dir,
+        }
+    }
+}
+
+impl<Spi, Cs, Step, Dir, OutputPinError> StepTrait for TMC5160<Spi, Cs, Step, Dir>
+where
+    Step: OutputPin<Error = OutputPinError>,
+{
+    // tSH/tSL, min 100ns
+    const PULSE_LENGTH: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(100);
+
+    type Step = Step;
+    type Error = OutputPinError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.step.set_high()
+    }
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.step.set_low()
+    }
+    fn setp_pin(&mut self) -> &mut Self::Step {
+        &mut self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    /// records the datagrams, responds with the queued ones
+    #[derive(Default)]
+    struct MockSpi {
+        sent: Vec<[u8; 5]>,
+        responses: VecDeque<[u8; 5]>,
+    }
+
+    impl Transfer<u8> for MockSpi {
+        type Error = ();
+        fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+            let mut sent = [0u8; 5];
+            sent.copy_from_slice(words);
+            self.sent.push(sent);
+            let response = self.responses.pop_front().unwrap_or_default();
+            words.copy_from_slice(&response);
+            Ok(words)
+        }
+    }
+
+    struct MockCs;
+
+    impl OutputPin for MockCs {
+        type Error = Infallible;
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    fn driver() -> TMC5160<MockSpi, MockCs, (), ()> {
+        TMC5160::new().enable_step_mode_control((MockSpi::default(), MockCs))
+    }
+
+    #[test]
+    fn registers_should_round_trip() {
+        let coolconf = Coolconf {
+            semin: 5,
+            seup: 2,
+            semax: 2,
+            sedn: 1,
+            seimin: true,
+            sgt: -3,
+            sfilt: true,
+        };
+        assert_eq!(Coolconf::from(u32::from(coolconf)), coolconf);
+        assert_eq!(u32::from(coolconf) >> 16 & 0x7F, 0x7D);
+
+        for value in [CHOPCONF_DEFAULT, 0x1401_8153, 0xFFFF_FFFF] {
+            assert_eq!(u32::from(Chopconf::from(value)), value);
+            assert_eq!(u32::from(Gconf::from(value)), value);
+        }
+    }
+
+    #[test]
+    fn chopconf_should_clear_a_lowered_tbl() {
+        // TBL=1, bit 15
+        let mut chopconf = Chopconf::from(CHOPCONF_DEFAULT & !(0x3 << 15) | 1 << 15);
+        assert_eq!(chopconf.tbl, 1);
+        chopconf.tbl = 0;
+        assert_eq!(u32::from(chopconf) & 0x3 << 15, 0);
+    }
+
+    #[test]
+    fn apply_mode_config_should_write_mres() {
+        let mut driver = driver();
+        driver.apply_mode_config(StepMode256::M16).unwrap();
+        assert_eq!(driver.spi.sent, [[0xEC, 0x04, 0x01, 0x00, 0xC3]]);
+        assert_eq!(Chopconf::from(driver.chopconf).mres, 4);
+    }
+
+    #[test]
+    fn read_should_decode_status_of_the_second_response() {
+        let mut driver = driver();
+        // overtemp, open load A, stall; SPI_STATUS: driver_error, stall
+        driver.spi.responses.extend([[0x00; 5], [0x06, 0x23, 0x1F, 0x00, 0x40]]);
+
+        let status = driver.read_drv_status().unwrap();
+        assert!(status.ot && status.ola && status.stallguard && !status.olb);
+        assert_eq!(status.cs_actual, 31);
+        assert_eq!(status.sg_result, 0x40);
+        assert_eq!(driver.spi.sent, [[reg::DRV_STATUS, 0, 0, 0, 0]; 2]);
+
+        let spi_status = driver.status();
+        assert!(spi_status.driver_error && spi_status.stall && !spi_status.reset);
+    }
+
+    #[test]
+    fn set_step_mode_should_work_through_montionctrl() {
+        use crate::sim::Sim;
+        use crate::{MontionCtrl, MotionControlStepModeTrait};
+
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(driver(), sim.convert());
+        ctrl.set_step_mode(StepMode256::M16).unwrap();
+
+        assert_eq!(ctrl.driver_mut().spi.sent, [[0xEC, 0x04, 0x01, 0x00, 0xC3]]);
+        assert_eq!(sim.now(), 0);
+    }
+}
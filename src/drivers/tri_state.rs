@@ -0,0 +1,273 @@
+//! drivers whose step mode is encoded by a tri-state M0 and a normal M1
+//! pin(DRV8834, DRV8880). they differ only in the mode table and the timing,
+//! `tri_state_driver` generates the driver type and its traits from them.
+
+/// generate the driver type of a tri-state M0/M1 driver, e.g.
+///
+/// ```rust,ignore
+///   tri_state_driver! {
+///       /// The DRV8834 driver API
+///       DRV8834,
+///       step_mode: StepMode32,
+///       mode_setup: 200, mode_hold: 200, dir_setup: 200, pulse_length: 1900,
+///       modes: { Full => (Low, Low), M2 => (High, Low), ... }
+///   }
+/// ```
+///
+/// the modes map each step mode to the (TriState, PinState) of M0/M1, the
+/// times are in ns.
+macro_rules! tri_state_driver {
+    (
+        $(#[$meta:meta])*
+        $name:ident,
+        step_mode: $step_mode:ident,
+        mode_setup: $mode_setup:expr,
+        mode_hold: $mode_hold:expr,
+        dir_setup: $dir_setup:expr,
+        pulse_length: $pulse_length:expr,
+        modes: { $($mode:ident => ($m0:ident, $m1:ident)),+ $(,)? }
+    ) => {
+        $(#[$meta])*
+        pub struct $name<Mode0, Mode1, Step, Dir> {
+            mode0: Mode0,
+            mode1: Mode1,
+            step: Step,
+            dir: Dir,
+        }
+
+        impl $name<(), (), (), ()> {
+            #[doc = concat!("Create a new instance of `", stringify!($name), "`")]
+            pub fn new() -> Self {
+                Self {
+                    mode0: (),
+                    mode1: (),
+                    step: (),
+                    dir: (),
+                }
+            }
+        }
+
+        impl Default for $name<(), (), (), ()> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<Mode0, Mode1, Step, Dir, OutputPinError>
+            $crate::interfaces::EnableStepModeControlTrait<(Mode0, Mode1)>
+            for $name<(), (), Step, Dir>
+        where
+            Mode0: $crate::interfaces::TriStatePinTrait<Error = OutputPinError>,
+            Mode1: embedded_hal::digital::v2::OutputPin<Error = OutputPinError>,
+        {
+            type WithStepModeControl = $name<Mode0, Mode1, Step, Dir>;
+
+            fn enable_step_mode_control(
+                self,
+                (mode0, mode1): (Mode0, Mode1),
+            ) -> Self::WithStepModeControl {
+                $name {
+                    mode0,
+                    mode1,
+                    step: self.step,
+                    dir: self.dir,
+                }
+            }
+        }
+
+        impl<Mode0, Mode1, Step, Dir, OutputPinError> $crate::interfaces::SetStepModeTrait
+            for $name<Mode0, Mode1, Step, Dir>
+        where
+            Mode0: $crate::interfaces::TriStatePinTrait<Error = OutputPinError>,
+            Mode1: embedded_hal::digital::v2::OutputPin<Error = OutputPinError>,
+        {
+            const SETUP_TIME: fugit::NanosDurationU64 =
+                fugit::NanosDurationU64::from_ticks($mode_setup);
+            const HOLD_TIME: fugit::NanosDurationU64 =
+                fugit::NanosDurationU64::from_ticks($mode_hold);
+
+            type Error = OutputPinError;
+            type StepMode = $crate::step_mode::$step_mode;
+
+            fn apply_mode_config(&mut self, step_mode: Self::StepMode) -> Result<(), Self::Error> {
+                let (mode0, mode1) = match step_mode {
+                    $(
+                        $crate::step_mode::$step_mode::$mode => (
+                            $crate::interfaces::TriState::$m0,
+                            embedded_hal::digital::v2::PinState::$m1,
+                        ),
+                    )+
+                };
+
+                self.mode0.set_tri_state(mode0)?;
+                self.mode1.set_state(mode1)
+            }
+        }
+
+        // the mode pins apply without reset
+        impl<Mode0, Mode1, Step, Dir> $crate::interfaces::StepModeResetTrait
+            for $name<Mode0, Mode1, Step, Dir>
+        {
+            type ModeResetError = core::convert::Infallible;
+        }
+
+        impl<Mode0, Mode1, Step, Dir, OutputPinError>
+            $crate::interfaces::EnableDirectionControlTrait<Dir>
+            for $name<Mode0, Mode1, Step, ()>
+        where
+            Dir: embedded_hal::digital::v2::OutputPin<Error = OutputPinError>,
+        {
+            type WithDirectionControl = $name<Mode0, Mode1, Step, Dir>;
+
+            fn enable_direction_control(self, dir: Dir) -> Self::WithDirectionControl {
+                $name {
+                    mode0: self.mode0,
+                    mode1: self.mode1,
+                    step: self.step,
+                    dir,
+                }
+            }
+        }
+
+        impl<Mode0, Mode1, Step, Dir, OutputPinError> $crate::interfaces::SetDirectionTrait
+            for $name<Mode0, Mode1, Step, Dir>
+        where
+            Dir: embedded_hal::digital::v2::OutputPin<Error = OutputPinError>,
+        {
+            const SETUP_TIME: fugit::NanosDurationU64 =
+                fugit::NanosDurationU64::from_ticks($dir_setup);
+
+            type Dir = Dir;
+            type Error = OutputPinError;
+
+            fn set_backward(&mut self) -> Result<(), Self::Error> {
+                self.dir.set_high()
+            }
+            fn set_forward(&mut self) -> Result<(), Self::Error> {
+                self.dir.set_low()
+            }
+            #[inline(always)]
+            fn dir_pin(&mut self) -> &mut Self::Dir {
+                &mut self.dir
+            }
+        }
+
+        impl<Mode0, Mode1, Step, Dir, OutputPinError>
+            $crate::interfaces::EnableStepControlTrait<Step>
+            for $name<Mode0, Mode1, (), Dir>
+        where
+            Step: embedded_hal::digital::v2::OutputPin<Error = OutputPinError>,
+        {
+            type WithStepControl = $name<Mode0, Mode1, Step, Dir>;
+
+            fn enable_step_control(self, step: Step) -> Self::WithStepControl {
+                $name {
+                    mode0: self.mode0,
+                    mode1: self.mode1,
+                    step,
+                    dir: self.dir,
+                }
+            }
+        }
+
+        impl<Mode0, Mode1, Step, Dir, OutputPinError> $crate::interfaces::StepTrait
+            for $name<Mode0, Mode1, Step, Dir>
+        where
+            Step: embedded_hal::digital::v2::OutputPin<Error = OutputPinError>,
+        {
+            const PULSE_LENGTH: fugit::NanosDurationU64 =
+                fugit::NanosDurationU64::from_ticks($pulse_length);
+
+            type Step = Step;
+            type Error = OutputPinError;
+
+            fn set_high(&mut self) -> Result<(), Self::Error> {
+                self.step.set_high()
+            }
+            fn set_low(&mut self) -> Result<(), Self::Error> {
+                self.step.set_low()
+            }
+            fn setp_pin(&mut self) -> &mut Self::Step {
+                &mut self.step
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interfaces::{
+        EnableStepModeControlTrait, SetStepModeTrait, TriState, TriStatePinTrait,
+    };
+    use crate::step_mode::{StepMode16, StepMode32};
+    use crate::{DRV8834, DRV8880};
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use embedded_hal::digital::v2::{OutputPin, PinState};
+
+    struct MockTriState<'a>(&'a Cell<Option<TriState>>);
+
+    impl TriStatePinTrait for MockTriState<'_> {
+        type Error = Infallible;
+        fn set_tri_state(&mut self, state: TriState) -> Result<(), Infallible> {
+            self.0.set(Some(state));
+            Ok(())
+        }
+    }
+
+    struct MockPin<'a>(&'a Cell<Option<PinState>>);
+
+    impl OutputPin for MockPin<'_> {
+        type Error = Infallible;
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.set(Some(PinState::Low));
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.set(Some(PinState::High));
+            Ok(())
+        }
+    }
+
+    use PinState::{High as H, Low as L};
+    use TriState::{Floating as Z, High as TH, Low as TL};
+
+    #[test]
+    fn drv8834_should_follow_the_mode_table() {
+        let (m0, m1) = (Cell::new(None), Cell::new(None));
+        let mut driver = DRV8834::new().enable_step_mode_control((MockTriState(&m0), MockPin(&m1)));
+
+        // Mx pin of the datasheet
+        let table = [
+            (StepMode32::Full, TL, L),
+            (StepMode32::M2, TH, L),
+            (StepMode32::M4, Z, L),
+            (StepMode32::M8, TL, H),
+            (StepMode32::M16, TH, H),
+            (StepMode32::M32, Z, H),
+        ];
+        for (mode, expected_m0, expected_m1) in table {
+            driver.apply_mode_config(mode).unwrap();
+            assert_eq!((m0.get(), m1.get()), (Some(expected_m0), Some(expected_m1)));
+        }
+    }
+
+    #[test]
+    fn drv8880_should_follow_the_mode_table() {
+        let (m0, m1) = (Cell::new(None), Cell::new(None));
+        let mut driver = DRV8880::new().enable_step_mode_control((MockTriState(&m0), MockPin(&m1)));
+
+        // Mx pin of the datasheet, M2 is the circular 1/2 step
+        let table = [
+            (StepMode16::Full, TL, L),
+            (StepMode16::M2, TL, H),
+            (StepMode16::M4, TH, H),
+            (StepMode16::M8, Z, L),
+            (StepMode16::M16, Z, H),
+        ];
+        for (mode, expected_m0, expected_m1) in table {
+            driver.apply_mode_config(mode).unwrap();
+            assert_eq!((m0.get(), m1.get()), (Some(expected_m0), Some(expected_m1)));
+        }
+    }
+}
@@ -0,0 +1,362 @@
+//! ULN2003 Driver
+//!
+//! four-phase unipolar motors(e.g. 28BYJ-48) through a ULN2003 darlington
+//! array. there is no STEP/DIR, the MCU energizes the four coil pins(IN1-IN4)
+//! in sequence, a step advances the sequence by one entry.
+//!
+//! ```rust,ignore
+//!   let driver = ULN2003::new().enable_step_control((in1, in2, in3, in4));
+//!   let mut ctrl = MontionCtrl::new(driver, convert);
+//!   ctrl.set_step_mode(StepModePhase::Full)?;
+//!   ctrl.enable_motor()?;
+//! ```
+//!
+//! the sequence of [StepModePhase], forward goes down:
+//!
+//! ```text
+//!   phase  IN1 IN2 IN3 IN4   Wave  Full  Half
+//!     0     1   0   0   0     *           *
+//!     1     1   1   0   0           *     *
+//!     2     0   1   0   0     *           *
+//!     3     0   1   1   0           *     *
+//!     4     0   0   1   0     *           *
+//!     5     0   0   1   1           *     *
+//!     6     0   0   0   1     *           *
+//!     7     1   0   0   1           *     *
+//! ```
+//!
+//! a mode change never moves the rotor. changed while energized on a phase of
+//! the other parity(e.g. Full on phase 0), the mode keeps stepping whole
+//! steps on that parity, Full drives a single coil like Wave(and Wave two
+//! coils). set the mode while disabled to get its own sequence.
+//!
+//! 28BYJ-48 has 2048 fullsteps(4096 halfsteps) per turn of the output shaft,
+//! it stalls above about 500 fullsteps per second. the coils stay energized
+//! between steps, use `disable_motor` when idle to save power and heat.
+
+use embedded_hal::digital::v2::OutputPin;
+
+use super::NoPin;
+use crate::interfaces::{
+    EnableStepControlTrait, OutputEnableTrait, SetDirectionTrait, SetStepModeTrait,
+    StepModeResetTrait, StepTrait,
+};
+use crate::step_mode::StepModePhase;
+use crate::Direction;
+
+/// coils of each phase, bit 0 is IN1
+const PHASES: [u8; 8] = [
+    0b0001, 0b0011, 0b0010, 0b0110, 0b0100, 0b1100, 0b1000, 0b1001,
+];
+
+/// The ULN2003 driver API
+///
+/// the default step mode is halfstep.
+pub struct ULN2003<Coils> {
+    coils: Coils,
+    mode: StepModePhase,
+    phase: usize,
+    energized: bool,
+    direction: Direction,
+    no_pin: NoPin,
+}
+
+impl ULN2003<()> {
+    /// Create a new instance of `ULN2003`
+    pub fn new() -> Self {
+        Self {
+            coils: (),
+            mode: StepModePhase::Half,
+            phase: 0,
+            energized: false,
+            direction: Direction::Forward,
+            no_pin: NoPin,
+        }
+    }
+}
+
+impl Default for ULN2003<()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<In1, In2, In3, In4, OutputPinError> EnableStepControlTrait<(In1, In2, In3, In4)>
+    for ULN2003<()>
+where
+    In1: OutputPin<Error = OutputPinError>,
+    In2: OutputPin<Error = OutputPinError>,
+    In3: OutputPin<Error = OutputPinError>,
+    In4: OutputPin<Error = OutputPinError>,
+{
+    type WithStepControl = ULN2003<(In1, In2, In3, In4)>;
+
+    fn enable_step_control(self, coils: (In1, In2, In3, In4)) -> Self::WithStepControl {
+        ULN2003 {
+            coils,
+            mode: self.mode,
+            phase: self.phase,
+            energized: self.energized,
+            direction: self.direction,
+            no_pin: self.no_pin,
+        }
+    }
+}
+
+impl<In1, In2, In3, In4, OutputPinError> ULN2003<(In1, In2, In3, In4)>
+where
+    In1: OutputPin<Error = OutputPinError>,
+    In2: OutputPin<Error = OutputPinError>,
+    In3: OutputPin<Error = OutputPinError>,
+    In4: OutputPin<Error = OutputPinError>,
+{
+    /// current index of the sequence, refer the module doc
+    pub fn phase(&self) -> usize {
+        self.phase
+    }
+
+    pub fn release(self) -> (In1, In2, In3, In4) {
+        self.coils
+    }
+
+    fn write(&mut self, coils: u8) -> Result<(), OutputPinError> {
+        self.energized = coils != 0;
+        let level = |n: u8| coils & (1 << n) != 0;
+        self.coils.0.set_state(level(0).into())?;
+        self.coils.1.set_state(level(1).into())?;
+        self.coils.2.set_state(level(2).into())?;
+        self.coils.3.set_state(level(3).into())
+    }
+}
+
+impl<In1, In2, In3, In4, OutputPinError> SetStepModeTrait for ULN2003<(In1, In2, In3, In4)>
+where
+    In1: OutputPin<Error = OutputPinError>,
+    In2: OutputPin<Error = OutputPinError>,
+    In3: OutputPin<Error = OutputPinError>,
+    In4: OutputPin<Error = OutputPinError>,
+{
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+    const HOLD_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Error = OutputPinError;
+    type StepMode = StepModePhase;
+
+    /// the coils are never switched, so the position is kept. when the coils
+    /// are off and the current phase is not one of the new mode(e.g. phase 1
+    /// for wave), it is re-indexed a halfstep in the current direction onto
+    /// one, taken at the next enable. energized coils keep their phase,
+    /// refer the module doc.
+    fn apply_mode_config(&mut self, step_mode: Self::StepMode) -> Result<(), Self::Error> {
+        self.mode = step_mode;
+        if self.energized {
+            return Ok(());
+        }
+        self.phase = match (step_mode, self.phase % 2) {
+            (StepModePhase::Wave, 1) | (StepModePhase::Full, 0) => match self.direction {
+                Direction::Forward => (self.phase + 1) % PHASES.len(),
+                Direction::Backward => (self.phase + 7) % PHASES.len(),
+            },
+            _ => self.phase,
+        };
+        Ok(())
+    }
+}
+
+// the mode applies at the next step, no reset
+impl<Coils> StepModeResetTrait for ULN2003<Coils> {
+    type ModeResetError = core::convert::Infallible;
+}
+
+impl<In1, In2, In3, In4, OutputPinError> SetDirectionTrait for ULN2003<(In1, In2, In3, In4)>
+where
+    In1: OutputPin<Error = OutputPinError>,
+    In2: OutputPin<Error = OutputPinError>,
+    In3: OutputPin<Error = OutputPinError>,
+    In4: OutputPin<Error = OutputPinError>,
+{
+    // the direction applies to the next step, no signal to set up
+    const SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Dir = NoPin;
+    type Error = OutputPinError;
+
+    fn set_backward(&mut self) -> Result<(), Self::Error> {
+        self.direction = Direction::Backward;
+        Ok(())
+    }
+    fn set_forward(&mut self) -> Result<(), Self::Error> {
+        self.direction = Direction::Forward;
+        Ok(())
+    }
+    #[inline(always)]
+    fn dir_pin(&mut self) -> &mut Self::Dir {
+        &mut self.no_pin
+    }
+}
+
+impl<In1, In2, In3, In4, OutputPinError> StepTrait for ULN2003<(In1, In2, In3, In4)>
+where
+    In1: OutputPin<Error = OutputPinError>,
+    In2: OutputPin<Error = OutputPinError>,
+    In3: OutputPin<Error = OutputPinError>,
+    In4: OutputPin<Error = OutputPinError>,
+{
+    // the coils are switched at the rising edge, the rest of the step period
+    // is the low time
+    const PULSE_LENGTH: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(1000);
+
+    type Step = NoPin;
+    type Error = OutputPinError;
+
+    /// advance the sequence and energize the coils of the next phase
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        // a whole step of the mode keeps the parity, refer apply_mode_config
+        let stride = match self.mode {
+            StepModePhase::Half => 1,
+            StepModePhase::Wave | StepModePhase::Full => 2,
+        };
+        self.phase = match self.direction {
+            Direction::Forward => self.phase + stride,
+            Direction::Backward => self.phase + PHASES.len() - stride,
+        } % PHASES.len();
+        self.write(PHASES[self.phase])
+    }
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    fn setp_pin(&mut self) -> &mut Self::Step {
+        &mut self.no_pin
+    }
+}
+
+impl<In1, In2, In3, In4, OutputPinError> OutputEnableTrait for ULN2003<(In1, In2, In3, In4)>
+where
+    In1: OutputPin<Error = OutputPinError>,
+    In2: OutputPin<Error = OutputPinError>,
+    In3: OutputPin<Error = OutputPinError>,
+    In4: OutputPin<Error = OutputPinError>,
+{
+    const ENABLE_SETUP_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    type Error = OutputPinError;
+
+    /// energize the coils of the current phase to hold the position
+    fn enable_output(&mut self) -> Result<(), Self::Error> {
+        self.write(PHASES[self.phase])
+    }
+
+    /// turn all coils off
+    fn disable_output(&mut self) -> Result<(), Self::Error> {
+        self.write(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    struct MockPin<'a>(&'a Cell<u8>, u8);
+
+    impl OutputPin for MockPin<'_> {
+        type Error = Infallible;
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.set(self.0.get() & !(1 << self.1));
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.set(self.0.get() | (1 << self.1));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn step_should_follow_the_sequence_of_the_mode() {
+        let coils = Cell::new(0);
+        let pins = (
+            MockPin(&coils, 0),
+            MockPin(&coils, 1),
+            MockPin(&coils, 2),
+            MockPin(&coils, 3),
+        );
+        let mut driver = ULN2003::new().enable_step_control(pins);
+        let steps = |driver: &mut ULN2003<_>, n| {
+            let mut out = [0u8; 4];
+            for coil in out.iter_mut().take(n) {
+                driver.set_high().unwrap();
+                driver.set_low().unwrap();
+                *coil = coils.get();
+            }
+            out
+        };
+
+        assert_eq!(steps(&mut driver, 3), [0b0011, 0b0010, 0b0110, 0]);
+        driver.set_backward().unwrap();
+        assert_eq!(steps(&mut driver, 2), [0b0010, 0b0011, 0, 0]);
+
+        // phase 1 is not a wave phase, energized it is kept and the wave
+        // steps stay on two coils
+        driver.apply_mode_config(StepModePhase::Wave).unwrap();
+        assert_eq!((driver.phase(), coils.get()), (1, 0b0011));
+        assert_eq!(steps(&mut driver, 3), [0b1001, 0b1100, 0b0110, 0]);
+        driver.set_forward().unwrap();
+        driver.apply_mode_config(StepModePhase::Full).unwrap();
+        assert_eq!((driver.phase(), coils.get()), (3, 0b0110));
+        assert_eq!(steps(&mut driver, 4), [0b1100, 0b1001, 0b0011, 0b0110]);
+
+        driver.disable_output().unwrap();
+        assert_eq!(coils.get(), 0);
+        driver.enable_output().unwrap();
+        assert_eq!(coils.get(), 0b0110);
+    }
+
+    #[test]
+    fn mode_change_should_not_energize_idle_coils() {
+        let coils = Cell::new(0);
+        let pins = (
+            MockPin(&coils, 0),
+            MockPin(&coils, 1),
+            MockPin(&coils, 2),
+            MockPin(&coils, 3),
+        );
+        let mut driver = ULN2003::new().enable_step_control(pins);
+
+        // re-indexed only, the coils of the aligned phase on enable
+        driver.apply_mode_config(StepModePhase::Full).unwrap();
+        assert_eq!((driver.phase(), coils.get()), (1, 0));
+        driver.enable_output().unwrap();
+        assert_eq!(coils.get(), 0b0011);
+
+        driver.disable_output().unwrap();
+        driver.apply_mode_config(StepModePhase::Wave).unwrap();
+        assert_eq!((driver.phase(), coils.get()), (2, 0));
+    }
+
+    #[test]
+    fn set_step_mode_should_work_through_montionctrl() {
+        use crate::sim::Sim;
+        use crate::{MontionCtrl, MotionControlStepModeTrait, MotionControlTrait};
+
+        let coils = Cell::new(0);
+        let pins = (
+            MockPin(&coils, 0),
+            MockPin(&coils, 1),
+            MockPin(&coils, 2),
+            MockPin(&coils, 3),
+        );
+        let driver = ULN2003::new().enable_step_control(pins);
+        let mut ctrl = MontionCtrl::new(driver, Sim::new().convert());
+        ctrl.set_step_mode(StepModePhase::Full).unwrap();
+        assert_eq!(ctrl.driver_mut().phase(), 1);
+
+        // energized, the mode change keeps the coils and the position
+        ctrl.enable_motor().unwrap();
+        ctrl.set_step_mode(StepModePhase::Half).unwrap();
+        ctrl.step().unwrap();
+        ctrl.set_step_mode(StepModePhase::Full).unwrap();
+        assert_eq!((ctrl.driver_mut().phase(), coils.get()), (2, 0b0010));
+        assert_eq!(ctrl.current_step(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+//! embedded-hal 1.0 support, enabled by the `eh1` feature
+//!
+//! the drivers take `embedded_hal::digital::v2` pins of embedded-hal 0.2, wrap
+//! an embedded-hal 1.0 pin with [Eh1Pin] to pass it to any of them. the
+//! convert [DelayNsConvert] is built on `embedded_hal::delay::DelayNs`.
+//!
+//! ```rust,ignore
+//!   let driver = A4988::new()
+//!       .enable_step_control(Eh1Pin::new(step_pin))
+//!       .enable_direction_control(Eh1Pin::new(dir_pin));
+//!   let mut ctrl = MontionCtrl::new(driver, DelayNsConvert(delay));
+//! ```
+
+use core::cell::RefCell;
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal_1::delay::DelayNs;
+use embedded_hal_1::digital::{InputPin as InputPin1, OutputPin as OutputPin1};
+
+use crate::{DelayToTicksTrait, FromTimerErrorTrait};
+
+/// embedded-hal 1.0 pin as embedded-hal 0.2 `OutputPin`/`InputPin`
+///
+/// embedded-hal 1.0 reads a pin by `&mut self`, the pin is kept in a
+/// `RefCell` to read it by `&self`.
+pub struct Eh1Pin<Pin>(RefCell<Pin>);
+
+impl<Pin> Eh1Pin<Pin> {
+    pub fn new(pin: Pin) -> Self {
+        Self(RefCell::new(pin))
+    }
+
+    pub fn release(self) -> Pin {
+        self.0.into_inner()
+    }
+}
+
+impl<Pin: OutputPin1> OutputPin for Eh1Pin<Pin> {
+    type Error = Pin::Error;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.get_mut().set_low()
+    }
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.get_mut().set_high()
+    }
+}
+
+impl<Pin: InputPin1> InputPin for Eh1Pin<Pin> {
+    type Error = Pin::Error;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.0.borrow_mut().is_high()
+    }
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.0.borrow_mut().is_low()
+    }
+}
+
+/// embedded-hal 1.0 `DelayNs` implement [DelayToTicksTrait]
+///
+/// DelayNs blocks, so the closure runs before the delay and its time adds
+/// to the timeout. it is fine for the pin toggles, which take far less
+/// than a step.
+pub struct DelayNsConvert<Delay>(pub Delay);
+
+impl<Delay: DelayNs> DelayToTicksTrait for DelayNsConvert<Delay> {
+    /// DelayNs can not fail
+    type Error = void::Void;
+
+    fn wait<E: FromTimerErrorTrait<Self::Error>>(
+        &mut self,
+        timeout: &fugit::NanosDurationU64,
+        mut closure: impl FnMut() -> Result<(), E>,
+    ) -> Result<(), E> {
+        closure()?;
+
+        // DelayNs takes u32 ns, about 4.3s at most
+        let mut nanos = timeout.ticks();
+        while nanos > 0 {
+            let chunk = nanos.min(u32::MAX as u64);
+            self.0.delay_ns(chunk as u32);
+            nanos -= chunk;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        EnableDirectionControlTrait, EnableStepControlTrait, MontionCtrl, MotionControlTrait,
+        MotionError, SOFT,
+    };
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use embedded_hal_1::digital::{ErrorType, PinState};
+
+    struct MockPin<'a>(&'a Cell<PinState>);
+
+    impl ErrorType for MockPin<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin1 for MockPin<'_> {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.set(PinState::Low);
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.set(PinState::High);
+            Ok(())
+        }
+    }
+
+    impl InputPin1 for MockPin<'_> {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            Ok(self.0.get() == PinState::High)
+        }
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            Ok(self.0.get() == PinState::Low)
+        }
+    }
+
+    struct MockDelay<'a>(&'a Cell<u64>);
+
+    impl DelayNs for MockDelay<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.set(self.0.get() + ns as u64);
+        }
+    }
+
+    #[test]
+    fn motion_control_should_drive_eh1_pins() {
+        let (step, dir, elapsed) = (
+            Cell::new(PinState::Low),
+            Cell::new(PinState::Low),
+            Cell::new(0),
+        );
+        let driver = SOFT::<_, _, 200, 1000>::new()
+            .enable_step_control(Eh1Pin::new(MockPin(&step)))
+            .enable_direction_control(Eh1Pin::new(MockPin(&dir)));
+        let mut ctrl = MontionCtrl::new(driver, DelayNsConvert(MockDelay(&elapsed)));
+
+        ctrl.set_direction(crate::Direction::Backward).unwrap();
+        assert_eq!(dir.get(), PinState::High);
+        ctrl.step().unwrap();
+        assert_eq!(step.get(), PinState::Low);
+        // DIR setup, then the high and low of the pulse
+        assert_eq!(elapsed.get(), 200 + 2 * 1000);
+
+        let pin = Eh1Pin::new(MockPin(&step));
+        assert!(pin.is_low().unwrap());
+    }
+
+    #[test]
+    fn delay_should_split_long_timeouts() {
+        let elapsed = Cell::new(0);
+        let mut convert = DelayNsConvert(MockDelay(&elapsed));
+        let mut called = false;
+        convert
+            .wait::<MotionError<(), void::Void>>(
+                &fugit::NanosDurationU64::from_ticks(10_000_000_000),
+                || {
+                    called = true;
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert!(called);
+        assert_eq!(elapsed.get(), 10_000_000_000);
+    }
+}
@@ -0,0 +1,156 @@
+//! Errors returned by motion control
+//!
+//! drivers report errors through their associated `Error` types (refer
+//! [StepTrait](crate::StepTrait), [SetDirectionTrait](crate::SetDirectionTrait),
+//! [SetStepModeTrait](crate::SetStepModeTrait), [ResetTrait](crate::ResetTrait) and so on),
+//! the timer reports through [DelayToTicksTrait::Error](crate::DelayToTicksTrait::Error).
+//! [MotionError] keeps which one failed, so that the app can tell a GPIO fault
+//! from a timer fault.
+
+/// Error that can occur while controlling the motion
+///
+/// `PinErr` is the driver's pin error(the STEP pin, or the enable/sleep/fault
+/// pin), `TimerErr` is the convert's timer error. the DIR, mode and reset
+/// errors have their own parameters, so that each driver trait keeps its own
+/// `Error`. they default to `PinErr`, e.g. `MotionError<E, T>` when all pins
+/// share the same error. `StallErr` is the error of the home input(the home
+/// switch or the stall source), only the homing reads it. `SwitchErr` is the
+/// error of the guard's switches(e.g. [EndStops](crate::EndStops)). both are
+/// `Infallible` where they are not read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MotionError<
+    PinErr,
+    TimerErr,
+    DirErr = PinErr,
+    ModeErr = PinErr,
+    ResetErr = ModeErr,
+    StallErr = core::convert::Infallible,
+    SwitchErr = core::convert::Infallible,
+> {
+    /// Error while accessing the STEP pin
+    Step(PinErr),
+    /// Error while accessing the DIR pin
+    Direction(DirErr),
+    /// Error while applying the step mode, e.g. setting the mode pins
+    StepMode(ModeErr),
+    /// Error while accessing the reset pin
+    Reset(ResetErr),
+    /// Error while accessing the enable pin
+    Enable(PinErr),
+    /// Error while accessing the sleep pin
+    Sleep(PinErr),
+    /// Error while reading the fault pin
+    Fault(PinErr),
+    /// the driver reports a fault, e.g. overcurrent or overheat
+    DriverFault,
+    /// the stall source failed to report its stall state, e.g. a UART/SPI
+    /// error or the DIAG pin
+    StallDetect(StallErr),
+    /// Error while reading the home switch of [MontionCtrl::home](crate::MontionCtrl::home)
+    HomeSwitch(StallErr),
+    /// Error while waiting on the timer
+    Timer(TimerErr),
+    /// Error while reading a switch of the guard, e.g. an end stop
+    Switch(SwitchErr),
+    /// the switch(or the stall detection) was not triggered within the max travel
+    SwitchNotFound,
+    /// a motion is in progress, the request can not be accepted now
+    Busy,
+    /// the target is out of the soft limits, refer [SoftLimits](crate::SoftLimits)
+    SoftLimit,
+    /// an end stop was hit while moving in the direction, refer [EndStops](crate::EndStops)
+    EndStop(crate::Direction),
+    /// the config can not be run, e.g. a zero velocity of
+    /// [HomingConfig](crate::HomingConfig)
+    InvalidConfig,
+}
+
+/// An error that can carry the convert's timer error, e.g. [MotionError]
+///
+/// the closure of [DelayToTicksTrait::wait](crate::DelayToTicksTrait::wait)
+/// returns it, the convert reports its timer error through the same type.
+pub trait FromTimerErrorTrait<TimerErr> {
+    fn from_timer_error(error: TimerErr) -> Self;
+}
+
+impl<PinErr, TimerErr, DirErr, ModeErr, ResetErr, StallErr, SwitchErr> FromTimerErrorTrait<TimerErr>
+    for MotionError<PinErr, TimerErr, DirErr, ModeErr, ResetErr, StallErr, SwitchErr>
+{
+    fn from_timer_error(error: TimerErr) -> Self {
+        MotionError::Timer(error)
+    }
+}
+
+impl<PinErr, TimerErr, DirErr, ModeErr, ResetErr, SwitchErr>
+    MotionError<PinErr, TimerErr, DirErr, ModeErr, ResetErr, core::convert::Infallible, SwitchErr>
+{
+    /// the same error with a stall error type, e.g. to join the errors of the
+    /// steps with the ones of the stall source
+    pub fn with_stall_error<StallErr>(
+        self,
+    ) -> MotionError<PinErr, TimerErr, DirErr, ModeErr, ResetErr, StallErr, SwitchErr> {
+        use MotionError::*;
+        match self {
+            Step(e) => Step(e),
+            Direction(e) => Direction(e),
+            StepMode(e) => StepMode(e),
+            Reset(e) => Reset(e),
+            Enable(e) => Enable(e),
+            Sleep(e) => Sleep(e),
+            Fault(e) => Fault(e),
+            DriverFault => DriverFault,
+            StallDetect(never) | HomeSwitch(never) => match never {},
+            Timer(e) => Timer(e),
+            Switch(e) => Switch(e),
+            SwitchNotFound => SwitchNotFound,
+            Busy => Busy,
+            SoftLimit => SoftLimit,
+            EndStop(direction) => EndStop(direction),
+            InvalidConfig => InvalidConfig,
+        }
+    }
+}
+
+impl<PinErr, TimerErr, DirErr, ModeErr, ResetErr, StallErr>
+    MotionError<PinErr, TimerErr, DirErr, ModeErr, ResetErr, StallErr, core::convert::Infallible>
+{
+    /// the same error with a switch error type, e.g. to join the errors of a
+    /// guard without switches with the ones of the end stops
+    pub fn with_switch_error<SwitchErr>(
+        self,
+    ) -> MotionError<PinErr, TimerErr, DirErr, ModeErr, ResetErr, StallErr, SwitchErr> {
+        use MotionError::*;
+        match self {
+            Step(e) => Step(e),
+            Direction(e) => Direction(e),
+            StepMode(e) => StepMode(e),
+            Reset(e) => Reset(e),
+            Enable(e) => Enable(e),
+            Sleep(e) => Sleep(e),
+            Fault(e) => Fault(e),
+            DriverFault => DriverFault,
+            StallDetect(e) => StallDetect(e),
+            HomeSwitch(e) => HomeSwitch(e),
+            Timer(e) => Timer(e),
+            Switch(never) => match never {},
+            SwitchNotFound => SwitchNotFound,
+            Busy => Busy,
+            SoftLimit => SoftLimit,
+            EndStop(direction) => EndStop(direction),
+            InvalidConfig => InvalidConfig,
+        }
+    }
+}
+
+/// Error returned by [MotionControlTrait::move_to_position](crate::MotionControlTrait::move_to_position)
+///
+/// the move stopped before reaching the target, `completed` is the number of
+/// steps actually done, it has the sign of the move direction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MoveError<PinErr, TimerErr, DirErr = PinErr, SwitchErr = core::convert::Infallible> {
+    /// completed steps before the error occurred
+    pub completed: i32,
+    /// the cause that stopped the move
+    pub error:
+        MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, core::convert::Infallible, SwitchErr>,
+}
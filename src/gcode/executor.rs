@@ -0,0 +1,451 @@
+//! executor of the parsed commands, refer [GcodeExecutor]
+
+use core::convert::Infallible;
+
+use super::parser::{parse, Axes, Command, ParseError};
+use crate::main::math::round;
+use crate::{MotionControlTrait, MotionError, MoveError, Num};
+
+/// Configuration of [GcodeExecutor]
+#[derive(Clone, Copy, Debug)]
+pub struct GcodeConfig {
+    /// steps per mm of X, Y, Z
+    pub steps_per_mm: [f64; 3],
+    /// accel of all moves, unit is mm per second^2
+    pub accel: f64,
+    /// feed of G0 and G28, unit is mm per minute
+    pub rapid_feed: f64,
+    /// feed of G1 until a F word is given, unit is mm per minute
+    pub default_feed: f64,
+}
+
+/// Error of executing a command, axis is 0(X), 1(Y) or 2(Z)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExecError<PinErr, TimerErr, DirErr = PinErr, SwitchErr = Infallible> {
+    /// the command uses an axis that is not attached to the executor
+    MissingAxis(usize),
+    /// the move of the axis stopped early
+    Move(usize, MoveError<PinErr, TimerErr, DirErr, SwitchErr>),
+    /// setting the position of the axis failed
+    Motion(
+        usize,
+        MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Infallible, SwitchErr>,
+    ),
+    /// the feed(the F word or the one of the config) is not positive
+    InvalidFeed,
+    /// the steps per mm or the accel of the axis is not positive, or the move
+    /// is too fast for the axis
+    InvalidConfig(usize),
+}
+
+/// Error of [GcodeExecutor::run]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcodeError<PinErr, TimerErr, DirErr = PinErr, SwitchErr = Infallible> {
+    Parse(ParseError),
+    /// the command of the line failed
+    Exec {
+        line: usize,
+        error: ExecError<PinErr, TimerErr, DirErr, SwitchErr>,
+    },
+}
+
+/// an axis driven by the executor, e.g. a [MontionCtrl](crate::MontionCtrl)
+pub type Axis<'a, PinErr, TimerErr, DirErr = PinErr, SwitchErr = Infallible> =
+    &'a mut dyn MotionControlTrait<
+        PinError = PinErr,
+        DirError = DirErr,
+        TimerError = TimerErr,
+        SwitchError = SwitchErr,
+    >;
+
+/// Run G-code commands on up to 3 axes(X, Y, Z)
+///
+/// each axis is an independent [MotionControlTrait], a move runs its axes one
+/// after another(X, Y, then Z) by `move_to_position`, with the feed as each
+/// axis's velocity. use [MultiAxisCtrl](crate::MultiAxisCtrl) if the axes must
+/// move together.
+///
+/// G28 moves the axes to position 0, the homing with switches is done by the
+/// app, e.g. [MontionCtrl::home](crate::MontionCtrl::home). M17/M18 are passed
+/// to the enable hook if any, otherwise they are ignored.
+pub struct GcodeExecutor<'a, PinErr, TimerErr, DirErr = PinErr, SwitchErr = Infallible> {
+    axes: [Option<Axis<'a, PinErr, TimerErr, DirErr, SwitchErr>>; 3],
+    enable_hook: Option<&'a mut dyn FnMut(bool)>,
+    config: GcodeConfig,
+    relative: bool,
+    /// unit is mm per minute
+    feed: f64,
+    /// logical position, unit is mm
+    position: [f64; 3],
+}
+
+impl<'a, PinErr, TimerErr, DirErr, SwitchErr>
+    GcodeExecutor<'a, PinErr, TimerErr, DirErr, SwitchErr>
+{
+    /// executor without axes, in absolute mode, at position 0
+    pub fn new(config: GcodeConfig) -> Self {
+        Self {
+            axes: [None, None, None],
+            enable_hook: None,
+            config,
+            relative: false,
+            feed: config.default_feed,
+            position: [0.0; 3],
+        }
+    }
+
+    pub fn with_x(mut self, axis: Axis<'a, PinErr, TimerErr, DirErr, SwitchErr>) -> Self {
+        self.axes[0] = Some(axis);
+        self
+    }
+
+    pub fn with_y(mut self, axis: Axis<'a, PinErr, TimerErr, DirErr, SwitchErr>) -> Self {
+        self.axes[1] = Some(axis);
+        self
+    }
+
+    pub fn with_z(mut self, axis: Axis<'a, PinErr, TimerErr, DirErr, SwitchErr>) -> Self {
+        self.axes[2] = Some(axis);
+        self
+    }
+
+    /// the hook is called with `true` on M17, `false` on M18
+    pub fn with_enable_hook(mut self, hook: &'a mut dyn FnMut(bool)) -> Self {
+        self.enable_hook = Some(hook);
+        self
+    }
+
+    /// logical position of X, Y, Z, unit is mm
+    pub fn position(&self) -> [f64; 3] {
+        self.position
+    }
+
+    /// Parse and execute a program, it stops at the first error
+    pub fn run(
+        &mut self,
+        program: &str,
+    ) -> Result<(), GcodeError<PinErr, TimerErr, DirErr, SwitchErr>> {
+        for command in parse(program) {
+            let (line, command) = command.map_err(GcodeError::Parse)?;
+            self.execute(&command)
+                .map_err(|error| GcodeError::Exec { line, error })?;
+        }
+        Ok(())
+    }
+
+    pub fn execute(
+        &mut self,
+        command: &Command,
+    ) -> Result<(), ExecError<PinErr, TimerErr, DirErr, SwitchErr>> {
+        match *command {
+            Command::Move {
+                rapid,
+                target,
+                feed,
+            } => {
+                if let Some(feed) = feed {
+                    self.feed = check_feed(feed)?;
+                }
+                let feed = if rapid {
+                    self.config.rapid_feed
+                } else {
+                    self.feed
+                };
+                let mut targets = target.to_array();
+                if self.relative {
+                    for (target, position) in targets.iter_mut().zip(self.position.iter()) {
+                        *target = target.map(|distance| position + distance);
+                    }
+                }
+                self.move_axes(targets, feed)
+            }
+            Command::Home(axes) => {
+                let all = axes == Axes::default();
+                let mut targets = [None; 3];
+                for (i, given) in axes.to_array().iter().enumerate() {
+                    if given.is_some() || (all && self.axes[i].is_some()) {
+                        targets[i] = Some(0.0);
+                    }
+                }
+                self.move_axes(targets, self.config.rapid_feed)
+            }
+            Command::Absolute => {
+                self.relative = false;
+                Ok(())
+            }
+            Command::Relative => {
+                self.relative = true;
+                Ok(())
+            }
+            Command::SetPosition(axes) => {
+                for (i, position) in axes.to_array().iter().enumerate() {
+                    if let Some(position) = *position {
+                        let step = round(position * self.steps_per_mm(i)?);
+                        self.axis(i)?
+                            .reset_position(step)
+                            .map_err(|e| ExecError::Motion(i, e))?;
+                        self.position[i] = position;
+                    }
+                }
+                Ok(())
+            }
+            Command::EnableMotors | Command::DisableMotors => {
+                if let Some(hook) = self.enable_hook.as_mut() {
+                    hook(*command == Command::EnableMotors);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// move the given axes one after another, unit of targets is mm
+    ///
+    /// all given axes are checked before moving: they must be attached, their
+    /// accel and velocity valid. the position of an axis is the one reached,
+    /// e.g. a move clamped by the soft limits stops short of the target.
+    fn move_axes(
+        &mut self,
+        targets: [Option<f64>; 3],
+        feed: f64,
+    ) -> Result<(), ExecError<PinErr, TimerErr, DirErr, SwitchErr>> {
+        let feed = check_feed(feed)?;
+        let mut rates = [(Num::ZERO, Num::ZERO); 3];
+        for (i, target) in targets.iter().enumerate() {
+            if target.is_some() {
+                self.axis(i)?;
+                let steps_per_mm = self.steps_per_mm(i)?;
+                let accel = positive_num(self.config.accel * steps_per_mm);
+                let velocity = positive_num(feed / 60.0 * steps_per_mm);
+                rates[i] = accel.zip(velocity).ok_or(ExecError::InvalidConfig(i))?;
+            }
+        }
+
+        for (i, target) in targets.iter().enumerate() {
+            let target = match *target {
+                Some(target) => target,
+                None => continue,
+            };
+            let steps_per_mm = self.config.steps_per_mm[i];
+            let (accel, velocity) = rates[i];
+            let start = round(self.position[i] * steps_per_mm);
+            let target_step = round(target * steps_per_mm);
+
+            let result = self.axis(i)?.move_to_position(accel, velocity, target_step);
+            match result {
+                // keep the fraction of a step if the target is reached
+                Ok(moved) if start + moved == target_step => self.position[i] = target,
+                Ok(moved) => self.position[i] = (start + moved) as f64 / steps_per_mm,
+                Err(e) => {
+                    // keep the position reached
+                    self.position[i] = (start + e.completed) as f64 / steps_per_mm;
+                    return Err(ExecError::Move(i, e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn steps_per_mm(
+        &self,
+        i: usize,
+    ) -> Result<f64, ExecError<PinErr, TimerErr, DirErr, SwitchErr>> {
+        let steps_per_mm = self.config.steps_per_mm[i];
+        if steps_per_mm.is_finite() && steps_per_mm > 0.0 {
+            Ok(steps_per_mm)
+        } else {
+            Err(ExecError::InvalidConfig(i))
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn axis(
+        &mut self,
+        i: usize,
+    ) -> Result<
+        &mut Axis<'a, PinErr, TimerErr, DirErr, SwitchErr>,
+        ExecError<PinErr, TimerErr, DirErr, SwitchErr>,
+    > {
+        self.axes[i].as_mut().ok_or(ExecError::MissingAxis(i))
+    }
+}
+
+fn check_feed<PinErr, TimerErr, DirErr, SwitchErr>(
+    feed: f64,
+) -> Result<f64, ExecError<PinErr, TimerErr, DirErr, SwitchErr>> {
+    if feed.is_finite() && feed > 0.0 {
+        Ok(feed)
+    } else {
+        Err(ExecError::InvalidFeed)
+    }
+}
+
+/// the value in Num, None if it is not positive or does not fit
+fn positive_num(value: f64) -> Option<Num> {
+    Num::checked_from_num(value).filter(|num| *num > Num::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim, SimA4988, SimConvert};
+    use crate::{LimitMode, MontionCtrl, SoftLimits};
+
+    fn config() -> GcodeConfig {
+        GcodeConfig {
+            steps_per_mm: [10.0; 3],
+            accel: 1000.0,
+            rapid_feed: 6000.0,
+            default_feed: 600.0,
+        }
+    }
+
+    fn ctrl(sim: &Sim) -> MontionCtrl<SimA4988, SimConvert> {
+        MontionCtrl::new(sim.a4988(), sim.convert())
+    }
+
+    /// the shortest step period of the pulses, unit is ns
+    fn min_period(pulses: &[(u64, u64)]) -> u64 {
+        pulses.windows(2).map(|w| w[1].0 - w[0].0).min().unwrap()
+    }
+
+    #[test]
+    fn g0_g1_should_move_each_axis_at_its_feed() {
+        let (sim_x, sim_y) = (Sim::new(), Sim::new());
+        let (mut x, mut y) = (ctrl(&sim_x), ctrl(&sim_y));
+        let mut executor = GcodeExecutor::new(config()).with_x(&mut x).with_y(&mut y);
+
+        // 20mm/s is 200 steps/s
+        executor.run("G1 X10 Y-5 F1200").unwrap();
+        assert_eq!((sim_x.position(), sim_y.position()), (100, -50));
+        assert!(min_period(&sim_x.pulses(Signal::Step)) >= 4_999_000);
+
+        // rapid 100mm/s is 1000 steps/s, F is kept for G1
+        sim_x.clear();
+        executor.run("G0 X-8").unwrap();
+        assert_eq!(sim_x.position(), -180);
+        assert!(min_period(&sim_x.pulses(Signal::Step)) < 1_001_000);
+        assert_eq!(executor.position(), [-8.0, -5.0, 0.0]);
+        assert_eq!(executor.feed, 1200.0);
+
+        // Z is checked before X moves
+        let error = executor.run("G1 X1 Z1").unwrap_err();
+        let error_z = ExecError::MissingAxis(2);
+        assert_eq!(
+            error,
+            GcodeError::Exec {
+                line: 1,
+                error: error_z
+            }
+        );
+        assert_eq!((x.current_step(), y.current_step()), (-80, -50));
+    }
+
+    #[test]
+    fn g90_g91_should_switch_the_coordinates() {
+        let sim = Sim::new();
+        let mut x = ctrl(&sim);
+        let mut executor = GcodeExecutor::new(config()).with_x(&mut x);
+
+        executor.run("G91\nG1 X1\nG1 X1.5").unwrap();
+        assert_eq!(executor.position()[0], 2.5);
+        executor.run("G90\nG1 X1").unwrap();
+        assert_eq!(executor.position()[0], 1.0);
+        assert_eq!(sim.position(), 10);
+        assert_eq!(sim.pulses(Signal::Step).len(), 40);
+    }
+
+    #[test]
+    fn g28_should_move_to_zero() {
+        let (sim_x, sim_y) = (Sim::new(), Sim::new());
+        let (mut x, mut y) = (ctrl(&sim_x), ctrl(&sim_y));
+        let mut executor = GcodeExecutor::new(config()).with_x(&mut x).with_y(&mut y);
+
+        executor.run("G1 X3 Y4\nG28 X0").unwrap();
+        assert_eq!((sim_x.position(), sim_y.position()), (0, 40));
+        // all attached axes, Z is not
+        executor.run("G28").unwrap();
+        assert_eq!((sim_x.position(), sim_y.position()), (0, 0));
+        assert_eq!(executor.position(), [0.0; 3]);
+    }
+
+    #[test]
+    fn m17_m18_should_call_the_enable_hook() {
+        let mut calls = Vec::new();
+        {
+            let mut hook = |enable| calls.push(enable);
+            let mut executor: GcodeExecutor<(), ()> =
+                GcodeExecutor::new(config()).with_enable_hook(&mut hook);
+            executor.run("M17\nM18\nM17").unwrap();
+        }
+        assert_eq!(calls, [true, false, true]);
+
+        // no hook, ignored
+        let mut executor: GcodeExecutor<(), ()> = GcodeExecutor::new(config());
+        executor.run("M17\nM18").unwrap();
+    }
+
+    #[test]
+    fn invalid_feed_and_config_should_be_rejected() {
+        let sim = Sim::new();
+        let mut x = ctrl(&sim);
+        let mut executor = GcodeExecutor::new(config()).with_x(&mut x);
+
+        for program in ["G1 X1 F0", "G1 X1 F-600"] {
+            let error = executor.run(program).unwrap_err();
+            let invalid = ExecError::InvalidFeed;
+            assert_eq!(
+                error,
+                GcodeError::Exec {
+                    line: 1,
+                    error: invalid
+                }
+            );
+        }
+        // the invalid F is not kept
+        executor.run("G1 X1").unwrap();
+        assert_eq!(sim.position(), 10);
+
+        let bad_configs = [
+            GcodeConfig {
+                accel: f64::NAN,
+                ..config()
+            },
+            GcodeConfig {
+                accel: -1.0,
+                ..config()
+            },
+            GcodeConfig {
+                steps_per_mm: [0.0; 3],
+                ..config()
+            },
+            GcodeConfig {
+                rapid_feed: 1e30,
+                ..config()
+            },
+        ];
+        for bad in bad_configs {
+            let mut executor = GcodeExecutor::new(bad).with_x(&mut x);
+            assert_eq!(
+                executor.execute(&Command::Home(Axes::default())),
+                Err(ExecError::InvalidConfig(0))
+            );
+        }
+        assert_eq!(sim.position(), 10);
+    }
+
+    #[test]
+    fn clamped_move_should_keep_the_position_reached() {
+        let sim = Sim::new();
+        let mut x = ctrl(&sim);
+        x.set_soft_limits(SoftLimits::new(-20, 50, LimitMode::Clamp));
+        let mut executor = GcodeExecutor::new(config()).with_x(&mut x);
+
+        executor.run("G1 X10").unwrap();
+        assert_eq!(sim.position(), 50);
+        assert_eq!(executor.position()[0], 5.0);
+
+        executor.run("G91\nG1 X-10").unwrap();
+        assert_eq!(sim.position(), -20);
+        assert_eq!(executor.position()[0], -2.0);
+    }
+}
@@ -0,0 +1,25 @@
+//! G-code
+//!
+//! a `no_std` parser of a G-code subset, and an executor that runs the parsed
+//! commands on [MotionControlTrait](crate::MotionControlTrait) axes.
+//!
+//! | command       | meaning                                  |
+//! |---------------|------------------------------------------|
+//! | G0/G1 X Y Z F | rapid/linear move, F unit is mm/min      |
+//! | G28 [X Y Z]   | move to home(position 0)                 |
+//! | G90/G91       | absolute/relative coordinates            |
+//! | G92 X Y Z     | set position without moving              |
+//! | M17/M18       | enable/disable motors                    |
+//!
+//! usage sample:
+//!
+//! ```rust,ignore
+//!   let mut executor = GcodeExecutor::new(config).with_x(&mut x_ctrl).with_y(&mut y_ctrl);
+//!   executor.run("G90\nG1 X10 Y5 F600\nG28")?;
+//! ```
+
+mod executor;
+mod parser;
+
+pub use self::executor::{Axis, ExecError, GcodeConfig, GcodeError, GcodeExecutor};
+pub use self::parser::{parse, parse_line, Axes, Command, ParseError, ParseErrorKind};
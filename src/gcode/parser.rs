@@ -0,0 +1,294 @@
+//! parser of the G-code subset, refer [Command]
+//!
+//! a line is a sequence of words, a word is a letter followed by a number,
+//! e.g. `G1 X10.5 F600`. the letters are case insensitive, the spaces between
+//! words are optional, `;` starts a comment to the end of line. a leading line
+//! number word(`N10`) is ignored.
+
+use nom::branch::alt;
+use nom::character::complete::{anychar, char, digit0, digit1, one_of, space0};
+use nom::combinator::{map_res, opt, recognize, verify};
+use nom::sequence::{pair, preceded, tuple};
+use nom::IResult;
+
+/// Coordinates of a command, unit is mm. `None` means the axis is not given
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Axes {
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub z: Option<f64>,
+}
+
+impl Axes {
+    /// the coordinates in order X, Y, Z
+    pub fn to_array(&self) -> [Option<f64>; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+/// The supported G-code commands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// G0(rapid) / G1(linear) move, feed unit is mm per minute
+    Move {
+        rapid: bool,
+        target: Axes,
+        feed: Option<f64>,
+    },
+    /// G28, move the given axes to home, all axes if none is given.
+    /// only whether an axis is given matters, not its value.
+    Home(Axes),
+    /// G90, coordinates are absolute
+    Absolute,
+    /// G91, coordinates are relative to the current position
+    Relative,
+    /// G92, set the current position of the given axes without moving
+    SetPosition(Axes),
+    /// M17
+    EnableMotors,
+    /// M18
+    DisableMotors,
+}
+
+/// What is wrong in a line, refer [ParseError]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// the text is not a word(letter + number)
+    Syntax,
+    /// the line has parameters but no G/M command
+    MissingCommand,
+    /// the G/M code is not supported
+    UnsupportedCommand,
+    /// the parameter is not supported by the command
+    UnexpectedParameter(char),
+}
+
+/// Error of parsing, line and column start at 1
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+/// a word of the line, the column is where its letter is
+#[derive(Clone, Copy)]
+struct Word {
+    letter: char,
+    value: f64,
+    column: usize,
+}
+
+/// the number of a word, g-code has no exponent
+fn number(input: &str) -> IResult<&str, f64> {
+    let digits = alt((
+        recognize(pair(digit1, opt(pair(char('.'), digit0)))),
+        recognize(pair(char('.'), digit1)),
+    ));
+    map_res(recognize(pair(opt(one_of("+-")), digits)), str::parse)(input)
+}
+
+fn word(input: &str) -> IResult<&str, (char, f64)> {
+    let letter = verify(anychar, |c: &char| c.is_ascii_alphabetic());
+    preceded(space0, tuple((letter, preceded(space0, number))))(input)
+}
+
+/// Parse a line. `line` is the line number reported in the error
+///
+/// return `Ok(None)` if the line has no command, e.g. empty or comment only.
+pub fn parse_line(text: &str, line: usize) -> Result<Option<Command>, ParseError> {
+    let error = |rest: &str, kind| ParseError {
+        line,
+        column: text.len() - rest.len() + 1,
+        kind,
+    };
+    let unexpected = |word: &Word| ParseError {
+        line,
+        column: word.column,
+        kind: ParseErrorKind::UnexpectedParameter(word.letter),
+    };
+
+    let code = match text.find(';') {
+        Some(end) => &text[..end],
+        None => text,
+    };
+
+    // at most a command and its parameters X Y Z F, and the line number
+    let mut words = [None::<Word>; 6];
+    let mut count = 0;
+    let mut rest = code;
+    while !rest.trim_start().is_empty() {
+        if count == words.len() {
+            return Err(error(rest.trim_start(), ParseErrorKind::Syntax));
+        }
+        let column = text.len() - rest.trim_start().len() + 1;
+        let (next, (letter, value)) =
+            word(rest).map_err(|_| error(rest.trim_start(), ParseErrorKind::Syntax))?;
+        rest = next;
+
+        let letter = letter.to_ascii_uppercase();
+        if letter == 'N' && count == 0 {
+            continue;
+        }
+        words[count] = Some(Word {
+            letter,
+            value,
+            column,
+        });
+        count += 1;
+    }
+    let mut words = words.iter().flatten();
+
+    let command = match words.next() {
+        Some(word) if word.letter == 'G' || word.letter == 'M' => *word,
+        Some(word) => {
+            return Err(ParseError {
+                line,
+                column: word.column,
+                kind: ParseErrorKind::MissingCommand,
+            })
+        }
+        None => return Ok(None),
+    };
+
+    let mut axes = Axes::default();
+    let mut feed = None;
+    // to report the parameters that the command does not take
+    let mut first_parameter = None;
+    let mut feed_word = None;
+    for word in words {
+        match word.letter {
+            'X' => axes.x = Some(word.value),
+            'Y' => axes.y = Some(word.value),
+            'Z' => axes.z = Some(word.value),
+            'F' => {
+                feed = Some(word.value);
+                feed_word = Some(*word);
+            }
+            _ => return Err(unexpected(word)),
+        }
+        first_parameter = first_parameter.or(Some(*word));
+    }
+
+    let no_parameters = |command| match first_parameter {
+        Some(word) => Err(unexpected(&word)),
+        None => Ok(Some(command)),
+    };
+    let no_feed = |command| match feed_word {
+        Some(word) => Err(unexpected(&word)),
+        None => Ok(Some(command)),
+    };
+
+    let unsupported = ParseError {
+        line,
+        column: command.column,
+        kind: ParseErrorKind::UnsupportedCommand,
+    };
+    // the codes are integers, e.g. G1.5 is not supported
+    let code = command.value as u32;
+    if code as f64 != command.value {
+        return Err(unsupported);
+    }
+
+    match (command.letter, code) {
+        ('G', 0) | ('G', 1) => Ok(Some(Command::Move {
+            rapid: code == 0,
+            target: axes,
+            feed,
+        })),
+        ('G', 28) => no_feed(Command::Home(axes)),
+        ('G', 90) => no_parameters(Command::Absolute),
+        ('G', 91) => no_parameters(Command::Relative),
+        ('G', 92) => no_feed(Command::SetPosition(axes)),
+        ('M', 17) => no_parameters(Command::EnableMotors),
+        ('M', 18) => no_parameters(Command::DisableMotors),
+        _ => Err(unsupported),
+    }
+}
+
+/// Parse a program, it yields the commands with their line number
+///
+/// lines without command are skipped. the iteration continues after an error,
+/// the app can stop at the first one.
+pub fn parse(program: &str) -> impl Iterator<Item = Result<(usize, Command), ParseError>> + '_ {
+    program
+        .lines()
+        .enumerate()
+        .filter_map(|(i, text)| match parse_line(text, i + 1) {
+            Ok(Some(command)) => Some(Ok((i + 1, command))),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_should_parse_moves() {
+        let command = parse_line("N10 g1 X10.5 y-2 F600 ; cut", 1).unwrap();
+        assert_eq!(
+            command,
+            Some(Command::Move {
+                rapid: false,
+                target: Axes {
+                    x: Some(10.5),
+                    y: Some(-2.0),
+                    z: None
+                },
+                feed: Some(600.0),
+            })
+        );
+        assert_eq!(
+            parse_line("G0Z.5", 1).unwrap(),
+            Some(Command::Move {
+                rapid: true,
+                target: Axes {
+                    x: None,
+                    y: None,
+                    z: Some(0.5)
+                },
+                feed: None,
+            })
+        );
+        assert_eq!(parse_line("  ; comment only", 1).unwrap(), None);
+    }
+
+    #[test]
+    fn parse_line_should_parse_other_commands() {
+        assert_eq!(
+            parse_line("G28", 1).unwrap(),
+            Some(Command::Home(Axes::default()))
+        );
+        assert_eq!(parse_line("G90", 1).unwrap(), Some(Command::Absolute));
+        assert_eq!(parse_line("G91", 1).unwrap(), Some(Command::Relative));
+        assert_eq!(
+            parse_line("G92 X0", 1).unwrap(),
+            Some(Command::SetPosition(Axes {
+                x: Some(0.0),
+                y: None,
+                z: None
+            }))
+        );
+        assert_eq!(parse_line("M17", 1).unwrap(), Some(Command::EnableMotors));
+        assert_eq!(parse_line("M18", 1).unwrap(), Some(Command::DisableMotors));
+    }
+
+    #[test]
+    fn parse_should_report_line_and_column() {
+        let program = "G90\n\nG1 X1 Y#2\nG2 X1\nG1 X1 E2\nX1\nG90 F100";
+        let errors: Vec<_> = parse(program).filter_map(Result::err).collect();
+        let expected = [
+            (3, 7, ParseErrorKind::Syntax),
+            (4, 1, ParseErrorKind::UnsupportedCommand),
+            (5, 7, ParseErrorKind::UnexpectedParameter('E')),
+            (6, 1, ParseErrorKind::MissingCommand),
+            (7, 5, ParseErrorKind::UnexpectedParameter('F')),
+        ];
+        assert_eq!(errors.len(), expected.len());
+        for (error, &(line, column, kind)) in errors.iter().zip(expected.iter()) {
+            assert_eq!(*error, ParseError { line, column, kind });
+        }
+    }
+}
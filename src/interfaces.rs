@@ -2,9 +2,11 @@
 //!
 
 use embedded_hal::digital::v2::OutputPin;
-use super::Direction;
+use super::{Direction, EndStopAction, FromTimerErrorTrait, MotionError, MoveError};
 pub type Num = ramp_maker::trapezoidal::DefaultNum;
-// use core::convert::Infallible;
+/// signed [Num], e.g. the velocity of [MotionControlTrait::enter_velocity_mode]
+pub type SignedNum = fixed::FixedI64<typenum::U32>;
+use core::convert::Infallible;
 
 /// Enable microstepping mode control for a driver
 ///
@@ -75,6 +77,12 @@ pub trait SetDirectionTrait {
     fn set_backward(&mut self)-> Result<(), Self::Error>;
     fn set_forward(&mut self)-> Result<(), Self::Error>;
     fn dir_pin(&mut self) -> &mut Self::Dir;
+
+    /// The DIR setup time used by the motion control, default is
+    /// [SetDirectionTrait::SETUP_TIME]. override it for timing set at runtime
+    fn setup_time(&self) -> fugit::NanosDurationU64 {
+        Self::SETUP_TIME
+    }
 }
 
 /// Enable step control for a driver
@@ -105,36 +113,85 @@ pub trait StepTrait {
     fn set_low(&mut self)-> Result<(), Self::Error>;
     fn setp_pin(&mut self) -> &mut Self::Step;
 
+    /// The STEP pulse length used by the motion control, default is
+    /// [StepTrait::PULSE_LENGTH]. override it for timing set at runtime
+    fn pulse_length(&self) -> fugit::NanosDurationU64 {
+        Self::PULSE_LENGTH
+    }
+
 }
 
 
 /// Implemented by drivers that have motion control capabilities
 ///
+#[allow(clippy::type_complexity)]
 pub trait MotionControlTrait {
+    /// The error of the driver's STEP pin, and of the guard's pins(e.g. the
+    /// fault pin)
+    type PinError;
+    /// The error of the driver's DIR pin
+    type DirError;
+    /// The error of the timer, refer [DelayToTicksTrait::Error]
+    type TimerError;
+    /// The error of the guard's switches, refer [StepGuardTrait::SwitchError]
+    type SwitchError;
+
     /// Move to the given position. accel unit is steps per second^2, velocity unit is steps per second
-    /// result repcent completed steps.
+    /// result repcent completed steps. if the move stops early, the error
+    /// carries the steps completed before it stopped. it returns
+    /// [MotionError::Busy] while velocity mode is running.
     /// the target_step is a value from home pos. for example, priviously has arrive pos(target_step=Y),
     /// when you call it again with target_step=Y, the motion will not move. if you call it with target_step=Y-1,
     /// the motion will move backward one step
-    /// 
+    ///
+    /// ```text
     ///       negative                          positive
     ///    <------------------------------+--------------------------->
     ///         backward                home      forward
+    /// ```
     fn move_to_position(
         &mut self,
         target_accel: Num,
         max_velocity: Num,
         target_step: i32,
-    ) -> Result<i32,i32>;
+    ) -> Result<i32, MoveError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>>;
 
     /// Reset internal position to the given value
     ///
     /// This method must not start a motion. Its only purpose is to change the
     /// driver's internal position value, for example for homing.
-    fn reset_position(&mut self, step: i32) -> Result<(), ()>;
+    fn reset_position(&mut self, step: i32) -> Result<(), MotionError<Self::PinError, Self::TimerError, Self::DirError, Self::PinError, Self::PinError, Infallible, Self::SwitchError>>;
 
-    fn set_direction(&mut self, direction: Direction) -> Result<(), ()>;
-    fn step(&mut self) -> Result<(), ()>;
+    /// Enter velocity(jog) mode, or change the target of the running one
+    ///
+    /// the motor accelerates(accel unit is steps per second^2) to the target
+    /// velocity(unit is steps per second), a positive velocity is forward, a
+    /// negative one backward. if it is running in the other direction, it
+    /// decelerates to standstill first. zero is the same as
+    /// [MotionControlTrait::stop]. the motion is driven by
+    /// [MotionControlTrait::velocity_step].
+    fn enter_velocity_mode(&mut self, target_accel: Num, velocity: SignedNum);
+
+    /// Decelerate the velocity mode smoothly to standstill
+    fn stop(&mut self);
+
+    /// Do the next step of velocity mode, it blocks for one step period
+    ///
+    /// return `Ok(false)` when the motor is at standstill and no velocity is
+    /// requested, e.g. after [MotionControlTrait::stop] has finished. typical usage:
+    ///
+    /// ```rust,ignore
+    ///   ctrl.enter_velocity_mode(accel, SignedNum::from_num(-200));
+    ///   while ctrl.velocity_step()? {
+    ///       if button_released() {
+    ///           ctrl.stop();
+    ///       }
+    ///   }
+    /// ```
+    fn velocity_step(&mut self) -> Result<bool, MotionError<Self::PinError, Self::TimerError, Self::DirError, Self::PinError, Self::PinError, Infallible, Self::SwitchError>>;
+
+    fn set_direction(&mut self, direction: Direction) -> Result<(), MotionError<Self::PinError, Self::TimerError, Self::DirError, Self::PinError, Self::PinError, Infallible, Self::SwitchError>>;
+    fn step(&mut self) -> Result<(), MotionError<Self::PinError, Self::TimerError, Self::DirError, Self::PinError, Self::PinError, Infallible, Self::SwitchError>>;
     fn step_high(&mut self);
     fn step_low(&mut self);
     
@@ -142,13 +199,148 @@ pub trait MotionControlTrait {
     fn help_delay_ns(&mut self, timeout: u64);
 }
 
+/// Checked by [MontionCtrl](crate::MontionCtrl) before every step
+///
+/// a guard can abort the motion in progress, e.g. the end stop switches
+/// ([EndStops](crate::EndStops)) or the driver fault([FaultGuard](crate::FaultGuard)).
+/// `()` is the guard that never aborts, a tuple `(A, B)` checks both.
+#[allow(clippy::type_complexity)]
+pub trait StepGuardTrait<DRIVER, PinErr> {
+    /// The error of the switches it reads, reported as [MotionError::Switch],
+    /// `Infallible` if it reads none
+    type SwitchError;
+
+    /// check before a step toward `direction`, the returned error aborts the motion
+    fn check<TimerErr, DirErr>(
+        &mut self,
+        driver: &mut DRIVER,
+        direction: Direction,
+    ) -> Result<(), MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Infallible, Self::SwitchError>>;
+
+    /// how to stop after [MotionError::EndStop], default is hard stop
+    fn end_stop_action(&self) -> EndStopAction {
+        EndStopAction::HardStop
+    }
+}
+
+impl<DRIVER, PinErr> StepGuardTrait<DRIVER, PinErr> for () {
+    type SwitchError = Infallible;
+
+    fn check<TimerErr, DirErr>(
+        &mut self,
+        _driver: &mut DRIVER,
+        _direction: Direction,
+    ) -> Result<(), MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Infallible, Self::SwitchError>> {
+        Ok(())
+    }
+}
+
+/// both guards are checked, the first error aborts the motion. the first
+/// one reads no switch, e.g. `(FaultGuard, end_stops)`
+impl<DRIVER, PinErr, A, B> StepGuardTrait<DRIVER, PinErr> for (A, B)
+where
+    A: StepGuardTrait<DRIVER, PinErr, SwitchError = Infallible>,
+    B: StepGuardTrait<DRIVER, PinErr>,
+{
+    type SwitchError = B::SwitchError;
+
+    fn check<TimerErr, DirErr>(
+        &mut self,
+        driver: &mut DRIVER,
+        direction: Direction,
+    ) -> Result<(), MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Infallible, Self::SwitchError>> {
+        self.0
+            .check(driver, direction)
+            .map_err(MotionError::with_switch_error)?;
+        self.1.check(driver, direction)
+    }
+
+    fn end_stop_action(&self) -> EndStopAction {
+        match self.0.end_stop_action() {
+            EndStopAction::HardStop => self.1.end_stop_action(),
+            action => action,
+        }
+    }
+}
+
+/// A source of stall detection for the sensorless homing, refer
+/// [MontionCtrl::home_sensorless](crate::MontionCtrl::home_sensorless)
+///
+/// the source is a DIAG pin([DiagPin](crate::DiagPin)), or the driver itself
+/// by polling its registers([DriverStall](crate::DriverStall)).
+#[allow(clippy::type_complexity)]
+pub trait StallDetectTrait<DRIVER, PinErr> {
+    /// The error of the stall source, reported as [MotionError::StallDetect]
+    type StallError;
+
+    /// whether the motor is stalled now
+    fn is_stalled<TimerErr, DirErr>(
+        &mut self,
+        driver: &mut DRIVER,
+    ) -> Result<bool, MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Self::StallError>>;
+}
+
 /// Implemented by drivers that have motion control capabilities
 ///
+#[allow(clippy::type_complexity)]
 pub trait MotionControlStepModeTrait {
     type  StepMode;
+    /// The error of the driver's mode pins
+    type PinError;
+    /// The error of the driver's reset pin
+    type ResetError;
+    /// The error of the timer, refer [DelayToTicksTrait::Error]
+    type TimerError;
     /// Set step mode of the wrapped driver
     ///
-    fn set_step_mode(&mut self, step_mode: Self::StepMode) -> Result<(), ()>;
+    fn set_step_mode(&mut self, step_mode: Self::StepMode) -> Result<(), MotionError<Self::PinError, Self::TimerError, Self::PinError, Self::PinError, Self::ResetError>>;
+}
+
+/// async counterpart of [MotionControlTrait], refer
+/// [AsyncMontionCtrl](crate::AsyncMontionCtrl)
+///
+/// the waits are awaited instead of blocking, so several axes can run as
+/// concurrent tasks of an async executor.
+#[cfg(feature = "async")]
+#[allow(async_fn_in_trait, clippy::type_complexity)]
+pub trait AsyncMotionControlTrait {
+    /// The error of the driver's STEP pin
+    type PinError;
+    /// The error of the driver's DIR pin
+    type DirError;
+    /// The error of the delay
+    type TimerError;
+    /// The error of the guard's switches, refer [StepGuardTrait::SwitchError]
+    type SwitchError;
+
+    /// Move to the given position, refer [MotionControlTrait::move_to_position]
+    async fn move_to_position(
+        &mut self,
+        target_accel: Num,
+        max_velocity: Num,
+        target_step: i32,
+    ) -> Result<i32, MoveError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>>;
+
+    /// Reset internal position to the given value, refer [MotionControlTrait::reset_position]
+    fn reset_position(&mut self, step: i32) -> Result<(), MotionError<Self::PinError, Self::TimerError, Self::DirError, Self::PinError, Self::PinError, Infallible, Self::SwitchError>>;
+
+    async fn set_direction(&mut self, direction: Direction) -> Result<(), MotionError<Self::PinError, Self::TimerError, Self::DirError, Self::PinError, Self::PinError, Infallible, Self::SwitchError>>;
+    async fn step(&mut self) -> Result<(), MotionError<Self::PinError, Self::TimerError, Self::DirError, Self::PinError, Self::PinError, Infallible, Self::SwitchError>>;
+}
+
+/// async counterpart of [MotionControlStepModeTrait]
+#[cfg(feature = "async")]
+#[allow(async_fn_in_trait)]
+pub trait AsyncMotionControlStepModeTrait {
+    type StepMode;
+    /// The error of the driver's mode pins
+    type PinError;
+    /// The error of the driver's reset pin
+    type ResetError;
+    /// The error of the delay
+    type TimerError;
+    /// Set step mode of the wrapped driver
+    async fn set_step_mode(&mut self, step_mode: Self::StepMode) -> Result<(), MotionError<Self::PinError, Self::TimerError, Self::PinError, Self::PinError, Self::ResetError>>;
 }
 
 
@@ -161,33 +353,78 @@ pub trait MotionControlStepModeTrait {
 ///
 /// the lib crate default implement for stm32-hal. refer[Stm32HalCounterWrapper]
 pub trait DelayToTicksTrait {
+    /// The error that can occur while waiting on the timer
+    type Error;
+
     /// Convert ramp delay value into fugit::NanosDurationU64
     fn rampdelay_to_nano(&self, delay: Num) -> fugit::NanosDurationU64 {
-        let delay_s = delay;
-        // let ticks = mrt::Ticks::try_from(Seconds(delay_s.int().to_num())).unwrap();
-        let mut out = fugit::NanosDurationU64::secs( delay_s.int().to_num() );
-
-        let delay_ms = delay_s.frac() * 1000;
-        out += fugit::NanosDurationU64::millis( delay_ms.int().to_num() );
-
-        let delay_us = delay_ms.frac() * 1000;
-        out += fugit::NanosDurationU64::micros( delay_us.int().to_num() );       
-
-        let delay_ns = delay_us.frac() * 1000;
-        out +=fugit::NanosDurationU64::nanos( delay_ns.int().to_num() );
-
-        out
+        rampdelay_to_nano(delay)
     }
 
     /// platform implement,
     /// internal logic is: start counter---> do closure action ---> wait until timeout
     /// if return err, will cause skip next actions in the flow that called it.
     /// so you should better carefully deal it.
-    fn wait(&mut self, timeout: &fugit::NanosDurationU64, closure: impl FnMut()-> Result<(), ()>) -> Result<(), ()>;
-    
+    ///
+    /// the closure's error should be returned as it is, the timer's error
+    /// should be returned by [FromTimerErrorTrait], e.g. [MotionError::Timer].
+    ///
+    /// the timeout is a minimum timing(e.g. DIR setup, STEP high), it must not
+    /// be shortened.
+    fn wait<E: FromTimerErrorTrait<Self::Error>>(
+        &mut self,
+        timeout: &fugit::NanosDurationU64,
+        closure: impl FnMut() -> Result<(), E>,
+    ) -> Result<(), E>;
+
+    /// the wait that ends a step period(the STEP low), the default is
+    /// [DelayToTicksTrait::wait].
+    ///
+    /// a convert with a timestamp source may end it at a deadline of the
+    /// step period instead, so the overhead of the previous waits does not
+    /// add to the period, refer [DeadlineConvert](crate::deadline_convert::DeadlineConvert).
+    fn wait_period<E: FromTimerErrorTrait<Self::Error>>(
+        &mut self,
+        timeout: &fugit::NanosDurationU64,
+        closure: impl FnMut() -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.wait(timeout, closure)
+    }
+}
+
+/// A free-running timestamp source, e.g. a 64-bit extended hardware counter
+/// or the DWT cycle counter
+///
+/// [DeadlineConvert](crate::deadline_convert::DeadlineConvert) schedules the
+/// step periods against absolute deadlines by it, and
+/// [MontionCtrl::calibrate_step_rate](crate::MontionCtrl::calibrate_step_rate)
+/// measures the achieved step rate by it.
+pub trait TimestampTrait {
+    /// time since an arbitrary epoch, it must not go backward
+    fn now(&mut self) -> fugit::NanosDurationU64;
 }
 
 
+/// Convert ramp delay value(unit is second) into fugit::NanosDurationU64
+///
+/// shared by [DelayToTicksTrait::rampdelay_to_nano] and the non-blocking
+/// motion engine, which has no convert of its own.
+pub(crate) fn rampdelay_to_nano(delay: Num) -> fugit::NanosDurationU64 {
+    let delay_s = delay;
+    // let ticks = mrt::Ticks::try_from(Seconds(delay_s.int().to_num())).unwrap();
+    let mut out = fugit::NanosDurationU64::secs( delay_s.int().to_num() );
+
+    let delay_ms = delay_s.frac() * 1000;
+    out += fugit::NanosDurationU64::millis( delay_ms.int().to_num() );
+
+    let delay_us = delay_ms.frac() * 1000;
+    out += fugit::NanosDurationU64::micros( delay_us.int().to_num() );
+
+    let delay_ns = delay_us.frac() * 1000;
+    out +=fugit::NanosDurationU64::nanos( delay_ns.int().to_num() );
+
+    out
+}
 
 //////////////////////////////
 pub trait EnableResetControlTrait<Resources> {
@@ -210,9 +447,146 @@ pub trait ResetTrait {
 
     /// The error that can occur while accessing the STEP pin
     type Error;
-        /// 
+
+    /// Re-enable the driver, e.g. release it from reset/standby
     fn enable_driver(&mut self) -> Result<(), Self::Error>;
 
 }
 
+/// The reset that follows [SetStepModeTrait::apply_mode_config], done by
+/// [MotionControlStepModeTrait::set_step_mode]
+///
+/// every [ResetTrait] driver implements it, it re-enables the driver to
+/// latch the new mode. a driver that applies the mode at once(e.g. DRV8834,
+/// TMC2209) implements it with the defaults, which do nothing.
+pub trait StepModeResetTrait {
+    /// The time to wait after the reset, before the driver accepts steps
+    const MODE_RESET_TIME: fugit::NanosDurationU64 = fugit::NanosDurationU64::from_ticks(0);
+
+    /// The error that can occur while resetting
+    type ModeResetError;
+
+    /// reset the driver to apply the new mode, the default does nothing
+    fn reset_for_mode(&mut self) -> Result<(), Self::ModeResetError> {
+        Ok(())
+    }
+}
+
+impl<T: ResetTrait> StepModeResetTrait for T {
+    const MODE_RESET_TIME: fugit::NanosDurationU64 =
+        fugit::NanosDurationU64::from_ticks(T::RESET_SETUP_TIME.ticks() + T::RESET_HOLD_TIME.ticks());
+
+    type ModeResetError = T::Error;
+
+    fn reset_for_mode(&mut self) -> Result<(), Self::ModeResetError> {
+        self.enable_driver()
+    }
+}
+
+
+//////////////////////////////
+/// Enable output enable(ENABLE pin) control for a driver
+pub trait EnableOutputControlTrait<Resources> {
+    /// The type of the driver after output enable control has been enabled
+    type WithOutputControl: OutputEnableTrait;
+
+    /// Enable output enable control
+    fn enable_output_control(self, res: Resources) -> Self::WithOutputControl;
+}
+
+/// Implemented by drivers that can switch their power stage on/off
+///
+/// disabled outputs de-energize the coils, the motor loses its holding torque.
+pub trait OutputEnableTrait {
+    /// The time after enabling the outputs before the first step
+    const ENABLE_SETUP_TIME: fugit::NanosDurationU64;
+
+    /// The error that can occur while accessing the ENABLE pin
+    type Error;
+
+    fn enable_output(&mut self) -> Result<(), Self::Error>;
+    fn disable_output(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Enable sleep(SLEEP pin) control for a driver
+pub trait EnableSleepControlTrait<Resources> {
+    /// The type of the driver after sleep control has been enabled
+    type WithSleepControl: SleepTrait;
+
+    /// Enable sleep control
+    fn enable_sleep_control(self, res: Resources) -> Self::WithSleepControl;
+}
+
+/// Implemented by drivers that have a low power sleep mode
+pub trait SleepTrait {
+    /// The time after waking up before the first step
+    const WAKE_UP_TIME: fugit::NanosDurationU64;
+
+    /// The error that can occur while accessing the SLEEP pin
+    type Error;
+
+    fn sleep(&mut self) -> Result<(), Self::Error>;
+    fn wake_up(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Enable fault(FAULT pin) reporting for a driver
+pub trait EnableFaultControlTrait<Resources> {
+    /// The type of the driver after fault reporting has been enabled
+    type WithFaultControl: FaultTrait;
+
+    /// Enable fault reporting
+    fn enable_fault_control(self, res: Resources) -> Self::WithFaultControl;
+}
+
+/// Implemented by drivers that report faults, e.g. overcurrent or overheat
+pub trait FaultTrait {
+    /// The error that can occur while reading the FAULT pin
+    type Error;
+
+    /// whether the driver is in fault state
+    fn is_fault(&mut self) -> Result<bool, Self::Error>;
+}
+
+/// Implemented by drivers that detect stalls by themselves, e.g. StallGuard
+pub trait StallTrait {
+    /// The error that can occur while reading the stall state
+    type Error;
+
+    /// whether the motor is stalled now
+    fn is_stalled(&mut self) -> Result<bool, Self::Error>;
+}
 
+/// The level of a tri-state mode pin, refer [TriStatePinTrait]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TriState {
+    Low,
+    High,
+    /// high impedance, the pin is not driven
+    Floating,
+}
+
+/// Implemented by pins that drive low/high or float, e.g. the three-level
+/// mode pins of DRV8834/DRV8880
+///
+/// a GPIO usually floats by switching to input(without pull), the app wraps
+/// it, e.g.:
+///
+/// ```rust,ignore
+///   impl TriStatePinTrait for ModePin {
+///       type Error = Infallible;
+///       fn set_tri_state(&mut self, state: TriState) -> Result<(), Self::Error> {
+///           match state {
+///               TriState::Floating => self.0.make_floating_input(),
+///               TriState::Low => self.0.make_push_pull_output_in_state(PinState::Low),
+///               TriState::High => self.0.make_push_pull_output_in_state(PinState::High),
+///           }
+///           Ok(())
+///       }
+///   }
+/// ```
+pub trait TriStatePinTrait {
+    /// The error that can occur while switching the pin
+    type Error;
+
+    fn set_tri_state(&mut self, state: TriState) -> Result<(), Self::Error>;
+}
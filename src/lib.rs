@@ -1,20 +1,49 @@
 // #![no_std]
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "sim")), no_std)]
+// the motion control lives in src/main, it is not a binary crate
+#![allow(special_module_name)]
 
 mod drivers;
+#[cfg(feature = "eh1")]
+pub mod eh1;
+mod error;
+pub mod gcode;
 mod interfaces;
 mod main;
 // pub mod compat;
 // pub mod compat_fugit;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+pub mod countdown_convert;
+pub mod deadline_convert;
 pub mod step_mode;
 pub mod stm32f4xx_convert;
-pub use drivers::{drv8825::DRV8825, stspin220::STSPIN220,a4988::A4988,soft::SOFT};
+#[cfg(feature = "embedded-time")]
+pub mod timeint_convert;
+pub use drivers::{drv8825::DRV8825, drv8834::DRV8834, drv8880::DRV8880, lv8729::LV8729, stspin220::{STSPIN220, EnableFaultPin},a4988::A4988,soft::{SOFT, SOFTRT},tmc2209::TMC2209,tmc5160::{TMC5160, TMC2130},uln2003::ULN2003,hbridge::{HBRIDGE, CoilCurrentTrait},NoPin,Inverted,
+    puldirena::{PULDIRENA, TB6600, DM542, DM432C}};
+/// registers, datagrams and status of TMC2209
+pub use drivers::tmc2209;
+/// typed registers and status of TMC5160/TMC2130
+pub use drivers::tmc5160;
 pub use interfaces::{
     EnableDirectionControlTrait, EnableStepControlTrait,EnableResetControlTrait,
     EnableStepModeControlTrait, SetDirectionTrait, SetStepModeTrait, StepTrait,ResetTrait,
-    Num,MotionControlTrait,MotionControlStepModeTrait,DelayToTicksTrait,
+    Num,SignedNum,MotionControlTrait,MotionControlStepModeTrait,DelayToTicksTrait,StepGuardTrait,
+    EnableOutputControlTrait, OutputEnableTrait, EnableSleepControlTrait, SleepTrait,
+    EnableFaultControlTrait, FaultTrait, StallDetectTrait, StallTrait, TriState, TriStatePinTrait,
+    TimestampTrait, StepModeResetTrait,
+};
+#[cfg(feature = "async")]
+pub use interfaces::{AsyncMotionControlStepModeTrait, AsyncMotionControlTrait};
+#[cfg(feature = "async")]
+pub use main::AsyncMontionCtrl;
+pub use error::{FromTimerErrorTrait, MotionError, MoveError};
+pub use main::{
+    AxesTrait, DiagPin, DriverStall, EndStopAction, EndStops, EngineError, FaultGuard,
+    HomingConfig, LimitMode, MontionCtrl, MotionEngine, MultiAxisCtrl, NoSwitch, SCurve,
+    SensorlessHomingConfig, SoftLimits, StepProfile, StepRateReport, SwitchTrait,
 };
-pub use main::MontionCtrl;
 
 pub extern crate embedded_hal;
 pub extern crate fixed;
@@ -37,7 +66,7 @@ pub enum Direction {
 }
 
 // use core::convert::{/*Infallible,*/ TryFrom, TryInto as _};
-/// only timer's internal err return error
+// only timer's internal err return error
 //
 
 
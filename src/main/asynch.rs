@@ -0,0 +1,445 @@
+//! AsyncMontionCtrl
+//!
+//! async counterpart of [MontionCtrl](super::MontionCtrl), enabled by the
+//! `async` feature. it awaits an `embedded_hal_async::delay::DelayNs`(e.g.
+//! `embassy_time::Delay`) instead of blocking on a counter, so each axis can
+//! be a task of the executor:
+//!
+//! ```rust,ignore
+//!   #[embassy_executor::task(pool_size = 2)]
+//!   async fn axis(mut ctrl: AsyncMontionCtrl<Driver, Delay>, target: i32) {
+//!       ctrl.move_to_position(accel, velocity, target).await.unwrap();
+//!   }
+//! ```
+//!
+//! the pins are toggled before each delay, their time adds to it. velocity
+//! mode and homing are only provided by MontionCtrl. the soft limits, the
+//! guard and the ramp are planned the same way as MontionCtrl's.
+
+use core::convert::Infallible;
+
+use embedded_hal_async::delay::DelayNs;
+use ramp_maker::Trapezoidal;
+
+use super::limits::SoftLimits;
+use super::plan;
+use super::GuardedError;
+use super::stepprofile::{Num, StepProfile};
+use crate::interfaces::{
+    rampdelay_to_nano, AsyncMotionControlStepModeTrait, AsyncMotionControlTrait, StepGuardTrait,
+};
+use crate::{
+    Direction, MotionError, MoveError, SetDirectionTrait, SetStepModeTrait, StepModeResetTrait,
+    StepTrait,
+};
+
+/// Profile and Guard are the same as [MontionCtrl](super::MontionCtrl)'s
+pub struct AsyncMontionCtrl<DRIVER, Delay, Profile = Trapezoidal, Guard = ()> {
+    driver: DRIVER,
+    current_step: i32,
+    current_direction: Direction,
+    delay: Delay,
+    profile: Profile,
+    soft_limits: Option<SoftLimits>,
+    guard: Guard,
+}
+
+impl<DRIVER, Delay> AsyncMontionCtrl<DRIVER, Delay> {
+    pub fn new(driver: DRIVER, delay: Delay) -> Self {
+        // the accel will be replaced by each move
+        Self::with_profile(driver, delay, Trapezoidal::new(Num::from_num(1)))
+    }
+}
+
+impl<DRIVER, Delay, Profile> AsyncMontionCtrl<DRIVER, Delay, Profile> {
+    /// create with the given motion profile, e.g. [SCurve](super::SCurve)
+    pub fn with_profile(driver: DRIVER, delay: Delay, profile: Profile) -> Self {
+        Self {
+            driver,
+            current_step: 0,
+            current_direction: Direction::Forward,
+            delay,
+            profile,
+            soft_limits: None,
+            guard: (),
+        }
+    }
+}
+
+impl<DRIVER, Delay, Profile, Guard> AsyncMontionCtrl<DRIVER, Delay, Profile, Guard> {
+    /// attach a guard that is checked before every step, e.g. [EndStops](super::EndStops).
+    /// it replaces the previous one.
+    pub fn with_guard<G>(self, guard: G) -> AsyncMontionCtrl<DRIVER, Delay, Profile, G> {
+        AsyncMontionCtrl {
+            driver: self.driver,
+            current_step: self.current_step,
+            current_direction: self.current_direction,
+            delay: self.delay,
+            profile: self.profile,
+            soft_limits: self.soft_limits,
+            guard,
+        }
+    }
+
+    pub fn guard_mut(&mut self) -> &mut Guard {
+        &mut self.guard
+    }
+
+    pub fn driver_mut(&mut self) -> &mut DRIVER {
+        &mut self.driver
+    }
+
+    pub fn release(self) -> (DRIVER, Delay) {
+        (self.driver, self.delay)
+    }
+
+    /// current position, unit is step
+    pub fn current_step(&self) -> i32 {
+        self.current_step
+    }
+
+    /// enable soft travel limits, they apply to `move_to_position` and `step`
+    pub fn set_soft_limits(&mut self, limits: SoftLimits) {
+        self.soft_limits = Some(limits);
+    }
+
+    pub fn disable_soft_limits(&mut self) {
+        self.soft_limits = None;
+    }
+
+    pub fn soft_limits(&self) -> Option<SoftLimits> {
+        self.soft_limits
+    }
+}
+
+impl<DRIVER, Delay, Profile, Guard> AsyncMontionCtrl<DRIVER, Delay, Profile, Guard>
+where
+    Delay: DelayNs,
+{
+    async fn wait(&mut self, timeout: fugit::NanosDurationU64) {
+        // DelayNs takes u32 ns, about 4.3s at most
+        let mut nanos = timeout.ticks();
+        while nanos > 0 {
+            let chunk = nanos.min(u32::MAX as u64);
+            self.delay.delay_ns(chunk as u32).await;
+            nanos -= chunk;
+        }
+    }
+}
+
+impl<DRIVER, Delay, Profile, Guard> AsyncMontionCtrl<DRIVER, Delay, Profile, Guard>
+where
+    DRIVER: StepTrait,
+    Delay: DelayNs,
+{
+    /// output one STEP pulse, `delay` is the whole step period(high + low)
+    async fn pulse<DirErr, SwitchErr>(
+        &mut self,
+        delay: fugit::NanosDurationU64,
+    ) -> Result<(), GuardedError<<DRIVER as StepTrait>::Error, Infallible, DirErr, SwitchErr>> {
+        let pulse_length = self.driver.pulse_length();
+        self.driver.set_high().map_err(MotionError::Step)?;
+        self.wait(pulse_length).await;
+
+        let delay_left = if delay < 2 * pulse_length {
+            pulse_length
+        } else {
+            delay - pulse_length
+        };
+        self.driver.set_low().map_err(MotionError::Step)?;
+        self.wait(delay_left).await;
+
+        self.current_step += self.current_direction as i32;
+        Ok(())
+    }
+
+    /// check the guard before a step, refer MontionCtrl's
+    async fn check_guard<DirErr>(
+        &mut self,
+        direction: Direction,
+        velocity: f64,
+    ) -> Result<(), GuardedError<<DRIVER as StepTrait>::Error, Infallible, DirErr, Guard::SwitchError>>
+    where
+        Guard: StepGuardTrait<DRIVER, <DRIVER as StepTrait>::Error>,
+    {
+        let error = match self.guard.check(&mut self.driver, direction) {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        let action = self.guard.end_stop_action();
+        if let Some(mut ramp) = plan::stop_ramp(action, &error, direction, velocity) {
+            // the switch stays triggered, dont check it while decelerating
+            while let Some((_, delay)) = ramp.next_step() {
+                // but stop at the soft limit, as a move does
+                let next = self.current_step + direction as i32;
+                if self.soft_limits.is_some_and(|limits| !limits.contains(next)) {
+                    break;
+                }
+                self.pulse(rampdelay_to_nano(Num::from_num(delay))).await?;
+            }
+        }
+        Err(error)
+    }
+}
+
+impl<DRIVER, Delay, Profile, Guard> AsyncMotionControlStepModeTrait
+    for AsyncMontionCtrl<DRIVER, Delay, Profile, Guard>
+where
+    DRIVER: SetStepModeTrait + StepModeResetTrait,
+    DRIVER::StepMode: Copy,
+    Delay: DelayNs,
+{
+    type StepMode = DRIVER::StepMode;
+    type PinError = <DRIVER as SetStepModeTrait>::Error;
+    type ResetError = DRIVER::ModeResetError;
+    type TimerError = Infallible;
+
+    async fn set_step_mode(
+        &mut self,
+        step_mode: Self::StepMode,
+    ) -> Result<
+        (),
+        MotionError<
+            Self::PinError,
+            Self::TimerError,
+            Self::PinError,
+            Self::PinError,
+            Self::ResetError,
+        >,
+    > {
+        self.driver
+            .apply_mode_config(step_mode)
+            .map_err(MotionError::StepMode)?;
+        self.wait(DRIVER::SETUP_TIME + DRIVER::HOLD_TIME).await;
+
+        self.driver.reset_for_mode().map_err(MotionError::Reset)?;
+        self.wait(DRIVER::MODE_RESET_TIME).await;
+        Ok(())
+    }
+}
+
+impl<DRIVER, Delay, Profile, Guard> AsyncMotionControlTrait
+    for AsyncMontionCtrl<DRIVER, Delay, Profile, Guard>
+where
+    DRIVER: SetDirectionTrait + StepTrait,
+    Delay: DelayNs,
+    Profile: StepProfile,
+    Guard: StepGuardTrait<DRIVER, <DRIVER as StepTrait>::Error>,
+{
+    type PinError = <DRIVER as StepTrait>::Error;
+    type DirError = <DRIVER as SetDirectionTrait>::Error;
+    type TimerError = Infallible;
+    type SwitchError = Guard::SwitchError;
+
+    async fn set_direction(
+        &mut self,
+        direction: Direction,
+    ) -> Result<(), GuardedError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
+        match direction {
+            Direction::Forward => self.driver.set_forward(),
+            Direction::Backward => self.driver.set_backward(),
+        }
+        .map_err(MotionError::Direction)?;
+        self.wait(self.driver.setup_time()).await;
+        self.current_direction = direction;
+        Ok(())
+    }
+
+    /// a step in current direction, refer [MotionControlTrait::step](crate::MotionControlTrait::step)
+    async fn step(
+        &mut self,
+    ) -> Result<(), GuardedError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
+        let next = self.current_step + self.current_direction as i32;
+        if !plan::limit_step(self.soft_limits, next)? {
+            return Ok(());
+        }
+        self.check_guard(self.current_direction, 0.0).await?;
+        self.pulse(2 * self.driver.pulse_length()).await
+    }
+
+    async fn move_to_position(
+        &mut self,
+        target_accel: Num,
+        max_velocity: Num,
+        target_step: i32,
+    ) -> Result<i32, MoveError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
+        let target_step =
+            plan::limit_target(self.soft_limits, target_step).map_err(|error| MoveError {
+                completed: 0,
+                error,
+            })?;
+
+        let orig = self.current_step;
+        let steps_from_here = target_step - self.current_step;
+        let direction = match plan::start_move(
+            &mut self.profile,
+            target_accel,
+            max_velocity,
+            steps_from_here,
+        ) {
+            Some(direction) => direction,
+            None => return Ok(0), // dont need move
+        };
+        if let Err(error) = self.set_direction(direction).await {
+            return Err(MoveError {
+                completed: 0,
+                error,
+            });
+        }
+
+        // velocity of the last step, to decelerate from after an end stop
+        let mut velocity = 0.0;
+        while let Some(delay) = self.profile.next_delay() {
+            let mut result = self.check_guard(direction, velocity).await;
+            if result.is_ok() {
+                velocity = plan::step_velocity(delay, velocity);
+                result = self.pulse(rampdelay_to_nano(delay)).await;
+            }
+            if let Err(error) = result {
+                return Err(MoveError {
+                    completed: self.current_step - orig,
+                    error,
+                });
+            }
+        }
+
+        Ok(self.current_step - orig)
+    }
+
+    fn reset_position(
+        &mut self,
+        step: i32,
+    ) -> Result<(), GuardedError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
+        self.current_step = step;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EnableDirectionControlTrait, EnableStepControlTrait, SOFT};
+    use core::cell::{Cell, RefCell};
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use embedded_hal::digital::v2::OutputPin;
+
+    /// a STEP pin that logs the name of its axis on each pulse
+    struct StepPin<'a>(&'a RefCell<Vec<char>>, char);
+
+    impl OutputPin for StepPin<'_> {
+        type Error = Infallible;
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().push(self.1);
+            Ok(())
+        }
+    }
+
+    struct DirPin;
+
+    impl OutputPin for DirPin {
+        type Error = Infallible;
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    /// yields once per delay, so the other task runs in between
+    struct MockDelay<'a> {
+        elapsed: &'a Cell<u64>,
+        yielded: bool,
+    }
+
+    impl DelayNs for MockDelay<'_> {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.yielded = false;
+            core::future::poll_fn(|_| {
+                if core::mem::replace(&mut self.yielded, true) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+            self.elapsed.set(self.elapsed.get() + ns as u64);
+        }
+    }
+
+    type Ctrl<'a> = AsyncMontionCtrl<SOFT<StepPin<'a>, DirPin, 200, 1000>, MockDelay<'a>>;
+
+    fn ctrl<'a>(log: &'a RefCell<Vec<char>>, axis: char, elapsed: &'a Cell<u64>) -> Ctrl<'a> {
+        let driver = SOFT::new()
+            .enable_step_control(StepPin(log, axis))
+            .enable_direction_control(DirPin);
+        AsyncMontionCtrl::new(
+            driver,
+            MockDelay {
+                elapsed,
+                yielded: false,
+            },
+        )
+    }
+
+    /// the executor polls in a loop, the waker is never used
+    fn noop_waker() -> Waker {
+        const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
+        const RAW: RawWaker = RawWaker::new(core::ptr::null(), &VTABLE);
+        // SAFETY: the vtable functions do nothing with the data pointer
+        unsafe { Waker::from_raw(RAW) }
+    }
+
+    /// a simple executor, polls both futures in turn until both are ready
+    fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+        let (mut a, mut b) = (pin!(a), pin!(b));
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let (mut out_a, mut out_b) = (None, None);
+        while out_a.is_none() || out_b.is_none() {
+            if out_a.is_none() {
+                if let Poll::Ready(out) = a.as_mut().poll(&mut cx) {
+                    out_a = Some(out);
+                }
+            }
+            if out_b.is_none() {
+                if let Poll::Ready(out) = b.as_mut().poll(&mut cx) {
+                    out_b = Some(out);
+                }
+            }
+        }
+        (out_a.unwrap(), out_b.unwrap())
+    }
+
+    #[test]
+    fn axes_should_move_concurrently() {
+        let log = RefCell::new(Vec::new());
+        let (x_elapsed, y_elapsed) = (Cell::new(0), Cell::new(0));
+        let mut x = ctrl(&log, 'x', &x_elapsed);
+        let mut y = ctrl(&log, 'y', &y_elapsed);
+        let (accel, velocity) = (Num::from_num(1000), Num::from_num(500));
+
+        let (moved_x, moved_y) = join(
+            x.move_to_position(accel, velocity, 20),
+            y.move_to_position(accel, velocity, -10),
+        );
+        assert_eq!(moved_x.unwrap(), 20);
+        assert_eq!(moved_y.unwrap(), -10);
+        assert_eq!((x.current_step(), y.current_step()), (20, -10));
+        assert!(x_elapsed.get() > y_elapsed.get());
+
+        // the steps alternate while both axes move, then X goes on alone
+        let expected: Vec<char> = "xy".repeat(10).chars().chain(['x'; 10]).collect();
+        assert_eq!(*log.borrow(), expected);
+
+        let (step, _) = join(x.step(), async {});
+        step.unwrap();
+        assert_eq!(x.current_step(), 21);
+    }
+}
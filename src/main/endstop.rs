@@ -0,0 +1,310 @@
+//! EndStops
+//!
+//! hardware end stop(limit) switches of an axis. attached to MontionCtrl by
+//! [MontionCtrl::with_guard](super::MontionCtrl::with_guard), they are sampled
+//! before every step of `move_to_position`, `step` and velocity mode. only the
+//! switch in the direction of travel is checked, so the axis can always move
+//! away from a triggered switch.
+//!
+//! ```text
+//!   min switch                                  max switch
+//!     |<-- checked when Backward    checked when Forward -->|
+//! ```
+//!
+//! when a switch is hit, the motion stops according to [EndStopAction] and
+//! returns [MotionError::EndStop]. homing checks them too, but its switch is
+//! checked first, so the home switch can be one of them.
+
+use embedded_hal::digital::v2::{InputPin, PinState};
+
+use super::stepprofile::Num;
+use super::GuardedError;
+use crate::interfaces::StepGuardTrait;
+use crate::{Direction, MotionError};
+
+/// How to stop when an end stop is hit
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EndStopAction {
+    /// stop at once, the step toward the switch is not outputted
+    HardStop,
+    /// decelerate with the given accel(unit is steps per second^2), the motor
+    /// runs past the trigger point by the stopping distance
+    Decelerate(Num),
+}
+
+/// A switch input of [EndStops]
+///
+/// it is implemented for every `InputPin`, its error is reported as
+/// [MotionError::Switch]. use [NoSwitch] if the axis has only one end stop.
+pub trait SwitchTrait {
+    type Error;
+
+    fn is_triggered(&mut self, active_level: PinState) -> Result<bool, Self::Error>;
+}
+
+impl<P> SwitchTrait for P
+where
+    P: InputPin,
+{
+    type Error = P::Error;
+
+    fn is_triggered(&mut self, active_level: PinState) -> Result<bool, Self::Error> {
+        let high = self.is_high()?;
+        Ok(high == (active_level == PinState::High))
+    }
+}
+
+/// placeholder of a missing end stop, it is never triggered. the error of
+/// [EndStops] is the one of the other switch.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoSwitch;
+
+/// min/max end stop switches, refer the module doc
+pub struct EndStops<Min, Max> {
+    min: Min,
+    max: Max,
+    active_level: PinState,
+    action: EndStopAction,
+}
+
+impl<Min, Max> EndStops<Min, Max> {
+    /// end stops at both ends, active high, hard stop
+    pub fn new(min: Min, max: Max) -> Self {
+        Self {
+            min,
+            max,
+            active_level: PinState::High,
+            action: EndStopAction::HardStop,
+        }
+    }
+
+    pub fn active_level(mut self, active_level: PinState) -> Self {
+        self.active_level = active_level;
+        self
+    }
+
+    pub fn action(mut self, action: EndStopAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn release(self) -> (Min, Max) {
+        (self.min, self.max)
+    }
+}
+
+impl<Min> EndStops<Min, NoSwitch> {
+    /// only the end stop at the backward end
+    pub fn min_only(min: Min) -> Self {
+        Self::new(min, NoSwitch)
+    }
+}
+
+impl<Max> EndStops<NoSwitch, Max> {
+    /// only the end stop at the forward end
+    pub fn max_only(max: Max) -> Self {
+        Self::new(NoSwitch, max)
+    }
+}
+
+impl<Min, Max> EndStops<Min, Max> {
+    /// the motion stops if `triggered`, that is the switch in `direction`
+    fn hit<E, PinErr, TimerErr, DirErr>(
+        &self,
+        direction: Direction,
+        triggered: Result<bool, E>,
+    ) -> Result<(), GuardedError<PinErr, TimerErr, DirErr, E>> {
+        if triggered.map_err(MotionError::Switch)? {
+            return Err(MotionError::EndStop(direction));
+        }
+        Ok(())
+    }
+}
+
+impl<DRIVER, Min, Max, PinErr> StepGuardTrait<DRIVER, PinErr> for EndStops<Min, Max>
+where
+    Min: SwitchTrait,
+    Max: SwitchTrait<Error = Min::Error>,
+{
+    type SwitchError = Min::Error;
+
+    fn check<TimerErr, DirErr>(
+        &mut self,
+        _driver: &mut DRIVER,
+        direction: Direction,
+    ) -> Result<(), GuardedError<PinErr, TimerErr, DirErr, Min::Error>> {
+        let triggered = match direction {
+            Direction::Forward => self.max.is_triggered(self.active_level),
+            Direction::Backward => self.min.is_triggered(self.active_level),
+        };
+        self.hit(direction, triggered)
+    }
+
+    fn end_stop_action(&self) -> EndStopAction {
+        self.action
+    }
+}
+
+impl<DRIVER, Min, PinErr> StepGuardTrait<DRIVER, PinErr> for EndStops<Min, NoSwitch>
+where
+    Min: SwitchTrait,
+{
+    type SwitchError = Min::Error;
+
+    fn check<TimerErr, DirErr>(
+        &mut self,
+        _driver: &mut DRIVER,
+        direction: Direction,
+    ) -> Result<(), GuardedError<PinErr, TimerErr, DirErr, Min::Error>> {
+        let triggered = match direction {
+            Direction::Forward => Ok(false),
+            Direction::Backward => self.min.is_triggered(self.active_level),
+        };
+        self.hit(direction, triggered)
+    }
+
+    fn end_stop_action(&self) -> EndStopAction {
+        self.action
+    }
+}
+
+impl<DRIVER, Max, PinErr> StepGuardTrait<DRIVER, PinErr> for EndStops<NoSwitch, Max>
+where
+    Max: SwitchTrait,
+{
+    type SwitchError = Max::Error;
+
+    fn check<TimerErr, DirErr>(
+        &mut self,
+        _driver: &mut DRIVER,
+        direction: Direction,
+    ) -> Result<(), GuardedError<PinErr, TimerErr, DirErr, Max::Error>> {
+        let triggered = match direction {
+            Direction::Forward => self.max.is_triggered(self.active_level),
+            Direction::Backward => Ok(false),
+        };
+        self.hit(direction, triggered)
+    }
+
+    fn end_stop_action(&self) -> EndStopAction {
+        self.action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim, SimA4988, SimConvert, SimInput};
+    use crate::{LimitMode, MontionCtrl, MotionControlTrait, MoveError, SignedNum, SoftLimits};
+
+    type Ctrl =
+        MontionCtrl<SimA4988, SimConvert, ramp_maker::Trapezoidal, EndStops<SimInput, SimInput>>;
+
+    /// min switch at -20, max switch at 30
+    fn ctrl(sim: &Sim, action: EndStopAction) -> Ctrl {
+        let end_stops = EndStops::new(
+            sim.input(|sim| sim.position() <= -20),
+            sim.input(|sim| sim.position() >= 30),
+        );
+        MontionCtrl::new(sim.a4988(), sim.convert()).with_guard(end_stops.action(action))
+    }
+
+    fn accel() -> Num {
+        Num::from_num(10_000)
+    }
+
+    #[test]
+    fn hard_stop_should_stop_at_the_switch() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim, EndStopAction::HardStop);
+
+        let result = ctrl.move_to_position(accel(), Num::from_num(2000), 100);
+        let error = MoveError {
+            completed: 30,
+            error: MotionError::EndStop(Direction::Forward),
+        };
+        assert_eq!(result, Err(error));
+        assert_eq!((ctrl.current_step(), sim.position()), (30, 30));
+        assert_eq!(ctrl.step(), Err(MotionError::EndStop(Direction::Forward)));
+
+        // away from the triggered switch, to the other one
+        let result = ctrl.move_to_position(accel(), Num::from_num(2000), -100);
+        let error = MoveError {
+            completed: -50,
+            error: MotionError::EndStop(Direction::Backward),
+        };
+        assert_eq!(result, Err(error));
+        assert_eq!((ctrl.current_step(), sim.position()), (-20, -20));
+    }
+
+    #[test]
+    fn decelerate_should_run_past_the_switch() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim, EndStopAction::Decelerate(accel()));
+
+        let result = ctrl.move_to_position(accel(), Num::from_num(2000), 200);
+        let error = result.unwrap_err();
+        assert_eq!(error.error, MotionError::EndStop(Direction::Forward));
+        // the switch is hit during the accel at about 775 steps/s, whose
+        // stopping distance is about 30 steps
+        assert!((55..=60).contains(&error.completed), "{}", error.completed);
+        assert_eq!(ctrl.current_step(), error.completed);
+        assert_eq!(sim.position(), error.completed);
+
+        let pulses = sim.pulses(Signal::Step);
+        let periods: Vec<_> = pulses.windows(2).map(|w| w[1].0 - w[0].0).collect();
+        assert!(periods[30..].windows(2).all(|w| w[1] > w[0]));
+    }
+
+    #[test]
+    fn decelerate_should_stop_at_the_soft_limit() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim, EndStopAction::Decelerate(accel()));
+        ctrl.set_soft_limits(SoftLimits::new(-100, 40, LimitMode::Reject));
+
+        // jogging hits the switch at about 775 steps/s, its ~30 steps of
+        // deceleration would pass the limit
+        ctrl.enter_velocity_mode(accel(), SignedNum::from_num(1000));
+        let result = loop {
+            match ctrl.velocity_step() {
+                Ok(true) => {}
+                result => break result,
+            }
+        };
+        assert_eq!(result, Err(MotionError::EndStop(Direction::Forward)));
+        assert_eq!((ctrl.current_step(), sim.position()), (40, 40));
+    }
+
+    #[test]
+    fn switch_error_should_keep_its_type() {
+        #[derive(Debug, PartialEq)]
+        struct ReadError;
+        struct BrokenSwitch;
+
+        impl InputPin for BrokenSwitch {
+            type Error = ReadError;
+            fn is_high(&self) -> Result<bool, ReadError> {
+                Err(ReadError)
+            }
+            fn is_low(&self) -> Result<bool, ReadError> {
+                Err(ReadError)
+            }
+        }
+
+        let sim = Sim::new();
+        let end_stops = EndStops::max_only(BrokenSwitch);
+        let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert()).with_guard(end_stops);
+
+        // only the switch in the direction of travel is read
+        assert_eq!(
+            ctrl.move_to_position(accel(), Num::from_num(2000), -10),
+            Ok(-10)
+        );
+        let result = ctrl.move_to_position(accel(), Num::from_num(2000), 0);
+        let error = MoveError {
+            completed: 0,
+            error: MotionError::Switch(ReadError),
+        };
+        assert_eq!(result, Err(error));
+    }
+}
@@ -0,0 +1,320 @@
+//! MotionEngine
+//!
+//! non-blocking counterpart of [MontionCtrl](super::MontionCtrl). the engine
+//! never waits by itself, it is a state machine that is started with a move
+//! request and then advanced by the app's timer interrupt. each call of
+//! [MotionEngine::tick] toggles the driver's pins and returns the delay that
+//! the app should program into its timer before calling `tick` again.
+//!
+//! ```text
+//!   start_move ──► DirectionSetup ──tick──► StepHigh ──tick──► StepLow ──┐
+//!                                             ▲                          │
+//!                                             └──────────tick────────────┘
+//!                                    (tick on StepLow returns None when done)
+//! ```
+//!
+//! usage sample:
+//!
+//! ```rust,ignore
+//!   // in app
+//!   if let Some(delay) = engine.start_move(target_accel, max_velocity, 1000)? {
+//!       timer.start(delay);
+//!   }
+//!
+//!   // in timer ISR
+//!   match engine.tick() {
+//!       Ok(Some(delay)) => timer.start(delay),
+//!       _ => timer.cancel(),
+//!   }
+//! ```
+
+use core::convert::Infallible;
+
+use ramp_maker::Trapezoidal;
+
+use super::stepprofile::StepProfile;
+use crate::interfaces::{rampdelay_to_nano, Num, SetDirectionTrait, StepTrait};
+use crate::{Direction, MotionError};
+
+/// the engine owns no timer, so its errors never carry a timer error
+pub type EngineError<PinErr, DirErr = PinErr> = MotionError<PinErr, Infallible, DirErr>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    /// no motion in progress
+    Idle,
+    /// DIR signal has been set, wait its setup time before first pulse
+    DirectionSetup,
+    /// STEP signal is high, the field is the low time after pulse finished
+    StepHigh(fugit::NanosDurationU64),
+    /// STEP signal is low, next tick starts next pulse or finishes the move
+    StepLow,
+}
+
+/// interrupt-driven motion engine
+///
+/// it reuses the driver traits([StepTrait], [SetDirectionTrait]) and the
+/// motion profiles([StepProfile], default is trapezoidal), so every driver that
+/// can be used by [MontionCtrl](super::MontionCtrl) can be used by it too.
+pub struct MotionEngine<DRIVER, Profile = Trapezoidal> {
+    driver: DRIVER,
+    profile: Profile,
+    state: State,
+    current_step: i32,
+    current_direction: Direction,
+}
+
+impl<DRIVER> MotionEngine<DRIVER> {
+    pub fn new(driver: DRIVER) -> Self {
+        // the accel will be replaced when start a move
+        Self::with_profile(driver, Trapezoidal::new(Num::from_num(1)))
+    }
+}
+
+impl<DRIVER, Profile> MotionEngine<DRIVER, Profile> {
+    /// create with the given motion profile
+    pub fn with_profile(driver: DRIVER, profile: Profile) -> Self {
+        Self {
+            driver,
+            profile,
+            state: State::Idle,
+            current_step: 0,
+            current_direction: Direction::Forward,
+        }
+    }
+
+    pub fn release(self) -> DRIVER {
+        self.driver
+    }
+
+    /// whether a move is in progress
+    pub fn is_busy(&self) -> bool {
+        self.state != State::Idle
+    }
+
+    /// current position, unit is step. refer [crate::MotionControlTrait::move_to_position]
+    pub fn current_step(&self) -> i32 {
+        self.current_step
+    }
+
+    /// Reset internal position to the given value
+    ///
+    /// it is ignored when a move is in progress.
+    pub fn reset_position(&mut self, step: i32) {
+        if !self.is_busy() {
+            self.current_step = step;
+        }
+    }
+
+    /// abort the move in progress immediately, without deceleration
+    ///
+    /// the STEP signal is left low, the app should stop its timer.
+    pub fn abort(&mut self) -> Result<(), EngineError<<DRIVER as StepTrait>::Error>>
+    where
+        DRIVER: StepTrait,
+    {
+        let state = self.state;
+        self.state = State::Idle;
+        if let State::StepHigh(_) = state {
+            self.driver.set_low().map_err(MotionError::Step)?;
+            // the pulse has been outputted
+            self.current_step += self.current_direction as i32;
+        }
+        Ok(())
+    }
+}
+
+impl<DRIVER, Profile> MotionEngine<DRIVER, Profile>
+where
+    DRIVER: SetDirectionTrait + StepTrait,
+    Profile: StepProfile,
+{
+    /// start a move to the given position. accel unit is steps per second^2,
+    /// velocity unit is steps per second.
+    ///
+    /// return the delay before first [MotionEngine::tick] should be called,
+    /// `None` means dont need move. return [MotionError::Busy] if a move is
+    /// in progress.
+    pub fn start_move(
+        &mut self,
+        target_accel: Num,
+        max_velocity: Num,
+        target_step: i32,
+    ) -> Result<
+        Option<fugit::NanosDurationU64>,
+        EngineError<<DRIVER as StepTrait>::Error, <DRIVER as SetDirectionTrait>::Error>,
+    > {
+        if self.is_busy() {
+            return Err(MotionError::Busy);
+        }
+
+        let steps_from_here = target_step - self.current_step;
+        let direction = if steps_from_here > 0 {
+            Direction::Forward
+        } else if steps_from_here < 0 {
+            Direction::Backward
+        } else {
+            return Ok(None); // dont need move
+        };
+
+        match direction {
+            Direction::Forward => self.driver.set_forward().map_err(MotionError::Direction)?,
+            Direction::Backward => self.driver.set_backward().map_err(MotionError::Direction)?,
+        }
+        self.current_direction = direction;
+
+        self.profile.set_target_accel(target_accel);
+        self.profile
+            .enter_position_mode(max_velocity, steps_from_here.unsigned_abs());
+        self.state = State::DirectionSetup;
+
+        Ok(Some(self.driver.setup_time()))
+    }
+
+    /// advance the state machine, should be called from timer interrupt
+    ///
+    /// return the delay to program before next call, `None` means the move
+    /// has finished. if a pin error occurs the move is aborted.
+    pub fn tick(
+        &mut self,
+    ) -> Result<
+        Option<fugit::NanosDurationU64>,
+        EngineError<<DRIVER as StepTrait>::Error, <DRIVER as SetDirectionTrait>::Error>,
+    > {
+        match self.state {
+            State::Idle => Ok(None),
+            State::DirectionSetup | State::StepLow => {
+                let delay = match self.profile.next_delay() {
+                    Some(delay) => rampdelay_to_nano(delay),
+                    None => {
+                        self.state = State::Idle;
+                        return Ok(None);
+                    }
+                };
+
+                if let Err(e) = self.driver.set_high() {
+                    self.state = State::Idle;
+                    return Err(MotionError::Step(e));
+                }
+
+                let pulse_length = self.driver.pulse_length();
+                let delay_left = if delay < 2 * pulse_length {
+                    pulse_length
+                } else {
+                    delay - pulse_length
+                };
+                self.state = State::StepHigh(delay_left);
+                Ok(Some(pulse_length))
+            }
+            State::StepHigh(delay_left) => {
+                if let Err(e) = self.driver.set_low() {
+                    self.state = State::Idle;
+                    return Err(MotionError::Step(e));
+                }
+                self.current_step += self.current_direction as i32;
+                self.state = State::StepLow;
+                Ok(Some(delay_left))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim};
+    use embedded_hal::digital::v2::PinState;
+
+    fn accel() -> Num {
+        Num::from_num(5000)
+    }
+
+    fn velocity() -> Num {
+        Num::from_num(1000)
+    }
+
+    #[test]
+    fn tick_should_walk_through_the_states() {
+        let sim = Sim::new();
+        let mut engine = MotionEngine::new(sim.a4988());
+
+        let setup = engine.start_move(accel(), velocity(), -2).unwrap();
+        assert_eq!(setup, Some(engine.driver.setup_time()));
+        assert_eq!(engine.state, State::DirectionSetup);
+        assert_eq!(sim.transitions_of(Signal::Dir)[0].level, PinState::High);
+        assert!(sim.transitions_of(Signal::Step).is_empty());
+
+        for step in 1..=2 {
+            let high = engine.tick().unwrap();
+            assert_eq!(high, Some(engine.driver.pulse_length()));
+            assert!(matches!(engine.state, State::StepHigh(_)));
+            assert_eq!(
+                sim.transitions_of(Signal::Step).last().unwrap().level,
+                PinState::High
+            );
+            // the step is counted when the pulse finished
+            assert_eq!(engine.current_step(), 1 - step);
+
+            let low = engine.tick().unwrap().unwrap();
+            assert!(low >= engine.driver.pulse_length());
+            assert_eq!(engine.state, State::StepLow);
+            assert_eq!(
+                sim.transitions_of(Signal::Step).last().unwrap().level,
+                PinState::Low
+            );
+            assert_eq!(engine.current_step(), -step);
+        }
+
+        assert_eq!(engine.tick().unwrap(), None);
+        assert!(!engine.is_busy());
+        assert_eq!(sim.pulses(Signal::Step).len(), 2);
+        assert_eq!(engine.tick().unwrap(), None);
+    }
+
+    #[test]
+    fn start_move_should_be_rejected_while_busy() {
+        let sim = Sim::new();
+        let mut engine = MotionEngine::new(sim.a4988());
+
+        assert_eq!(engine.start_move(accel(), velocity(), 0).unwrap(), None);
+        assert!(!engine.is_busy());
+
+        engine.start_move(accel(), velocity(), 10).unwrap();
+        assert_eq!(
+            engine.start_move(accel(), velocity(), 20),
+            Err(MotionError::Busy)
+        );
+        engine.reset_position(100);
+        assert_eq!(engine.current_step(), 0);
+    }
+
+    #[test]
+    fn abort_should_finish_the_pulse_and_stop() {
+        let sim = Sim::new();
+        let mut engine = MotionEngine::new(sim.a4988());
+
+        engine.start_move(accel(), velocity(), 10).unwrap();
+        engine.tick().unwrap();
+        engine.tick().unwrap();
+        engine.tick().unwrap();
+        // abort in the middle of the second pulse
+        engine.abort().unwrap();
+        assert!(!engine.is_busy());
+        assert_eq!(engine.current_step(), 2);
+        assert_eq!(sim.pulses(Signal::Step).len(), 2);
+        assert_eq!(engine.tick().unwrap(), None);
+
+        // abort between pulses, no pulse is counted
+        engine.start_move(accel(), velocity(), 10).unwrap();
+        engine.tick().unwrap();
+        engine.tick().unwrap();
+        engine.abort().unwrap();
+        assert_eq!(engine.current_step(), 3);
+        assert_eq!(sim.pulses(Signal::Step).len(), 3);
+
+        // a new move starts from the aborted position
+        engine.start_move(accel(), velocity(), 4).unwrap();
+        while engine.tick().unwrap().is_some() {}
+        assert_eq!(engine.current_step(), 4);
+    }
+}
@@ -0,0 +1,589 @@
+//! Homing
+//!
+//! the classic two-pass homing with a limit switch: seek the switch fast, back
+//! off, re-approach it slowly for an accurate trigger point, then reset the
+//! position to the home offset.
+//!
+//! ```text
+//!   switch                                  start
+//!     |<================ 1. seek fast ===========+
+//!     +====== 2. back off ======>
+//!     |<===== 3. approach slow ==+
+//!     home(reset_position(home_offset))
+//! ```
+//!
+//! the motor runs at constant velocity without ramp during homing, so the
+//! velocities should be low enough to start/stop at once. soft limits are not
+//! applied during homing, the guard(e.g. [FaultGuard](crate::FaultGuard)) is
+//! checked before every step. the switch is checked before the guard, so it
+//! can be one of the [EndStops](crate::EndStops).
+//!
+//! the config is validated before the motor moves, a velocity that is zero or
+//! too low for the step delay returns [MotionError::InvalidConfig].
+//!
+//! the sensorless homing drives toward the mechanical end until the driver
+//! detects the stall(e.g. StallGuard of Trinamic), the stall point is home,
+//! then it backs off. the stall source is a [StallDetectTrait], it is the
+//! DIAG pin([DiagPin]), or the driver polled between steps([DriverStall]).
+//! refer [MontionCtrl::home_sensorless].
+
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::{InputPin, PinState};
+
+use super::stepprofile::{Num, StepProfile};
+use super::MontionCtrl;
+use crate::interfaces::{
+    DelayToTicksTrait, MotionControlTrait, SetDirectionTrait, StallDetectTrait, StallTrait,
+    StepGuardTrait, StepTrait,
+};
+use crate::{Direction, MotionError};
+
+/// the home input(the switch or the stall source) reports its own error, the
+/// switches of the guard theirs
+type HomingError<DRIVER, Convert, Guard, StallErr = Infallible> = MotionError<
+    <DRIVER as StepTrait>::Error,
+    <Convert as DelayToTicksTrait>::Error,
+    <DRIVER as SetDirectionTrait>::Error,
+    <DRIVER as StepTrait>::Error,
+    <DRIVER as StepTrait>::Error,
+    StallErr,
+    <Guard as StepGuardTrait<DRIVER, <DRIVER as StepTrait>::Error>>::SwitchError,
+>;
+
+/// Configuration of [MontionCtrl::home]
+#[derive(Clone, Copy, Debug)]
+pub struct HomingConfig {
+    /// direction toward the switch
+    pub direction: Direction,
+    /// velocity of the first approach, unit is steps per second
+    pub seek_velocity: Num,
+    /// velocity of the back off and the second approach, unit is steps per second
+    pub approach_velocity: Num,
+    /// steps to back off from the switch before the second approach
+    pub backoff_steps: u32,
+    /// max steps of each approach, the switch is regarded as not found beyond it
+    pub max_travel: u32,
+    /// the position assigned to the trigger point
+    pub home_offset: i32,
+    /// the level of the switch input when triggered
+    pub active_level: PinState,
+}
+
+impl HomingConfig {
+    /// config with home offset 0 and active high switch
+    pub fn new(
+        direction: Direction,
+        seek_velocity: Num,
+        approach_velocity: Num,
+        backoff_steps: u32,
+        max_travel: u32,
+    ) -> Self {
+        Self {
+            direction,
+            seek_velocity,
+            approach_velocity,
+            backoff_steps,
+            max_travel,
+            home_offset: 0,
+            active_level: PinState::High,
+        }
+    }
+
+    pub fn home_offset(mut self, home_offset: i32) -> Self {
+        self.home_offset = home_offset;
+        self
+    }
+
+    pub fn active_level(mut self, active_level: PinState) -> Self {
+        self.active_level = active_level;
+        self
+    }
+}
+
+/// Configuration of [MontionCtrl::home_sensorless]
+#[derive(Clone, Copy, Debug)]
+pub struct SensorlessHomingConfig {
+    /// direction toward the mechanical end
+    pub direction: Direction,
+    /// velocity toward the end and of the back off, unit is steps per second
+    pub velocity: Num,
+    /// steps to back off from the end after the stall
+    pub backoff_steps: u32,
+    /// max steps toward the end, the stall is regarded as not found beyond it
+    pub max_travel: u32,
+    /// steps at the start that are not checked, the stall detection is not
+    /// valid until the motor runs at the velocity
+    pub ignore_steps: u32,
+    /// the position assigned to the stall point
+    pub home_offset: i32,
+}
+
+impl SensorlessHomingConfig {
+    /// config with home offset 0 and no ignored steps
+    pub fn new(direction: Direction, velocity: Num, backoff_steps: u32, max_travel: u32) -> Self {
+        Self {
+            direction,
+            velocity,
+            backoff_steps,
+            max_travel,
+            ignore_steps: 0,
+            home_offset: 0,
+        }
+    }
+
+    pub fn ignore_steps(mut self, ignore_steps: u32) -> Self {
+        self.ignore_steps = ignore_steps;
+        self
+    }
+
+    pub fn home_offset(mut self, home_offset: i32) -> Self {
+        self.home_offset = home_offset;
+        self
+    }
+}
+
+/// Stall source of the DIAG pin, refer [StallDetectTrait]
+///
+/// DIAG of TMC2209 is active high, DIAG0/DIAG1 of TMC5160/TMC2130 are active
+/// low(open drain) by default.
+pub struct DiagPin<Pin> {
+    pin: Pin,
+    active_level: PinState,
+}
+
+impl<Pin> DiagPin<Pin> {
+    /// an active high DIAG pin
+    pub fn new(pin: Pin) -> Self {
+        Self {
+            pin,
+            active_level: PinState::High,
+        }
+    }
+
+    pub fn active_level(mut self, active_level: PinState) -> Self {
+        self.active_level = active_level;
+        self
+    }
+
+    pub fn release(self) -> Pin {
+        self.pin
+    }
+}
+
+impl<DRIVER, Pin, PinErr> StallDetectTrait<DRIVER, PinErr> for DiagPin<Pin>
+where
+    Pin: InputPin,
+{
+    type StallError = Pin::Error;
+
+    fn is_stalled<TimerErr, DirErr>(
+        &mut self,
+        _driver: &mut DRIVER,
+    ) -> Result<bool, MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Self::StallError>> {
+        let high = self.pin.is_high().map_err(MotionError::StallDetect)?;
+        Ok(high == (self.active_level == PinState::High))
+    }
+}
+
+/// Stall source of the driver itself, it is polled before every step, refer
+/// [StallTrait]
+///
+/// the read error of the driver is reported as [MotionError::StallDetect],
+/// e.g. the UART error of TMC2209.
+/// a register read takes time(e.g. about 1ms of UART at 115200), it slows the
+/// homing down.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DriverStall;
+
+impl<DRIVER, PinErr> StallDetectTrait<DRIVER, PinErr> for DriverStall
+where
+    DRIVER: StallTrait,
+{
+    type StallError = DRIVER::Error;
+
+    fn is_stalled<TimerErr, DirErr>(
+        &mut self,
+        driver: &mut DRIVER,
+    ) -> Result<bool, MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Self::StallError>> {
+        driver.is_stalled().map_err(MotionError::StallDetect)
+    }
+}
+
+impl<DRIVER, Convert, Profile, Guard> MontionCtrl<DRIVER, Convert, Profile, Guard>
+where
+    DRIVER: SetDirectionTrait + StepTrait,
+    Convert: DelayToTicksTrait,
+    Profile: StepProfile,
+    Guard: StepGuardTrait<DRIVER, <DRIVER as StepTrait>::Error>,
+{
+    /// Home the axis with a limit switch, refer [HomingConfig]
+    ///
+    /// return [MotionError::SwitchNotFound] if the switch is not triggered
+    /// within max travel, the position is not reset in this case. the read
+    /// error of the switch is [MotionError::HomeSwitch].
+    pub fn home<Switch>(
+        &mut self,
+        switch: &mut Switch,
+        config: &HomingConfig,
+    ) -> Result<(), HomingError<DRIVER, Convert, Guard, Switch::Error>>
+    where
+        Switch: InputPin,
+    {
+        if self.jog.is_running() {
+            return Err(MotionError::Busy);
+        }
+        let seek_delay = self
+            .velocity_to_nano(config.seek_velocity)
+            .map_err(MotionError::with_stall_error)?;
+        let approach_delay = self
+            .velocity_to_nano(config.approach_velocity)
+            .map_err(MotionError::with_stall_error)?;
+
+        let triggered = |switch: &mut Switch| -> Result<bool, _> {
+            let high = switch.is_high().map_err(MotionError::HomeSwitch)?;
+            Ok(high == (config.active_level == PinState::High))
+        };
+        let backward = match config.direction {
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+        };
+
+        // 1. seek fast
+        self.set_direction(config.direction)
+            .map_err(MotionError::with_stall_error)?;
+        self.step_until(seek_delay, config.max_travel, || triggered(switch))?;
+
+        // 2. back off
+        self.set_direction(backward)
+            .map_err(MotionError::with_stall_error)?;
+        for _ in 0..config.backoff_steps {
+            self.guarded_pulse(approach_delay)
+                .map_err(MotionError::with_stall_error)?;
+        }
+
+        // 3. approach slow
+        self.set_direction(config.direction)
+            .map_err(MotionError::with_stall_error)?;
+        self.step_until(approach_delay, config.max_travel, || triggered(switch))?;
+
+        self.reset_position(config.home_offset)
+            .map_err(MotionError::with_stall_error)
+    }
+
+    /// Home the axis without switch, by the stall detection of the driver,
+    /// refer [SensorlessHomingConfig]
+    ///
+    /// the position of the stall point is reset to the home offset, then the
+    /// axis backs off. return [MotionError::SwitchNotFound] if no stall is
+    /// detected within max travel, the position is not reset in this case.
+    pub fn home_sensorless<Stall>(
+        &mut self,
+        stall: &mut Stall,
+        config: &SensorlessHomingConfig,
+    ) -> Result<(), HomingError<DRIVER, Convert, Guard, Stall::StallError>>
+    where
+        Stall: StallDetectTrait<DRIVER, <DRIVER as StepTrait>::Error>,
+    {
+        if self.jog.is_running() {
+            return Err(MotionError::Busy);
+        }
+
+        let delay = self
+            .velocity_to_nano(config.velocity)
+            .map_err(MotionError::with_stall_error)?;
+        self.set_direction(config.direction)
+            .map_err(MotionError::with_stall_error)?;
+        let mut steps = 0;
+        loop {
+            let stalled = steps >= config.ignore_steps
+                && stall
+                    .is_stalled(&mut self.driver)
+                    .map_err(MotionError::with_switch_error)?;
+            if stalled {
+                break;
+            }
+            if steps >= config.max_travel {
+                return Err(MotionError::SwitchNotFound);
+            }
+            self.guarded_pulse(delay)
+                .map_err(MotionError::with_stall_error)?;
+            steps += 1;
+        }
+        self.reset_position(config.home_offset)
+            .map_err(MotionError::with_stall_error)?;
+
+        let backward = match config.direction {
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+        };
+        self.set_direction(backward)
+            .map_err(MotionError::with_stall_error)?;
+        for _ in 0..config.backoff_steps {
+            self.guarded_pulse(delay)
+                .map_err(MotionError::with_stall_error)?;
+        }
+        Ok(())
+    }
+
+    /// step at constant velocity until `done` returns true, at most `max_steps`
+    fn step_until<StallErr>(
+        &mut self,
+        delay: fugit::NanosDurationU64,
+        max_steps: u32,
+        mut done: impl FnMut() -> Result<bool, HomingError<DRIVER, Convert, Guard, StallErr>>,
+    ) -> Result<(), HomingError<DRIVER, Convert, Guard, StallErr>> {
+        for _ in 0..max_steps {
+            if done()? {
+                return Ok(());
+            }
+            self.guarded_pulse(delay)
+                .map_err(MotionError::with_stall_error)?;
+        }
+        if done()? {
+            return Ok(());
+        }
+        Err(MotionError::SwitchNotFound)
+    }
+
+    /// a step at the constant velocity, after the guard
+    fn guarded_pulse(
+        &mut self,
+        delay: fugit::NanosDurationU64,
+    ) -> Result<(), HomingError<DRIVER, Convert, Guard>> {
+        let velocity = 1_000_000_000.0 / delay.ticks() as f64;
+        self.check_guard(self.current_direction, velocity)?;
+        self.pulse(delay)
+    }
+
+    /// the step delay of the velocity, a zero velocity or one whose delay
+    /// overflows is [MotionError::InvalidConfig]
+    fn velocity_to_nano(
+        &self,
+        velocity: Num,
+    ) -> Result<fugit::NanosDurationU64, HomingError<DRIVER, Convert, Guard>> {
+        let delay = Num::ONE
+            .checked_div(velocity)
+            .ok_or(MotionError::InvalidConfig)?;
+        Ok(self.convert.rampdelay_to_nano(delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim, SimA4988};
+    use crate::{EndStops, MontionCtrl};
+    use core::convert::Infallible;
+
+    /// A4988 that reports a stall after `stall_after` steps, or fails to read
+    struct ScriptedStall {
+        driver: SimA4988,
+        steps: u32,
+        stall_after: u32,
+        fail: bool,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct ReadError;
+
+    impl StallTrait for ScriptedStall {
+        type Error = ReadError;
+        fn is_stalled(&mut self) -> Result<bool, ReadError> {
+            match self.fail {
+                true => Err(ReadError),
+                false => Ok(self.steps >= self.stall_after),
+            }
+        }
+    }
+
+    impl StepTrait for ScriptedStall {
+        const PULSE_LENGTH: fugit::NanosDurationU64 = SimA4988::PULSE_LENGTH;
+        type Step = <SimA4988 as StepTrait>::Step;
+        type Error = Infallible;
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.steps += 1;
+            self.driver.set_high()
+        }
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.driver.set_low()
+        }
+        fn setp_pin(&mut self) -> &mut Self::Step {
+            self.driver.setp_pin()
+        }
+    }
+
+    impl SetDirectionTrait for ScriptedStall {
+        const SETUP_TIME: fugit::NanosDurationU64 = <SimA4988 as SetDirectionTrait>::SETUP_TIME;
+        type Dir = <SimA4988 as SetDirectionTrait>::Dir;
+        type Error = Infallible;
+        fn set_forward(&mut self) -> Result<(), Infallible> {
+            self.driver.set_forward()
+        }
+        fn set_backward(&mut self) -> Result<(), Infallible> {
+            self.driver.set_backward()
+        }
+        fn dir_pin(&mut self) -> &mut Self::Dir {
+            self.driver.dir_pin()
+        }
+    }
+
+    fn scripted(sim: &Sim, stall_after: u32, fail: bool) -> ScriptedStall {
+        ScriptedStall {
+            driver: sim.a4988(),
+            steps: 0,
+            stall_after,
+            fail,
+        }
+    }
+
+    #[test]
+    fn home_sensorless_should_stop_at_the_stall() {
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(scripted(&sim, 30, false), sim.convert());
+        let config = SensorlessHomingConfig::new(Direction::Backward, Num::from_num(1000), 5, 100)
+            .home_offset(-2);
+        ctrl.home_sensorless(&mut DriverStall, &config).unwrap();
+
+        // 30 steps to the stall, then 5 back
+        assert_eq!(sim.pulses(Signal::Step).len(), 35);
+        assert_eq!(ctrl.current_step(), 3);
+
+        // no stall within max travel
+        let mut ctrl = MontionCtrl::new(scripted(&sim, 300, false), sim.convert());
+        let result = ctrl.home_sensorless(&mut DriverStall, &config);
+        assert_eq!(result, Err(MotionError::SwitchNotFound));
+    }
+
+    #[test]
+    fn home_sensorless_should_report_the_read_error() {
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(scripted(&sim, 30, true), sim.convert());
+        let config = SensorlessHomingConfig::new(Direction::Forward, Num::from_num(1000), 5, 100);
+        let result = ctrl.home_sensorless(&mut DriverStall, &config);
+        assert_eq!(result, Err(MotionError::StallDetect(ReadError)));
+        assert!(sim.pulses(Signal::Step).is_empty());
+    }
+
+    /// the driver is always in fault
+    struct Fault;
+
+    impl<D, E> StepGuardTrait<D, E> for Fault {
+        type SwitchError = Infallible;
+
+        fn check<T, Dir>(&mut self, _: &mut D, _: Direction) -> Result<(), MotionError<E, T, Dir>> {
+            Err(MotionError::DriverFault)
+        }
+    }
+
+    /// a switch that fails to read
+    struct BrokenSwitch;
+
+    impl InputPin for BrokenSwitch {
+        type Error = ReadError;
+        fn is_high(&self) -> Result<bool, ReadError> {
+            Err(ReadError)
+        }
+        fn is_low(&self) -> Result<bool, ReadError> {
+            Err(ReadError)
+        }
+    }
+
+    fn homing(seek: u32, approach: u32) -> HomingConfig {
+        HomingConfig::new(
+            Direction::Backward,
+            Num::from_num(seek),
+            Num::from_num(approach),
+            10,
+            100,
+        )
+        .home_offset(5)
+    }
+
+    #[test]
+    fn home_should_back_off_and_approach_the_switch() {
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert());
+        let mut switch = sim.input(|sim| sim.position() <= -40);
+        ctrl.home(&mut switch, &homing(2000, 500)).unwrap();
+
+        // 40 steps to the switch, 10 back, 10 to it again
+        let pulses = sim.pulses(Signal::Step);
+        assert_eq!(pulses.len(), 60);
+        assert_eq!(sim.position(), -40);
+        assert_eq!(ctrl.current_step(), 5);
+
+        let dir = sim.transitions_of(Signal::Dir);
+        let levels: Vec<_> = dir.iter().map(|t| t.level).collect();
+        assert_eq!(levels, [PinState::High, PinState::Low, PinState::High]);
+        let backoff = pulses
+            .iter()
+            .filter(|(rise, _)| (dir[1].time..dir[2].time).contains(rise))
+            .count();
+        assert_eq!(backoff, 10);
+
+        // seek at 2000 steps/s, back off and approach at 500 steps/s, the
+        // delay is rounded to ns
+        assert!((pulses[1].0 - pulses[0].0).abs_diff(500_000) <= 1);
+        assert!((pulses[59].0 - pulses[58].0).abs_diff(2_000_000) <= 1);
+    }
+
+    #[test]
+    fn home_should_report_switch_not_found() {
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert());
+        let mut switch = sim.input(|_| false);
+        let result = ctrl.home(&mut switch, &homing(2000, 500));
+        assert_eq!(result, Err(MotionError::SwitchNotFound));
+
+        // max travel, the position is not reset
+        assert_eq!(sim.pulses(Signal::Step).len(), 100);
+        assert_eq!(ctrl.current_step(), -100);
+    }
+
+    #[test]
+    fn home_should_report_the_switch_error() {
+        // the switch has its own error, the STEP pin is infallible
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert());
+        let result = ctrl.home(&mut BrokenSwitch, &homing(2000, 500));
+        assert_eq!(result, Err(MotionError::HomeSwitch(ReadError)));
+        assert!(sim.pulses(Signal::Step).is_empty());
+
+        let config = SensorlessHomingConfig::new(Direction::Forward, Num::from_num(1000), 5, 100);
+        let result = ctrl.home_sensorless(&mut DiagPin::new(BrokenSwitch), &config);
+        assert_eq!(result, Err(MotionError::StallDetect(ReadError)));
+    }
+
+    #[test]
+    fn home_should_validate_the_config_before_moving() {
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert());
+        let mut switch = sim.input(|_| false);
+        let result = ctrl.home(&mut switch, &homing(2000, 0));
+        assert_eq!(result, Err(MotionError::InvalidConfig));
+
+        let config = SensorlessHomingConfig::new(Direction::Forward, Num::ZERO, 5, 100);
+        let result = ctrl.home_sensorless(&mut DiagPin::new(sim.input(|_| false)), &config);
+        assert_eq!(result, Err(MotionError::InvalidConfig));
+        assert!(sim.transitions().is_empty());
+    }
+
+    #[test]
+    fn home_should_check_the_guard() {
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert()).with_guard(Fault);
+        let mut switch = sim.input(|_| false);
+        let result = ctrl.home(&mut switch, &homing(2000, 500));
+        assert_eq!(result, Err(MotionError::DriverFault));
+        assert!(sim.pulses(Signal::Step).is_empty());
+
+        // the home switch is the min end stop
+        let sim = Sim::new();
+        let end_stops = EndStops::new(sim.input(|sim| sim.position() <= -40), sim.input(|_| false));
+        let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert()).with_guard(end_stops);
+        let mut switch = sim.input(|sim| sim.position() <= -40);
+        ctrl.home(&mut switch, &homing(2000, 500)).unwrap();
+        assert_eq!(sim.position(), -40);
+    }
+}
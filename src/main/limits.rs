@@ -0,0 +1,128 @@
+//! SoftLimits
+//!
+//! software travel limits of an axis. when enabled, MontionCtrl checks every
+//! target/step against them, so that a bad target can not drive the axis past
+//! its mechanical end. the limits are meaningful only after homing, so they
+//! can be disabled([MontionCtrl::disable_soft_limits](super::MontionCtrl::disable_soft_limits))
+//! before homing and enabled again after it.
+
+use crate::Direction;
+
+/// How MontionCtrl deals with a target out of the soft limits
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LimitMode {
+    /// refuse the request with [MotionError::SoftLimit](crate::MotionError::SoftLimit)
+    Reject,
+    /// move as far as the limit allows
+    Clamp,
+}
+
+/// soft travel limits, both ends are included in the allowed range
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SoftLimits {
+    pub min: i32,
+    pub max: i32,
+    pub mode: LimitMode,
+}
+
+impl SoftLimits {
+    /// # Panics
+    ///
+    /// Panics, if `min` is greater than `max`.
+    pub fn new(min: i32, max: i32, mode: LimitMode) -> Self {
+        assert!(min <= max, "soft limit min must not be greater than max");
+        Self { min, max, mode }
+    }
+
+    pub fn contains(&self, step: i32) -> bool {
+        self.min <= step && step <= self.max
+    }
+
+    pub fn clamp(&self, step: i32) -> i32 {
+        step.clamp(self.min, self.max)
+    }
+
+    /// steps left from `step` to the limit in `direction`
+    pub(crate) fn room(&self, step: i32, direction: Direction) -> i64 {
+        match direction {
+            Direction::Forward => self.max as i64 - step as i64,
+            Direction::Backward => step as i64 - self.min as i64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim, SimA4988, SimConvert};
+    use crate::{MontionCtrl, MotionControlTrait, MotionError, MoveError, Num, SignedNum};
+
+    fn ctrl(sim: &Sim, mode: LimitMode) -> MontionCtrl<SimA4988, SimConvert> {
+        let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert());
+        ctrl.set_soft_limits(SoftLimits::new(-10, 50, mode));
+        ctrl
+    }
+
+    fn accel() -> Num {
+        Num::from_num(10_000)
+    }
+
+    fn velocity() -> Num {
+        Num::from_num(1000)
+    }
+
+    #[test]
+    fn reject_should_refuse_the_target_without_moving() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim, LimitMode::Reject);
+
+        let result = ctrl.move_to_position(accel(), velocity(), 60);
+        let error = MoveError {
+            completed: 0,
+            error: MotionError::SoftLimit,
+        };
+        assert_eq!(result, Err(error));
+        assert!(sim.transitions().is_empty());
+
+        // the ends are included
+        assert_eq!(ctrl.move_to_position(accel(), velocity(), 50), Ok(50));
+        assert_eq!(ctrl.step(), Err(MotionError::SoftLimit));
+        assert_eq!((ctrl.current_step(), sim.position()), (50, 50));
+
+        // velocity mode stops at once at the limit
+        ctrl.reset_position(-5).unwrap();
+        ctrl.enter_velocity_mode(accel(), SignedNum::from_num(-1000));
+        let mut result = Ok(true);
+        while result == Ok(true) {
+            result = ctrl.velocity_step();
+        }
+        assert_eq!(result, Err(MotionError::SoftLimit));
+        assert_eq!(ctrl.current_step(), -10);
+    }
+
+    #[test]
+    fn clamp_should_stop_at_the_limit() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim, LimitMode::Clamp);
+
+        assert_eq!(ctrl.move_to_position(accel(), velocity(), 60), Ok(50));
+        assert_eq!(ctrl.step(), Ok(()));
+        assert_eq!((ctrl.current_step(), sim.position()), (50, 50));
+        assert_eq!(sim.pulses(Signal::Step).len(), 50);
+
+        // velocity mode decelerates to stand still at the limit
+        ctrl.enter_velocity_mode(accel(), SignedNum::from_num(-1000));
+        while ctrl.velocity_step().unwrap() {}
+        assert_eq!(ctrl.current_step(), -10);
+        assert_eq!(sim.position(), -10);
+        let periods: Vec<_> = sim
+            .pulses(Signal::Step)
+            .windows(2)
+            .map(|w| w[1].0 - w[0].0)
+            .collect();
+        assert!(periods[periods.len() - 20..]
+            .windows(2)
+            .all(|w| w[1] > w[0]));
+        assert!(*periods.last().unwrap() > 4_000_000);
+    }
+}
@@ -0,0 +1,42 @@
+//! float helpers, core has no float math functions
+
+pub fn clamp_min(x: f64, min: f64) -> f64 {
+    if x < min {
+        min
+    } else {
+        x
+    }
+}
+
+pub fn abs(x: f64) -> f64 {
+    if x < 0.0 {
+        -x
+    } else {
+        x
+    }
+}
+
+/// square root by newton iteration
+pub fn sqrt(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut r = if x > 1.0 { x } else { 1.0 };
+    for _ in 0..100 {
+        let next = (r + x / r) / 2.0;
+        if next >= r {
+            break;
+        }
+        r = next;
+    }
+    r
+}
+
+/// round half away from zero
+pub fn round(x: f64) -> i32 {
+    if x < 0.0 {
+        (x - 0.5) as i32
+    } else {
+        (x + 0.5) as i32
+    }
+}
@@ -2,115 +2,278 @@
 //!
 //!
 
+#[cfg(feature = "async")]
+mod asynch;
+mod endstop;
+mod engine;
+mod homing;
+mod limits;
+pub(crate) mod math;
+mod multiaxis;
+mod plan;
+mod power;
+mod scurve;
 mod stepprofile;
+mod timing;
+mod velocity;
 
-use self::stepprofile::{Num, StepProfile};
-use crate::interfaces::{DelayToTicksTrait, MotionControlStepModeTrait, MotionControlTrait};
+#[cfg(feature = "async")]
+pub use self::asynch::AsyncMontionCtrl;
+pub use self::endstop::{EndStopAction, EndStops, NoSwitch, SwitchTrait};
+pub use self::engine::{EngineError, MotionEngine};
+pub use self::homing::{DiagPin, DriverStall, HomingConfig, SensorlessHomingConfig};
+pub use self::limits::{LimitMode, SoftLimits};
+pub use self::multiaxis::{AxesTrait, MultiAxisCtrl};
+pub use self::power::FaultGuard;
+pub use self::scurve::SCurve;
+pub use self::stepprofile::StepProfile;
+pub use self::timing::StepRateReport;
+
+use self::stepprofile::Num;
+use self::velocity::VelocityRamp;
+use crate::interfaces::{
+    DelayToTicksTrait, MotionControlStepModeTrait, MotionControlTrait, SignedNum, StepGuardTrait,
+};
 use crate::SetDirectionTrait;
 
-use super::{Direction, ResetTrait, SetStepModeTrait, StepTrait};
+use super::{
+    Direction, MotionError, MoveError, SetStepModeTrait, StepModeResetTrait, StepTrait,
+};
 // use core::{convert::TryFrom, ops};
-use ramp_maker::MotionProfile;
+use core::convert::Infallible;
+use ramp_maker::Trapezoidal;
+
+/// error of the motions that check the guard, the switches of the guard
+/// report theirs
+type GuardedError<PinErr, TimerErr, DirErr, SwitchErr> =
+    MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Infallible, SwitchErr>;
 
-pub struct MontionCtrl<DRIVER, Convert> {
+/// Profile is the acceleration ramp used by `move_to_position`, the default
+/// is trapezoidal. refer [StepProfile]
+///
+/// Guard is checked before every step, e.g. [EndStops], [FaultGuard]. the default `()`
+/// never aborts a motion. refer [StepGuardTrait]
+pub struct MontionCtrl<DRIVER, Convert, Profile = Trapezoidal, Guard = ()> {
     // state: State<Driver, Timer, Profile>,
     driver: DRIVER,
     current_step: i32,
     current_direction: Direction,
     convert: Convert,
+    profile: Profile,
+    // ramp of velocity mode
+    jog: VelocityRamp,
+    soft_limits: Option<SoftLimits>,
+    guard: Guard,
 }
 
 impl<DRIVER, Convert> MontionCtrl<DRIVER, Convert> {
     pub fn new(driver: DRIVER, convert: Convert ) -> Self {
+        // the accel will be replaced by each move
+        Self::with_profile(driver, convert, Trapezoidal::new(Num::from_num(1)))
+    }
+}
+
+impl<DRIVER, Convert, Profile> MontionCtrl<DRIVER, Convert, Profile> {
+    /// create with the given motion profile, e.g. [SCurve] or `ramp_maker::Flat`
+    pub fn with_profile(driver: DRIVER, convert: Convert, profile: Profile) -> Self {
         Self {
             driver,
             current_step: 0,
             current_direction: Direction::Forward,
             convert,
+            profile,
+            jog: VelocityRamp::new(),
+            soft_limits: None,
+            guard: (),
         }
     }
+}
+
+impl<DRIVER, Convert, Profile, Guard> MontionCtrl<DRIVER, Convert, Profile, Guard> {
+    /// attach a guard that is checked before every step, e.g. [EndStops].
+    /// it replaces the previous one.
+    pub fn with_guard<G>(self, guard: G) -> MontionCtrl<DRIVER, Convert, Profile, G> {
+        MontionCtrl {
+            driver: self.driver,
+            current_step: self.current_step,
+            current_direction: self.current_direction,
+            convert: self.convert,
+            profile: self.profile,
+            jog: self.jog,
+            soft_limits: self.soft_limits,
+            guard,
+        }
+    }
+
+    pub fn guard_mut(&mut self) -> &mut Guard {
+        &mut self.guard
+    }
+
+    /// the driver, e.g. to configure a UART/SPI driver between moves
+    pub fn driver_mut(&mut self) -> &mut DRIVER {
+        &mut self.driver
+    }
 
+    #[allow(clippy::result_unit_err)]
     pub fn release(self) -> Result<(DRIVER,), ()> {
         Ok((self.driver,))
     }
+
+    /// current position, unit is step. refer [MotionControlTrait::move_to_position]
+    pub fn current_step(&self) -> i32 {
+        self.current_step
+    }
+
+    /// enable soft travel limits, they apply to `move_to_position`, `step`
+    /// and velocity mode. homing is not limited by them.
+    pub fn set_soft_limits(&mut self, limits: SoftLimits) {
+        self.soft_limits = Some(limits);
+    }
+
+    /// disable soft travel limits, e.g. before homing
+    pub fn disable_soft_limits(&mut self) {
+        self.soft_limits = None;
+    }
+
+    pub fn soft_limits(&self) -> Option<SoftLimits> {
+        self.soft_limits
+    }
 }
 
-impl<DRIVER, Convert> MotionControlStepModeTrait for MontionCtrl<DRIVER, Convert>
+impl<DRIVER, Convert, Profile, Guard> MontionCtrl<DRIVER, Convert, Profile, Guard>
 where
-    DRIVER: SetStepModeTrait + ResetTrait,
+    DRIVER: StepTrait,
+    Convert: DelayToTicksTrait,
+{
+    /// output one STEP pulse, `delay` is the whole step period(high + low)
+    fn pulse<DirErr, SwitchErr>(
+        &mut self,
+        delay: fugit::NanosDurationU64,
+    ) -> Result<(), GuardedError<<DRIVER as StepTrait>::Error, Convert::Error, DirErr, SwitchErr>> {
+        let pulse_length = self.driver.pulse_length();
+        self.convert
+            .wait(&pulse_length, || self.driver.set_high().map_err(MotionError::Step))?;
+
+        let delay_left = if delay < 2 * pulse_length {
+            pulse_length
+        } else {
+            delay - pulse_length
+        };
+        let do_steplow = || self.driver.set_low().map_err(MotionError::Step);
+        self.convert.wait_period(&delay_left, do_steplow)?;
+
+        self.current_step += self.current_direction as i32;
+        Ok(())
+    }
+
+    /// check the guard before a step, an end stop is dealt according to its
+    /// [EndStopAction]. `velocity` is the current one, unit is steps per second.
+    ///
+    /// the error of the guard is returned when the motion should stop.
+    #[allow(clippy::type_complexity)]
+    fn check_guard<DirErr>(
+        &mut self,
+        direction: Direction,
+        velocity: f64,
+    ) -> Result<(), GuardedError<<DRIVER as StepTrait>::Error, Convert::Error, DirErr, Guard::SwitchError>>
+    where
+        Guard: StepGuardTrait<DRIVER, <DRIVER as StepTrait>::Error>,
+    {
+        let error = match self.guard.check(&mut self.driver, direction) {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        let action = self.guard.end_stop_action();
+        if let Some(mut ramp) = plan::stop_ramp(action, &error, direction, velocity) {
+            // the switch stays triggered, dont check it while decelerating
+            while let Some((_, delay)) = ramp.next_step() {
+                // but stop at the soft limit, as a move does
+                let next = self.current_step + direction as i32;
+                if self.soft_limits.is_some_and(|limits| !limits.contains(next)) {
+                    break;
+                }
+                let delay = self.convert.rampdelay_to_nano(Num::from_num(delay));
+                self.pulse(delay)?;
+            }
+        }
+        Err(error)
+    }
+}
+
+impl<DRIVER, Convert, Profile, Guard> MotionControlStepModeTrait
+    for MontionCtrl<DRIVER, Convert, Profile, Guard>
+where
+    DRIVER: SetStepModeTrait + StepModeResetTrait,
     DRIVER::StepMode: Copy,
     Convert: DelayToTicksTrait,
 {
     type StepMode = DRIVER::StepMode;
+    type PinError = <DRIVER as SetStepModeTrait>::Error;
+    type ResetError = DRIVER::ModeResetError;
+    type TimerError = Convert::Error;
 
     /// Set step mode of the wrapped driver
     ///
-    fn set_step_mode(&mut self, step_mode: Self::StepMode) -> Result<(), ()> {
-        let mut do_modify = || match self.driver.apply_mode_config(step_mode) {
-            Err(_) => return Err(()),
-            Ok(_) => return Ok(()),
+    fn set_step_mode(
+        &mut self,
+        step_mode: Self::StepMode,
+    ) -> Result<
+        (),
+        MotionError<Self::PinError, Self::TimerError, Self::PinError, Self::PinError, Self::ResetError>,
+    > {
+        let do_modify = || {
+            self.driver
+                .apply_mode_config(step_mode)
+                .map_err(MotionError::StepMode)
         };
 
         let total = DRIVER::SETUP_TIME + DRIVER::HOLD_TIME;
-        match self.convert.wait(&total, || {
-            return do_modify();
-        }) {
-            Ok(_) => {
-                let total = DRIVER::RESET_SETUP_TIME + DRIVER::RESET_HOLD_TIME;
-                let do_enable = || match self.driver.enable_driver() {
-                    Err(_) => return Err(()),
-                    Ok(_) => return Ok(()),
-                };
-                return self.convert.wait(&total, do_enable);
-            }
-            Err(_) => return Err(()),
-        }
+        self.convert.wait(&total, do_modify)?;
+
+        let do_reset = || self.driver.reset_for_mode().map_err(MotionError::Reset);
+        self.convert.wait(&DRIVER::MODE_RESET_TIME, do_reset)
     }
 }
 
-impl<DRIVER, Convert> MotionControlTrait for MontionCtrl<DRIVER, Convert>
+impl<DRIVER, Convert, Profile, Guard> MotionControlTrait
+    for MontionCtrl<DRIVER, Convert, Profile, Guard>
 where
     DRIVER: SetDirectionTrait + StepTrait,
     Convert: DelayToTicksTrait,
+    Profile: StepProfile,
+    Guard: StepGuardTrait<DRIVER, <DRIVER as StepTrait>::Error>,
 {
-    fn set_direction(&mut self, direction: Direction) -> Result<(), ()> {
+    type PinError = <DRIVER as StepTrait>::Error;
+    type DirError = <DRIVER as SetDirectionTrait>::Error;
+    type TimerError = Convert::Error;
+    type SwitchError = Guard::SwitchError;
+
+    fn set_direction(
+        &mut self,
+        direction: Direction,
+    ) -> Result<(), GuardedError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
+        let setup_time = self.driver.setup_time();
         let do_modify = || match direction {
-            Direction::Forward => match self.driver.set_forward() {
-                Err(_) => return Err(()),
-                Ok(_) => return Ok(()),
-            },
-            Direction::Backward => match self.driver.set_backward() {
-                Err(_) => return Err(()),
-                Ok(_) => return Ok(()),
-            },
+            Direction::Forward => self.driver.set_forward().map_err(MotionError::Direction),
+            Direction::Backward => self.driver.set_backward().map_err(MotionError::Direction),
         };
 
-        match self.convert.wait(&DRIVER::PULSE_LENGTH, do_modify){
-            Ok(_) => self.current_direction = direction,
-            Err(_) => return Err(()),
-        }
+        self.convert.wait(&setup_time, do_modify)?;
+        self.current_direction = direction;
         Ok(())
     }
 
-    fn step(&mut self) -> Result<(), ()> {
-        //////////////////////////////
-        // Start step pulse
-        match self.convert.wait(&DRIVER::PULSE_LENGTH, || {
-            match self.driver.set_high() {
-                Err(_) => return Err(()),
-                Ok(_) => return Ok(()),
-            };
-        }) {
-            Ok(_) => {
-                return self.convert.wait(&DRIVER::PULSE_LENGTH, || {
-                    match self.driver.set_low() {
-                        Err(_) => return Err(()),
-                        Ok(_) => return Ok(()),
-                    };
-                });
-            }
-            Err(_) => return Err(()),
+    /// a step in current direction, it also updates the current position.
+    /// a step out of the soft limits is refused, or skipped in [LimitMode::Clamp].
+    /// a step toward a triggered end stop is refused.
+    fn step(&mut self) -> Result<(), GuardedError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
+        let next = self.current_step + self.current_direction as i32;
+        if !plan::limit_step(self.soft_limits, next)? {
+            return Ok(());
         }
+        self.check_guard(self.current_direction, 0.0)?;
+        self.pulse(2 * self.driver.pulse_length())
     }
 
     fn step_high(&mut self) {
@@ -125,75 +288,114 @@ where
         target_accel: Num,
         max_velocity: Num,
         target_step: i32,
-    ) -> Result<i32,i32> {
+    ) -> Result<i32, MoveError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
+        if self.jog.is_running() {
+            return Err(MoveError { completed: 0, error: MotionError::Busy });
+        }
+
+        let target_step = plan::limit_target(self.soft_limits, target_step)
+            .map_err(|error| MoveError { completed: 0, error })?;
+
         let orig = self.current_step;
         let steps_from_here = target_step - self.current_step;
+        let direction =
+            match plan::start_move(&mut self.profile, target_accel, max_velocity, steps_from_here) {
+                Some(direction) => direction,
+                None => return Ok(0), // dont need move
+            };
+        if let Err(error) = self.set_direction(direction) {
+            return Err(MoveError { completed: 0, error });
+        }
 
-        let prof = StepProfile::new(target_accel);
+        // velocity of the last step, to decelerate from after an end stop
+        let mut velocity = 0.0;
+        while let Some(delay) = self.profile.next_delay() {
+            ///////////////////////////////////////
+            let result = self.check_guard(direction, velocity).and_then(|_| {
+                velocity = plan::step_velocity(delay, velocity);
+                let delay = self.convert.rampdelay_to_nano(delay);
+                self.pulse(delay)
+            });
+            if let Err(error) = result {
+                return Err(MoveError {
+                    completed: self.current_step - orig,
+                    error,
+                });
+            }
+        }
 
-        prof.0
-            .borrow_mut()
-            .enter_position_mode(max_velocity, steps_from_here.abs() as u32);
+        Ok(self.current_step - orig)
+    }
 
-        let direction = if steps_from_here > 0 {
-            Direction::Forward
-        } else if steps_from_here < 0 {
+    fn enter_velocity_mode(&mut self, target_accel: Num, velocity: SignedNum) {
+        let direction = if velocity < SignedNum::ZERO {
             Direction::Backward
         } else {
-            return Ok(0); // dont need move
+            Direction::Forward
         };
-        if self.set_direction(direction).is_err() {
-            return Err(0);
+        self.jog.set_target(target_accel, direction, velocity.unsigned_abs());
+    }
+
+    fn stop(&mut self) {
+        self.jog.stop();
+    }
+
+    fn velocity_step(&mut self) -> Result<bool, GuardedError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
+        if let Some(limits) = self.soft_limits {
+            // decelerate in time to stand still at the limit
+            if limits.mode == LimitMode::Clamp
+                && self.jog.is_running()
+                && limits.room(self.current_step, self.jog.direction()) <= self.jog.stopping_steps()
+            {
+                self.jog.stop();
+            }
         }
 
-        let mut stepped_num: usize = 0;
-        while let Some(delay) = prof.0.borrow_mut().next_delay() {
-            ///////////////////////////////////////
-            match self.convert.wait(&DRIVER::PULSE_LENGTH, || {
-                match self.driver.set_high() {
-                    Err(_) => return Err(()),
-                    Ok(_) => return Ok(()),
-                };
-            }) {
-                Ok(_) => {
-                    let delay = self.convert.rampdelay_to_nano(delay);
-                    let delay_left = if delay < 2 * DRIVER::PULSE_LENGTH {
-                        DRIVER::PULSE_LENGTH
-                    } else {
-                        delay - DRIVER::PULSE_LENGTH
-                    };
-                    let do_steplow = || match self.driver.set_low() {
-                        Err(_) => return Err(()),
-                        Ok(_) => return Ok(()),
-                    };
-                    match self.convert.wait(&delay_left, do_steplow) {
-                        Ok(_) => {
-                            self.current_step += self.current_direction as i32;
-                            stepped_num += 1;
-                        }
-                        Err(_) => break,
-                    }
-                }
-                Err(_) => break,
+        let (direction, delay) = match self.jog.next_step() {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+
+        match plan::limit_step(self.soft_limits, self.current_step + direction as i32) {
+            Ok(true) => {}
+            result => {
+                self.jog.halt();
+                return result;
             }
         }
 
-        if steps_from_here.abs() as usize == stepped_num {
-            return Ok(self.current_step-orig);
+        let result = if direction != self.current_direction {
+            self.set_direction(direction)
         } else {
-            return Err(self.current_step-orig);
-        }
+            Ok(())
+        };
+        let result = result
+            .and_then(|_| self.check_guard(direction, 1.0 / delay))
+            .and_then(|_| {
+                let delay = self.convert.rampdelay_to_nano(Num::from_num(delay));
+                self.pulse(delay)
+            });
 
+        if result.is_err() {
+            // the motion is broken, dont decelerate from the old velocity
+            self.jog.halt();
+        }
+        result.map(|_| true)
     }
 
-    fn reset_position(&mut self, step: i32) -> Result<(), ()> {
+    fn reset_position(
+        &mut self,
+        step: i32,
+    ) -> Result<(), GuardedError<Self::PinError, Self::TimerError, Self::DirError, Self::SwitchError>> {
         self.current_step = step;
         Ok(())
     }
 
     fn help_delay_ns(&mut self, timeout: u64) {
         let timeout = fugit::NanosDurationU64::from_ticks(timeout);
-        let _ = self.convert.wait(&timeout, || Ok(()));
+        let _ = self
+            .convert
+            .wait::<MotionError<Self::PinError, Self::TimerError>>(&timeout, || Ok(()));
     }
 }
 //
@@ -0,0 +1,323 @@
+//! MultiAxisCtrl
+//!
+//! coordinated linear moves of several axes(2-4), e.g. plotters and
+//! pick-and-place heads. all axes start and finish together, the tool follows
+//! a straight line in step space.
+//!
+//! the axes share one convert(timer). a single ramp is planned along the
+//! dominant axis(the one with the most steps), it steps on every tick of the
+//! ramp, the other axes are distributed over the ticks with Bresenham(DDA).
+//!
+//! ```text
+//!   dominant X: | | | | | | | | | |    10 steps
+//!   Y:          |   |   |   |   |       5 steps
+//!   Z:            |       |     |       3 steps
+//! ```
+//!
+//! the drivers are passed as a tuple, e.g. `(x_driver, y_driver)`. they can be
+//! of different types, but their STEP pin errors must be the same type, so
+//! must their DIR pin errors.
+
+use ramp_maker::Trapezoidal;
+
+use super::stepprofile::{Num, StepProfile};
+use crate::interfaces::{DelayToTicksTrait, SetDirectionTrait, StepTrait};
+use crate::{Direction, MotionError};
+
+/// A group of N drivers moved by [MultiAxisCtrl]
+///
+/// it is implemented for tuples of 2-4 drivers that implement [StepTrait] and
+/// [SetDirectionTrait]. the timings are the longest of all drivers.
+pub trait AxesTrait<const N: usize> {
+    /// The error of the drivers' STEP pins
+    type Error;
+    /// The error of the drivers' DIR pins
+    type DirError;
+
+    fn set_direction(&mut self, axis: usize, direction: Direction) -> Result<(), Self::DirError>;
+    fn set_high(&mut self, axis: usize) -> Result<(), Self::Error>;
+    fn set_low(&mut self, axis: usize) -> Result<(), Self::Error>;
+
+    /// the longest of the drivers' [StepTrait::pulse_length]
+    fn pulse_length(&self) -> fugit::NanosDurationU64;
+    /// the longest of the drivers' [SetDirectionTrait::setup_time]
+    fn setup_time(&self) -> fugit::NanosDurationU64;
+}
+
+fn longest(durations: &[fugit::NanosDurationU64]) -> fugit::NanosDurationU64 {
+    let out = durations.iter().map(|d| d.ticks()).max().unwrap_or(0);
+    fugit::NanosDurationU64::from_ticks(out)
+}
+
+macro_rules! impl_axes {
+    ($n:literal; $($driver:ident $idx:tt),+) => {
+        impl<E, DirE, $($driver),+> AxesTrait<$n> for ($($driver,)+)
+        where
+            $($driver: StepTrait<Error = E> + SetDirectionTrait<Error = DirE>,)+
+        {
+            type Error = E;
+            type DirError = DirE;
+
+            fn set_direction(&mut self, axis: usize, direction: Direction) -> Result<(), DirE> {
+                match (axis, direction) {
+                    $(
+                        ($idx, Direction::Forward) => self.$idx.set_forward(),
+                        ($idx, Direction::Backward) => self.$idx.set_backward(),
+                    )+
+                    _ => Ok(()),
+                }
+            }
+
+            fn set_high(&mut self, axis: usize) -> Result<(), E> {
+                match axis {
+                    $($idx => self.$idx.set_high(),)+
+                    _ => Ok(()),
+                }
+            }
+
+            fn set_low(&mut self, axis: usize) -> Result<(), E> {
+                match axis {
+                    $($idx => self.$idx.set_low(),)+
+                    _ => Ok(()),
+                }
+            }
+
+            fn pulse_length(&self) -> fugit::NanosDurationU64 {
+                longest(&[$(self.$idx.pulse_length()),+])
+            }
+
+            fn setup_time(&self) -> fugit::NanosDurationU64 {
+                longest(&[$(self.$idx.setup_time()),+])
+            }
+        }
+    };
+}
+
+impl_axes!(2; D0 0, D1 1);
+impl_axes!(3; D0 0, D1 1, D2 2);
+impl_axes!(4; D0 0, D1 1, D2 2, D3 3);
+
+/// coordinated motion control of N axes, refer the module doc
+///
+/// Profile is the ramp of the dominant axis, the default is trapezoidal.
+pub struct MultiAxisCtrl<AXES, Convert, const N: usize, Profile = Trapezoidal> {
+    axes: AXES,
+    convert: Convert,
+    profile: Profile,
+    current_step: [i32; N],
+    current_direction: [Direction; N],
+}
+
+impl<AXES, Convert, const N: usize> MultiAxisCtrl<AXES, Convert, N> {
+    pub fn new(axes: AXES, convert: Convert) -> Self {
+        // the accel will be replaced by each move
+        Self::with_profile(axes, convert, Trapezoidal::new(Num::from_num(1)))
+    }
+}
+
+impl<AXES, Convert, const N: usize, Profile> MultiAxisCtrl<AXES, Convert, N, Profile> {
+    /// create with the given motion profile, refer [StepProfile]
+    pub fn with_profile(axes: AXES, convert: Convert, profile: Profile) -> Self {
+        Self {
+            axes,
+            convert,
+            profile,
+            current_step: [0; N],
+            current_direction: [Direction::Forward; N],
+        }
+    }
+
+    pub fn release(self) -> (AXES, Convert) {
+        (self.axes, self.convert)
+    }
+
+    /// current position of each axis, unit is step
+    pub fn current_position(&self) -> [i32; N] {
+        self.current_step
+    }
+
+    /// Reset internal position to the given value, it does not move
+    pub fn reset_position(&mut self, position: [i32; N]) {
+        self.current_step = position;
+    }
+}
+
+impl<AXES, Convert, const N: usize, Profile> MultiAxisCtrl<AXES, Convert, N, Profile>
+where
+    AXES: AxesTrait<N>,
+    Convert: DelayToTicksTrait,
+    Profile: StepProfile,
+{
+    /// Move all axes to the given position along a straight line
+    ///
+    /// accel(unit is steps per second^2) and velocity(unit is steps per
+    /// second) apply to the dominant axis, the other axes are slower in
+    /// proportion. if the move stops early on error, the position reached is
+    /// kept, refer [MultiAxisCtrl::current_position].
+    #[allow(clippy::type_complexity)]
+    pub fn move_to_position(
+        &mut self,
+        target_accel: Num,
+        max_velocity: Num,
+        target: [i32; N],
+    ) -> Result<(), MotionError<AXES::Error, Convert::Error, AXES::DirError>> {
+        let (delta, direction) = axis_steps(&self.current_step, &target);
+        let dominant = delta.iter().copied().max().unwrap_or(0);
+        if dominant == 0 {
+            return Ok(()); // dont need move
+        }
+
+        // set all DIR signals, then wait their setup time once
+        let setup_time = self.axes.setup_time();
+        let axes = &mut self.axes;
+        let current_direction = &mut self.current_direction;
+        let do_modify = || {
+            for axis in (0..N).filter(|&axis| delta[axis] > 0) {
+                axes.set_direction(axis, direction[axis])
+                    .map_err(MotionError::Direction)?;
+                current_direction[axis] = direction[axis];
+            }
+            Ok(())
+        };
+        self.convert.wait(&setup_time, do_modify)?;
+
+        self.profile.set_target_accel(target_accel);
+        self.profile.enter_position_mode(max_velocity, dominant);
+
+        // bresenham error of each axis, start at half to center the steps
+        let mut error = [dominant as u64 / 2; N];
+        while let Some(delay) = self.profile.next_delay() {
+            let stepping = bresenham_step(&mut error, &delta, dominant);
+            let delay = self.convert.rampdelay_to_nano(delay);
+            self.pulse(&stepping, delay)?;
+        }
+        Ok(())
+    }
+
+    /// output one STEP pulse on the selected axes at the same time, `delay`
+    /// is the whole step period(high + low)
+    #[allow(clippy::type_complexity)]
+    fn pulse(
+        &mut self,
+        stepping: &[bool; N],
+        delay: fugit::NanosDurationU64,
+    ) -> Result<(), MotionError<AXES::Error, Convert::Error, AXES::DirError>> {
+        let pulse_length = self.axes.pulse_length();
+        let axes = &mut self.axes;
+        let do_stephigh = || {
+            for axis in (0..N).filter(|&axis| stepping[axis]) {
+                axes.set_high(axis).map_err(MotionError::Step)?;
+            }
+            Ok(())
+        };
+        self.convert.wait(&pulse_length, do_stephigh)?;
+
+        let delay_left = if delay < 2 * pulse_length {
+            pulse_length
+        } else {
+            delay - pulse_length
+        };
+        let axes = &mut self.axes;
+        let do_steplow = || {
+            for axis in (0..N).filter(|&axis| stepping[axis]) {
+                axes.set_low(axis).map_err(MotionError::Step)?;
+            }
+            Ok(())
+        };
+        self.convert.wait_period(&delay_left, do_steplow)?;
+
+        for axis in (0..N).filter(|&axis| stepping[axis]) {
+            self.current_step[axis] += self.current_direction[axis] as i32;
+        }
+        Ok(())
+    }
+}
+
+/// steps and direction of each axis from `from` to `to`. the steps are
+/// computed in i64, a move between the i32 limits is u32::MAX steps.
+fn axis_steps<const N: usize>(from: &[i32; N], to: &[i32; N]) -> ([u32; N], [Direction; N]) {
+    let mut delta = [0u32; N];
+    let mut direction = [Direction::Forward; N];
+    for axis in 0..N {
+        let steps = to[axis] as i64 - from[axis] as i64;
+        delta[axis] = steps.unsigned_abs() as u32;
+        direction[axis] = if steps < 0 {
+            Direction::Backward
+        } else {
+            Direction::Forward
+        };
+    }
+    (delta, direction)
+}
+
+/// advance the bresenham `error` of each axis by a step of the dominant
+/// axis, return the axes that step. the error is u64 since it reaches about
+/// twice the dominant steps.
+fn bresenham_step<const N: usize>(
+    error: &mut [u64; N],
+    delta: &[u32; N],
+    dominant: u32,
+) -> [bool; N] {
+    let mut stepping = [false; N];
+    for axis in 0..N {
+        error[axis] += delta[axis] as u64;
+        if error[axis] >= dominant as u64 {
+            error[axis] -= dominant as u64;
+            stepping[axis] = true;
+        }
+    }
+    stepping
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim};
+
+    #[test]
+    fn move_to_position_should_distribute_the_steps() {
+        let sim_x = Sim::new();
+        let sim_y = sim_x.axis();
+        let mut ctrl = MultiAxisCtrl::new((sim_x.a4988(), sim_y.a4988()), sim_x.convert());
+        let (accel, velocity) = (Num::from_num(10_000), Num::from_num(1000));
+
+        ctrl.move_to_position(accel, velocity, [10, 5]).unwrap();
+        let x = sim_x.pulses(Signal::Step);
+        let y = sim_y.pulses(Signal::Step);
+        assert_eq!((x.len(), y.len()), (10, 5));
+
+        // Y steps with every other X step, from the first one
+        let every_other: Vec<u64> = x.iter().step_by(2).map(|&(rise, _)| rise).collect();
+        let y_rises: Vec<u64> = y.iter().map(|&(rise, _)| rise).collect();
+        assert_eq!(y_rises, every_other);
+        assert_eq!(ctrl.current_position(), [10, 5]);
+
+        // both backward, 10 and 8 steps
+        sim_x.clear();
+        sim_y.clear();
+        ctrl.move_to_position(accel, velocity, [0, -3]).unwrap();
+        assert_eq!(sim_x.pulses(Signal::Step).len(), 10);
+        assert_eq!(sim_y.pulses(Signal::Step).len(), 8);
+        assert_eq!(ctrl.current_position(), [0, -3]);
+        assert_eq!([sim_x.position(), sim_y.position()], [-10, -8]);
+    }
+
+    #[test]
+    fn steps_should_not_overflow_at_the_i32_limits() {
+        let (delta, direction) = axis_steps(&[i32::MAX, i32::MIN], &[i32::MIN, i32::MAX]);
+        assert_eq!(delta, [u32::MAX; 2]);
+        assert_eq!(direction, [Direction::Backward, Direction::Forward]);
+
+        // the error passes u32::MAX on the first step
+        let delta = [u32::MAX, u32::MAX - 1, 1];
+        let mut error = [u32::MAX as u64 / 2; 3];
+        assert_eq!(
+            bresenham_step(&mut error, &delta, u32::MAX),
+            [true, true, false]
+        );
+        assert_eq!(
+            bresenham_step(&mut error, &delta, u32::MAX),
+            [true, true, false]
+        );
+    }
+}
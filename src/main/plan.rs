@@ -0,0 +1,90 @@
+//! planning shared by [MontionCtrl](super::MontionCtrl) and
+//! [AsyncMontionCtrl](super::AsyncMontionCtrl)
+//!
+//! it touches neither the driver nor the timer, the controllers only output
+//! the steps it plans, so the two behave the same.
+
+use core::cmp::Ordering;
+
+use super::endstop::EndStopAction;
+use super::limits::{LimitMode, SoftLimits};
+use super::stepprofile::{Num, StepProfile};
+use super::velocity::VelocityRamp;
+use super::GuardedError;
+use crate::{Direction, MotionError};
+
+/// the target of a move checked against the soft limits, it is clamped to
+/// the limit in [LimitMode::Clamp]
+pub(crate) fn limit_target<PinErr, TimerErr, DirErr, SwitchErr>(
+    limits: Option<SoftLimits>,
+    target: i32,
+) -> Result<i32, GuardedError<PinErr, TimerErr, DirErr, SwitchErr>> {
+    match limits {
+        Some(limits) if !limits.contains(target) => match limits.mode {
+            LimitMode::Reject => Err(MotionError::SoftLimit),
+            LimitMode::Clamp => Ok(limits.clamp(target)),
+        },
+        _ => Ok(target),
+    }
+}
+
+/// whether a single step to `next` is output, it is skipped(false) in
+/// [LimitMode::Clamp] if out of the soft limits
+pub(crate) fn limit_step<PinErr, TimerErr, DirErr, SwitchErr>(
+    limits: Option<SoftLimits>,
+    next: i32,
+) -> Result<bool, GuardedError<PinErr, TimerErr, DirErr, SwitchErr>> {
+    match limits {
+        Some(limits) if !limits.contains(next) => match limits.mode {
+            LimitMode::Reject => Err(MotionError::SoftLimit),
+            LimitMode::Clamp => Ok(false),
+        },
+        _ => Ok(true),
+    }
+}
+
+/// plan the ramp of a move of `steps` from here, return its direction or
+/// `None` if it dont need move
+pub(crate) fn start_move<Profile: StepProfile>(
+    profile: &mut Profile,
+    target_accel: Num,
+    max_velocity: Num,
+    steps: i32,
+) -> Option<Direction> {
+    profile.set_target_accel(target_accel);
+    profile.enter_position_mode(max_velocity, steps.unsigned_abs());
+
+    match steps.cmp(&0) {
+        Ordering::Greater => Some(Direction::Forward),
+        Ordering::Less => Some(Direction::Backward),
+        Ordering::Equal => None,
+    }
+}
+
+/// velocity of a step of the ramp's `delay`, unit is steps per second.
+/// `last` is kept for a zero delay.
+pub(crate) fn step_velocity(delay: Num, last: f64) -> f64 {
+    if delay > Num::ZERO {
+        1.0 / delay.to_num::<f64>()
+    } else {
+        last
+    }
+}
+
+/// the ramp to decelerate on after the guard stopped the motion with
+/// `error`, `None` means a hard stop. `velocity` is the current one.
+pub(crate) fn stop_ramp<PinErr, TimerErr, DirErr, SwitchErr>(
+    action: EndStopAction,
+    error: &GuardedError<PinErr, TimerErr, DirErr, SwitchErr>,
+    direction: Direction,
+    velocity: f64,
+) -> Option<VelocityRamp> {
+    match (error, action) {
+        (MotionError::EndStop(_), EndStopAction::Decelerate(accel)) => {
+            let mut ramp = VelocityRamp::new();
+            ramp.decelerate_from(accel, direction, velocity);
+            Some(ramp)
+        }
+        _ => None,
+    }
+}
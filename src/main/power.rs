@@ -0,0 +1,231 @@
+//! power control of the driver: enable/disable the outputs, sleep/wake up,
+//! and fault reporting. each one is available only if the driver supports
+//! it, refer [OutputEnableTrait], [SleepTrait] and [FaultTrait].
+//!
+//! disable the outputs(or sleep) when the axis is idle to de-energize the
+//! coils and reduce heat, the motor loses its holding torque then.
+//!
+//! to abort moves on fault, attach [FaultGuard] by
+//! [MontionCtrl::with_guard], the fault is checked before every step.
+
+use core::convert::Infallible;
+
+use super::MontionCtrl;
+use crate::interfaces::{
+    DelayToTicksTrait, FaultTrait, OutputEnableTrait, SleepTrait, StepGuardTrait,
+};
+use crate::{Direction, MotionError};
+
+/// Guard that aborts the motion with [MotionError::DriverFault], refer [StepGuardTrait]
+///
+/// combine it with end stops by a tuple, e.g. `(FaultGuard, end_stops)`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FaultGuard;
+
+impl<DRIVER, PinErr> StepGuardTrait<DRIVER, PinErr> for FaultGuard
+where
+    DRIVER: FaultTrait<Error = PinErr>,
+{
+    type SwitchError = Infallible;
+
+    fn check<TimerErr, DirErr>(
+        &mut self,
+        driver: &mut DRIVER,
+        _direction: Direction,
+    ) -> Result<(), MotionError<PinErr, TimerErr, DirErr, PinErr, PinErr, Infallible>> {
+        if driver.is_fault().map_err(MotionError::Fault)? {
+            return Err(MotionError::DriverFault);
+        }
+        Ok(())
+    }
+}
+
+impl<DRIVER, Convert, Profile, Guard> MontionCtrl<DRIVER, Convert, Profile, Guard>
+where
+    DRIVER: OutputEnableTrait,
+    Convert: DelayToTicksTrait,
+{
+    /// energize the motor, it waits until the driver can accept steps
+    pub fn enable_motor(
+        &mut self,
+    ) -> Result<(), MotionError<<DRIVER as OutputEnableTrait>::Error, Convert::Error>> {
+        let do_enable = || self.driver.enable_output().map_err(MotionError::Enable);
+        self.convert.wait(&DRIVER::ENABLE_SETUP_TIME, do_enable)
+    }
+
+    /// de-energize the motor, the position is kept but may be lost if the
+    /// axis is moved by external force
+    pub fn disable_motor(
+        &mut self,
+    ) -> Result<(), MotionError<<DRIVER as OutputEnableTrait>::Error, Convert::Error>> {
+        self.driver.disable_output().map_err(MotionError::Enable)
+    }
+}
+
+impl<DRIVER, Convert, Profile, Guard> MontionCtrl<DRIVER, Convert, Profile, Guard>
+where
+    DRIVER: SleepTrait,
+    Convert: DelayToTicksTrait,
+{
+    /// put the driver into sleep mode, it also de-energizes the motor
+    pub fn sleep(&mut self) -> Result<(), MotionError<<DRIVER as SleepTrait>::Error, Convert::Error>> {
+        self.driver.sleep().map_err(MotionError::Sleep)
+    }
+
+    /// wake the driver up, it waits the wake up time of the driver
+    pub fn wake_up(
+        &mut self,
+    ) -> Result<(), MotionError<<DRIVER as SleepTrait>::Error, Convert::Error>> {
+        let do_wake_up = || self.driver.wake_up().map_err(MotionError::Sleep);
+        self.convert.wait(&DRIVER::WAKE_UP_TIME, do_wake_up)
+    }
+}
+
+impl<DRIVER, Convert, Profile, Guard> MontionCtrl<DRIVER, Convert, Profile, Guard>
+where
+    DRIVER: FaultTrait,
+    Convert: DelayToTicksTrait,
+{
+    /// return [MotionError::DriverFault] if the driver reports a fault
+    pub fn check_fault(
+        &mut self,
+    ) -> Result<(), MotionError<<DRIVER as FaultTrait>::Error, Convert::Error>> {
+        if self.driver.is_fault().map_err(MotionError::Fault)? {
+            return Err(MotionError::DriverFault);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Signal, Sim, SimInput, SimPin};
+    use crate::{
+        EnableDirectionControlTrait, EnableFaultControlTrait, EnableOutputControlTrait,
+        EnableSleepControlTrait, EnableStepControlTrait, MotionControlTrait, MoveError, Num,
+        DRV8825, STSPIN220,
+    };
+    use embedded_hal::digital::v2::{InputPin, OutputPin, PinState};
+
+    type SimDRV8825 = DRV8825<SimPin, SimInput, SimPin, (), (), (), (), SimPin, SimPin>;
+
+    /// nFAULT is pulled low after `fault_at` steps
+    fn drv8825(sim: &Sim, fault_at: i32) -> SimDRV8825 {
+        DRV8825::new()
+            .enable_output_control(sim.pin(Signal::Enable))
+            .enable_sleep_control(sim.pin(Signal::Sleep))
+            .enable_fault_control(sim.input(move |sim| sim.position() < fault_at))
+            .enable_step_control(sim.pin(Signal::Step))
+            .enable_direction_control(sim.pin(Signal::Dir))
+    }
+
+    fn accel() -> Num {
+        Num::from_num(10_000)
+    }
+
+    fn velocity() -> Num {
+        Num::from_num(1000)
+    }
+
+    /// time of the first transition of the signal
+    fn first(sim: &Sim, signal: Signal) -> u64 {
+        sim.transitions_of(signal)[0].time
+    }
+
+    #[test]
+    fn enable_and_wake_up_should_wait_before_the_first_step() {
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(drv8825(&sim, i32::MAX), sim.convert());
+
+        ctrl.enable_motor().unwrap();
+        ctrl.move_to_position(accel(), velocity(), 1).unwrap();
+        let enabled = sim.transitions_of(Signal::Enable)[0];
+        assert_eq!(enabled.level, PinState::Low);
+        assert!(first(&sim, Signal::Dir) - enabled.time >= 650);
+
+        ctrl.sleep().unwrap();
+        sim.clear();
+        ctrl.wake_up().unwrap();
+        ctrl.move_to_position(accel(), velocity(), 2).unwrap();
+        let woken = sim.transitions_of(Signal::Sleep)[0];
+        assert_eq!(woken.level, PinState::High);
+        // tWAKE is 1.7ms
+        assert!(first(&sim, Signal::Step) - woken.time >= 1_700_000);
+
+        ctrl.disable_motor().unwrap();
+        assert_eq!(ctrl.check_fault(), Ok(()));
+        let levels: Vec<PinState> = sim
+            .transitions_of(Signal::Enable)
+            .iter()
+            .map(|t| t.level)
+            .collect();
+        assert_eq!(levels, [PinState::High]);
+    }
+
+    #[test]
+    fn fault_guard_should_abort_the_move() {
+        let sim = Sim::new();
+        let mut ctrl = MontionCtrl::new(drv8825(&sim, 5), sim.convert()).with_guard(FaultGuard);
+        assert_eq!(ctrl.check_fault(), Ok(()));
+
+        let error = MoveError {
+            completed: 5,
+            error: MotionError::DriverFault,
+        };
+        assert_eq!(ctrl.move_to_position(accel(), velocity(), 20), Err(error));
+        assert_eq!(ctrl.check_fault(), Err(MotionError::DriverFault));
+        assert_eq!((ctrl.current_step(), sim.position()), (5, 5));
+    }
+
+    /// the open-drain EN/FAULT line, the chip pulls it low after `fault_at` steps
+    struct EnableFault {
+        out: SimPin,
+        line: SimInput,
+    }
+
+    impl OutputPin for EnableFault {
+        type Error = core::convert::Infallible;
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.out.set_low()
+        }
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.out.set_high()
+        }
+    }
+
+    impl InputPin for EnableFault {
+        type Error = core::convert::Infallible;
+        fn is_high(&self) -> Result<bool, Self::Error> {
+            self.line.is_high()
+        }
+        fn is_low(&self) -> Result<bool, Self::Error> {
+            self.line.is_low()
+        }
+    }
+
+    #[test]
+    fn stspin220_should_report_the_fault_only_while_enabled() {
+        let sim = Sim::new();
+        let enable_fault = EnableFault {
+            out: sim.pin(Signal::Enable),
+            line: sim.input(|sim| sim.position() < 3),
+        };
+        let driver = STSPIN220::new()
+            .enable_output_control(enable_fault)
+            .enable_step_control(sim.pin(Signal::Step))
+            .enable_direction_control(sim.pin(Signal::Dir));
+        let mut ctrl = MontionCtrl::new(driver, sim.convert()).with_guard(FaultGuard);
+
+        ctrl.enable_motor().unwrap();
+        let error = MoveError {
+            completed: 3,
+            error: MotionError::DriverFault,
+        };
+        assert_eq!(ctrl.move_to_position(accel(), velocity(), 10), Err(error));
+
+        // the app holds the line low, it is not a fault
+        ctrl.disable_motor().unwrap();
+        assert_eq!(ctrl.check_fault(), Ok(()));
+    }
+}
@@ -0,0 +1,328 @@
+//! SCurve
+//!
+//! jerk-limited(7-segment S-curve) motion profile. compare to the trapezoidal
+//! profile, the acceleration is not switched on/off at once but ramps with a
+//! limited jerk, which reduces resonance and missed steps on long belt axes.
+//!
+//! ```text
+//!   accel   ┌──┐                 jerk phases: 1,3,5,7
+//!          /    \                const accel: 2,6
+//!   ──────/      \──────  ─────  const velocity: 4
+//!                        \      /
+//!                         └────┘
+//!          1  2  3   4    5 6 7
+//! ```
+//!
+//! the profile is computed as position over time. the accel segment(phase 1-3)
+//! is solved analytically, the decel segment(phase 5-7) is its mirror, so the
+//! ramp is symmetric. each step's delay is the time between two neighboring
+//! step positions. if the move is too short to reach max velocity, the peak
+//! velocity is reduced so that accel and decel segments meet in the middle.
+//!
+//! the internal computation uses f64, the interface uses [Num] like ramp-maker.
+
+use ramp_maker::MotionProfile;
+
+use super::math::{abs, clamp_min, sqrt};
+use super::stepprofile::{Num, StepProfile};
+
+/// jerk-limited(S-curve) motion profile
+///
+/// target accel unit is steps per second^2, max jerk unit is steps per second^3.
+pub struct SCurve {
+    max_jerk: f64,
+    target_accel: f64,
+
+    num_steps: u32,
+    next_step: u32,
+    // time of the last position returned, unit is second
+    time_prev: f64,
+    plan: Plan,
+}
+
+/// the accel segment of current move, decel segment is its mirror
+#[derive(Clone, Copy, Debug, Default)]
+struct Plan {
+    /// peak velocity
+    velocity: f64,
+    /// duration of each jerk phase
+    jerk_time: f64,
+    /// duration of the constant accel phase
+    accel_time: f64,
+    /// distance of the accel segment
+    accel_steps: f64,
+    /// duration of the whole move
+    total_time: f64,
+}
+
+impl SCurve {
+    /// Create a new instance of `SCurve`
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `target_accel` or `max_jerk` is zero.
+    pub fn new(target_accel: Num, max_jerk: Num) -> Self {
+        let mut out = Self {
+            max_jerk: max_jerk.to_num(),
+            target_accel: 0.0,
+            num_steps: 0,
+            next_step: 0,
+            time_prev: 0.0,
+            plan: Plan::default(),
+        };
+        assert!(out.max_jerk > 0.0, "max jerk must not be zero");
+        out.set_target_accel(target_accel);
+        out
+    }
+
+    /// the max jerk, unit is steps per second^3
+    pub fn max_jerk(&self) -> Num {
+        Num::from_num(self.max_jerk)
+    }
+
+    /// phase durations(jerk, const accel) to reach `velocity` from standstill
+    fn phase_times(&self, velocity: f64) -> (f64, f64) {
+        let (j, a) = (self.max_jerk, self.target_accel);
+        if velocity >= a * a / j {
+            (a / j, velocity / a - a / j)
+        } else {
+            // target accel is never reached
+            (sqrt(velocity / j), 0.0)
+        }
+    }
+
+    /// distance needed to reach `velocity` from standstill
+    fn accel_steps(&self, velocity: f64) -> f64 {
+        let (tj, ta) = self.phase_times(velocity);
+        // the accel segment is point-symmetric, average velocity is half of peak
+        velocity * (2.0 * tj + ta) / 2.0
+    }
+
+    fn plan(&self, max_velocity: Option<f64>, num_steps: u32) -> Plan {
+        let half = num_steps as f64 / 2.0;
+
+        let velocity = match max_velocity {
+            Some(v) if self.accel_steps(v) <= half => v,
+            _ => {
+                // max velocity can not be reached, find the peak velocity whose
+                // accel segment ends at the middle of the move
+                let mut hi = match max_velocity {
+                    Some(v) => v,
+                    None => {
+                        let mut hi = 1.0;
+                        while self.accel_steps(hi) < half {
+                            hi *= 2.0;
+                        }
+                        hi
+                    }
+                };
+                let mut lo = 0.0;
+                for _ in 0..64 {
+                    let mid = (lo + hi) / 2.0;
+                    if self.accel_steps(mid) < half {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                lo
+            }
+        };
+
+        let (jerk_time, accel_time) = self.phase_times(velocity);
+        let accel_steps = self.accel_steps(velocity);
+        let accel_total = 2.0 * jerk_time + accel_time;
+        let cruise_time = if velocity > 0.0 {
+            (num_steps as f64 - 2.0 * accel_steps) / velocity
+        } else {
+            0.0
+        };
+
+        Plan {
+            velocity,
+            jerk_time,
+            accel_time,
+            accel_steps,
+            total_time: 2.0 * accel_total + clamp_min(cruise_time, 0.0),
+        }
+    }
+
+    /// position and velocity of the accel segment at time `t`
+    fn accel_state(&self, t: f64) -> (f64, f64) {
+        let j = self.max_jerk;
+        let p = &self.plan;
+        let t1 = p.jerk_time;
+        let t2 = t1 + p.accel_time;
+        let t3 = t2 + p.jerk_time;
+
+        if t < t1 {
+            (j * t * t * t / 6.0, j * t * t / 2.0)
+        } else if t < t2 {
+            let a = j * t1;
+            let (s1, v1) = (j * t1 * t1 * t1 / 6.0, j * t1 * t1 / 2.0);
+            let tau = t - t1;
+            (s1 + v1 * tau + a * tau * tau / 2.0, v1 + a * tau)
+        } else {
+            // mirror of phase 1, counted back from the end of accel segment
+            let u = clamp_min(t3 - t, 0.0);
+            (
+                p.accel_steps - (p.velocity * u - j * u * u * u / 6.0),
+                p.velocity - j * u * u / 2.0,
+            )
+        }
+    }
+
+    /// time when the accel segment arrives position `s`
+    fn accel_time_at(&self, s: f64) -> f64 {
+        let mut lo = 0.0;
+        let mut hi = 2.0 * self.plan.jerk_time + self.plan.accel_time;
+        if s <= 0.0 {
+            return 0.0;
+        }
+        if s >= self.plan.accel_steps {
+            return hi;
+        }
+
+        // newton iteration, fall back to bisection when it leaves the bracket
+        let mut t = hi;
+        for _ in 0..100 {
+            let (pos, vel) = self.accel_state(t);
+            let err = pos - s;
+            if err > 0.0 {
+                hi = t;
+            } else {
+                lo = t;
+            }
+            if abs(err) < 1e-12 || hi - lo < 1e-15 {
+                break;
+            }
+
+            let next = if vel > 0.0 { t - err / vel } else { lo };
+            t = if next > lo && next < hi {
+                next
+            } else {
+                (lo + hi) / 2.0
+            };
+        }
+        t
+    }
+
+    /// time when the move arrives position `s`
+    fn time_at(&self, s: f64) -> f64 {
+        let p = &self.plan;
+        let n = self.num_steps as f64;
+
+        if s <= p.accel_steps {
+            self.accel_time_at(s)
+        } else if s < n - p.accel_steps {
+            let accel_total = 2.0 * p.jerk_time + p.accel_time;
+            accel_total + (s - p.accel_steps) / p.velocity
+        } else {
+            p.total_time - self.accel_time_at(n - s)
+        }
+    }
+}
+
+impl StepProfile for SCurve {
+    fn set_target_accel(&mut self, target_accel: Num) {
+        self.target_accel = target_accel.to_num();
+        assert!(self.target_accel > 0.0, "target accel must not be zero");
+    }
+}
+
+impl MotionProfile for SCurve {
+    type Velocity = Num;
+    type Delay = Num;
+
+    /// zero max velocity means no limit, like ramp-maker's profiles
+    fn enter_position_mode(&mut self, max_velocity: Self::Velocity, num_steps: u32) {
+        let max_velocity = if max_velocity == Num::ZERO {
+            None
+        } else {
+            Some(max_velocity.to_num())
+        };
+
+        self.num_steps = num_steps;
+        self.next_step = 0;
+        self.time_prev = 0.0;
+        self.plan = self.plan(max_velocity, num_steps);
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        if self.next_step >= self.num_steps {
+            return None;
+        }
+
+        self.next_step += 1;
+        let time = self.time_at(self.next_step as f64);
+        let delay = time - self.time_prev;
+        self.time_prev = time;
+
+        Some(Num::from_num(clamp_min(delay, 0.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCEL: f64 = 2000.0;
+    const JERK: f64 = 50_000.0;
+
+    fn delays(max_velocity: f64, num_steps: u32) -> Vec<f64> {
+        let mut profile = SCurve::new(Num::from_num(ACCEL), Num::from_num(JERK));
+        profile.enter_position_mode(Num::from_num(max_velocity), num_steps);
+        profile.delays().map(|d| d.to_num::<f64>()).collect()
+    }
+
+    #[test]
+    fn scurve_should_generate_exact_step_count() {
+        for &(velocity, steps) in &[(1000.0, 2000), (1000.0, 101), (200.0, 1), (800.0, 0)] {
+            assert_eq!(delays(velocity, steps).len(), steps as usize);
+        }
+    }
+
+    #[test]
+    fn scurve_should_generate_symmetric_accel_and_decel() {
+        // reaches max velocity, and short move with reduced peak velocity
+        for &(velocity, steps) in &[(1000.0, 2000), (1000.0, 301)] {
+            let delays = delays(velocity, steps);
+            let n = delays.len();
+            for i in 0..n / 2 {
+                let (a, b) = (delays[i], delays[n - 1 - i]);
+                assert!((a - b).abs() <= a * 1e-6, "step {}: {} != {}", i, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn scurve_should_reach_but_not_exceed_max_velocity() {
+        let delays = delays(1000.0, 2000);
+        let min = delays.iter().cloned().fold(f64::MAX, f64::min);
+        assert!((1.0 / min - 1000.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn scurve_should_never_exceed_max_jerk() {
+        for &(velocity, steps) in &[(1000.0, 2000), (1000.0, 301), (150.0, 500)] {
+            let delays = delays(velocity, steps);
+
+            // step times, position i is reached at times[i]
+            let mut times = vec![0.0];
+            for d in &delays {
+                times.push(times.last().unwrap() + d);
+            }
+
+            // jerk is 6 times the third divided difference of position over
+            // time, which is bounded by the max jerk of the continuous curve
+            let first: Vec<f64> = times.windows(2).map(|w| 1.0 / (w[1] - w[0])).collect();
+            let second: Vec<f64> = (0..first.len() - 1)
+                .map(|i| (first[i + 1] - first[i]) / (times[i + 2] - times[i]))
+                .collect();
+            for i in 0..second.len() - 1 {
+                let jerk = 6.0 * (second[i + 1] - second[i]) / (times[i + 3] - times[i]);
+                assert!(jerk.abs() <= JERK * 1.01, "step {}: jerk {}", i, jerk);
+            }
+        }
+    }
+}
@@ -1,15 +1,26 @@
+//
 // use crate::interfaces::DelayToTicksTrait;
-use ramp_maker::{/*MotionProfile,*/Trapezoidal};
-use core::cell::RefCell;
+use ramp_maker::{Flat, MotionProfile, Trapezoidal};
 pub type Num = crate::interfaces::Num;
 
-// FREQ should be equal to counter freq that used byMontionCtrl
-pub struct StepProfile(pub RefCell<Trapezoidal>);
+/// Motion profile that can be used by [MontionCtrl](super::MontionCtrl)
+///
+/// the target accel is given by each `move_to_position` call, the controller
+/// applies it to the profile before entering position mode. the lib provides
+/// it for ramp-maker's [Trapezoidal] and [Flat], and for [SCurve](super::SCurve).
+pub trait StepProfile: MotionProfile<Velocity = Num, Delay = Num> {
+    /// apply the target accel(unit is steps per second^2) for the next move
+    fn set_target_accel(&mut self, target_accel: Num);
+}
 
-impl StepProfile {
-    pub fn new(target_accel: Num) -> Self {
-        Self( RefCell::new(Trapezoidal::new(target_accel)))
+impl StepProfile for Trapezoidal {
+    fn set_target_accel(&mut self, target_accel: Num) {
+        // ramp-maker dont provide setter, recreate it
+        *self = Trapezoidal::new(target_accel);
     }
 }
 
-// // for counter_ns ,
+/// flat profile run the whole move at max velocity, target accel is ignored
+impl StepProfile for Flat<Num> {
+    fn set_target_accel(&mut self, _target_accel: Num) {}
+}
@@ -0,0 +1,161 @@
+//! step rate calibration
+//!
+//! the pin toggles and the loop take time, which the waits of the convert may
+//! not cover, refer [DeadlineConvert](crate::deadline_convert::DeadlineConvert).
+//! [MontionCtrl::calibrate_step_rate] measures the step rate really achieved
+//! by a free-running timestamp source, e.g. to tune the driver's timing or
+//! check the max velocity of the platform.
+
+use super::MontionCtrl;
+use crate::interfaces::{DelayToTicksTrait, StepTrait, TimestampTrait};
+use crate::MotionError;
+
+/// result of [MontionCtrl::calibrate_step_rate]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StepRateReport {
+    /// steps output
+    pub steps: u32,
+    /// the requested step period
+    pub period: fugit::NanosDurationU64,
+    /// the measured time of all steps
+    pub elapsed: fugit::NanosDurationU64,
+}
+
+impl StepRateReport {
+    /// unit is steps per second
+    pub fn requested_rate(&self) -> f64 {
+        1_000_000_000.0 / self.period.ticks() as f64
+    }
+
+    /// unit is steps per second
+    pub fn achieved_rate(&self) -> f64 {
+        self.steps as f64 * 1_000_000_000.0 / self.elapsed.ticks() as f64
+    }
+
+    /// the time each step takes more than the requested period
+    pub fn overhead(&self) -> fugit::NanosDurationU64 {
+        let per_step = self.elapsed.ticks() / self.steps.max(1) as u64;
+        fugit::NanosDurationU64::from_ticks(per_step.saturating_sub(self.period.ticks()))
+    }
+}
+
+impl<DRIVER, Convert, Profile, Guard> MontionCtrl<DRIVER, Convert, Profile, Guard>
+where
+    DRIVER: StepTrait,
+    Convert: DelayToTicksTrait + TimestampTrait,
+{
+    /// output `steps` pulses of `period` in the current direction, and
+    /// measure them by the convert's timestamp.
+    ///
+    /// the motor moves, the current position is updated. the guard and the
+    /// soft limits are not checked. the period shorter than twice the pulse
+    /// length is extended to it, like `step`.
+    ///
+    /// ```rust,ignore
+    ///   let report = ctrl.calibrate_step_rate(1000, 5000.nanos())?;
+    ///   log::info!("{} of {} steps/s", report.achieved_rate(), report.requested_rate());
+    /// ```
+    pub fn calibrate_step_rate(
+        &mut self,
+        steps: u32,
+        period: fugit::NanosDurationU64,
+    ) -> Result<StepRateReport, MotionError<<DRIVER as StepTrait>::Error, Convert::Error>> {
+        let start = self.convert.now();
+        for _ in 0..steps {
+            self.pulse(period)?;
+        }
+        let elapsed = self.convert.now() - start;
+
+        Ok(StepRateReport {
+            steps,
+            period,
+            elapsed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deadline_convert::DeadlineConvert;
+    use crate::FromTimerErrorTrait;
+    use crate::{EnableDirectionControlTrait, EnableStepControlTrait, SOFT};
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use embedded_hal::digital::v2::OutputPin;
+    use fugit::ExtU64;
+
+    /// a pin toggle takes 300ns
+    struct SlowPin<'a>(&'a Cell<u64>);
+
+    impl OutputPin for SlowPin<'_> {
+        type Error = Infallible;
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.set(self.0.get() + 300);
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.set(self.0.get() + 300);
+            Ok(())
+        }
+    }
+
+    struct Clock<'a>(&'a Cell<u64>);
+
+    impl TimestampTrait for Clock<'_> {
+        fn now(&mut self) -> fugit::NanosDurationU64 {
+            self.0.set(self.0.get() + 10);
+            self.0.get().nanos()
+        }
+    }
+
+    /// start ---> closure ---> wait, the toggles add to each wait
+    struct RelativeConvert<'a>(&'a Cell<u64>);
+
+    impl DelayToTicksTrait for RelativeConvert<'_> {
+        type Error = Infallible;
+
+        fn wait<E: FromTimerErrorTrait<Self::Error>>(
+            &mut self,
+            timeout: &fugit::NanosDurationU64,
+            mut closure: impl FnMut() -> Result<(), E>,
+        ) -> Result<(), E> {
+            closure()?;
+            self.0.set(self.0.get() + timeout.ticks());
+            Ok(())
+        }
+    }
+
+    impl TimestampTrait for RelativeConvert<'_> {
+        fn now(&mut self) -> fugit::NanosDurationU64 {
+            self.0.get().nanos()
+        }
+    }
+
+    fn soft(now: &Cell<u64>) -> SOFT<SlowPin<'_>, SlowPin<'_>, 200, 2500> {
+        SOFT::new()
+            .enable_step_control(SlowPin(now))
+            .enable_direction_control(SlowPin(now))
+    }
+
+    #[test]
+    fn calibration_should_report_the_achieved_rate() {
+        let now = Cell::new(0);
+
+        // 2500ns high/low, 200kHz requested
+        let mut ctrl = MontionCtrl::new(soft(&now), RelativeConvert(&now));
+        let report = ctrl.calibrate_step_rate(100, 5000.nanos()).unwrap();
+        assert_eq!(report.requested_rate(), 200_000.0);
+        assert_eq!(report.overhead().ticks(), 600);
+        assert!(report.achieved_rate() < 180_000.0);
+        assert_eq!(ctrl.current_step(), 100);
+
+        let mut ctrl = MontionCtrl::new(soft(&now), DeadlineConvert::new(Clock(&now)));
+        let report = ctrl.calibrate_step_rate(100, 5000.nanos()).unwrap();
+        assert!(
+            report.achieved_rate() > 199_000.0,
+            "{}",
+            report.achieved_rate()
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! VelocityRamp
+//!
+//! ramp of the velocity(jog) mode. unlike the position mode, the move has no
+//! end position: the motor accelerates to the target velocity and keeps
+//! running until the target is changed. a target in the other direction or
+//! [VelocityRamp::stop] make it decelerate to standstill first.
+//!
+//! the accel is constant in step space, each step changes the squared velocity
+//! by `2 * accel`, so the first and last step of a ramp run at `sqrt(2 * accel)`.
+
+use super::math::sqrt;
+use super::stepprofile::Num;
+use crate::Direction;
+
+pub struct VelocityRamp {
+    accel: f64,
+    target_velocity: f64,
+    target_direction: Direction,
+    // velocity of the last step, zero means standstill
+    velocity: f64,
+    direction: Direction,
+}
+
+impl VelocityRamp {
+    pub fn new() -> Self {
+        Self {
+            accel: 0.0,
+            target_velocity: 0.0,
+            target_direction: Direction::Forward,
+            velocity: 0.0,
+            direction: Direction::Forward,
+        }
+    }
+
+    /// set a new target, it can be called while running
+    pub fn set_target(&mut self, target_accel: Num, direction: Direction, velocity: Num) {
+        self.accel = target_accel.to_num();
+        self.target_velocity = velocity.to_num();
+        self.target_direction = direction;
+    }
+
+    /// start decelerating to standstill from the given velocity(unit is
+    /// steps per second), e.g. after a position move was aborted
+    pub fn decelerate_from(&mut self, accel: Num, direction: Direction, velocity: f64) {
+        self.set_target(accel, direction, Num::ZERO);
+        self.velocity = velocity;
+        self.direction = direction;
+    }
+
+    /// decelerate to standstill
+    pub fn stop(&mut self) {
+        self.target_velocity = 0.0;
+    }
+
+    /// drop the motion at once, e.g. the motor has been stopped by other way
+    pub fn halt(&mut self) {
+        self.target_velocity = 0.0;
+        self.velocity = 0.0;
+    }
+
+    /// whether the motor is moving
+    pub fn is_running(&self) -> bool {
+        self.velocity > 0.0
+    }
+
+    /// the direction of the last step
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// steps needed to decelerate from current velocity to standstill
+    pub fn stopping_steps(&self) -> i64 {
+        if self.accel <= 0.0 {
+            return 0;
+        }
+        (self.velocity * self.velocity / (2.0 * self.accel)) as i64
+    }
+
+    /// compute the next step: its direction and delay(unit is second).
+    ///
+    /// return `None` when the motor is(or came to) standstill and the target
+    /// velocity is zero. without accel it cannot ramp, the motion is dropped.
+    pub fn next_step(&mut self) -> Option<(Direction, f64)> {
+        if self.accel <= 0.0 {
+            self.halt();
+            return None;
+        }
+        let min_velocity = sqrt(2.0 * self.accel);
+
+        if self.velocity > 0.0 {
+            let reverse = self.direction != self.target_direction;
+            let target = if reverse { 0.0 } else { self.target_velocity };
+
+            if self.velocity > target {
+                // decelerate
+                let velocity = sqrt(self.velocity * self.velocity - 2.0 * self.accel);
+                if velocity < min_velocity && (reverse || target == 0.0) {
+                    // come to standstill, maybe start again in other direction
+                    self.velocity = 0.0;
+                } else {
+                    self.velocity = if velocity > target { velocity } else { target };
+                }
+            } else if self.velocity < target {
+                // accelerate
+                let velocity = sqrt(self.velocity * self.velocity + 2.0 * self.accel);
+                self.velocity = if velocity < target { velocity } else { target };
+            }
+        }
+
+        if self.velocity == 0.0 {
+            if self.target_velocity == 0.0 {
+                return None;
+            }
+            self.direction = self.target_direction;
+            self.velocity = if min_velocity < self.target_velocity {
+                min_velocity
+            } else {
+                self.target_velocity
+            };
+        }
+
+        Some((self.direction, 1.0 / self.velocity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{Signal, Sim, SimA4988, SimConvert};
+    use crate::{MontionCtrl, MotionControlTrait, Num, SignedNum};
+    use embedded_hal::digital::v2::PinState;
+
+    fn ctrl(sim: &Sim) -> MontionCtrl<SimA4988, SimConvert> {
+        MontionCtrl::new(sim.a4988(), sim.convert())
+    }
+
+    /// the step periods of the pulses, unit is ns
+    fn periods(sim: &Sim) -> Vec<u64> {
+        let pulses = sim.pulses(Signal::Step);
+        pulses.windows(2).map(|w| w[1].0 - w[0].0).collect()
+    }
+
+    #[test]
+    fn velocity_mode_should_ramp_up_and_stop() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim);
+        let accel = Num::from_num(10_000);
+
+        ctrl.enter_velocity_mode(accel, SignedNum::from_num(1000));
+        for _ in 0..100 {
+            assert!(ctrl.velocity_step().unwrap());
+        }
+        // starts at sqrt(2 * accel) and reaches 1000 steps/s after 50 steps
+        let periods = periods(&sim);
+        assert!(periods[0] > 6_000_000);
+        assert!(periods.windows(2).all(|w| w[1] <= w[0]));
+        assert!(periods[60..].iter().all(|&p| p.abs_diff(1_000_000) <= 1));
+
+        // about 50 steps to stand still
+        ctrl.stop();
+        let mut steps = 0;
+        while ctrl.velocity_step().unwrap() {
+            steps += 1;
+        }
+        assert!((48..=51).contains(&steps), "{}", steps);
+        assert!(!ctrl.velocity_step().unwrap());
+        assert_eq!(ctrl.current_step(), 100 + steps);
+        assert_eq!(ctrl.current_step(), sim.position());
+    }
+
+    #[test]
+    fn velocity_mode_should_stop_before_reversing() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim);
+        let accel = Num::from_num(10_000);
+
+        ctrl.enter_velocity_mode(accel, SignedNum::from_num(1000));
+        for _ in 0..100 {
+            ctrl.velocity_step().unwrap();
+        }
+        ctrl.enter_velocity_mode(accel, SignedNum::from_num(-500));
+        for _ in 0..200 {
+            assert!(ctrl.velocity_step().unwrap());
+        }
+
+        // DIR turns once, after the motor has slowed down
+        let dir = sim.transitions_of(Signal::Dir);
+        assert_eq!(dir.last().unwrap().level, PinState::High);
+        let before: Vec<_> = sim
+            .pulses(Signal::Step)
+            .into_iter()
+            .filter(|(rise, _)| *rise < dir.last().unwrap().time)
+            .collect();
+        let last = before[before.len() - 1].0 - before[before.len() - 2].0;
+        assert!(last > 4_000_000, "{}", last);
+        assert!(periods(&sim).last().unwrap().abs_diff(2_000_000) <= 1);
+
+        assert_eq!(ctrl.current_step(), sim.position());
+        assert!(ctrl.current_step() < 100);
+
+        // zero is stop
+        ctrl.enter_velocity_mode(accel, SignedNum::ZERO);
+        while ctrl.velocity_step().unwrap() {}
+        assert_eq!(ctrl.current_step(), sim.position());
+    }
+
+    #[test]
+    fn zero_accel_should_drop_the_motion() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim);
+        let (accel, velocity) = (Num::from_num(10_000), Num::from_num(1000));
+
+        ctrl.enter_velocity_mode(accel, SignedNum::from_num(1000));
+        for _ in 0..10 {
+            ctrl.velocity_step().unwrap();
+        }
+        ctrl.enter_velocity_mode(Num::ZERO, SignedNum::from_num(1000));
+        assert!(!ctrl.velocity_step().unwrap());
+
+        // not busy anymore
+        assert_eq!(ctrl.move_to_position(accel, velocity, 20), Ok(10));
+        assert_eq!(ctrl.current_step(), sim.position());
+    }
+}
@@ -0,0 +1,420 @@
+//! Simulation
+//!
+//! host-side driver pins and convert to exercise the motion control without
+//! hardware, e.g. on a CI box. it needs std, enable the `sim` feature.
+//!
+//! [SimConvert] advances a virtual clock instead of sleeping, [SimPin] records
+//! every level transition with the virtual time it happened. both share the
+//! same [Sim], which is queried afterwards:
+//!
+//! ```rust,ignore
+//!   let sim = Sim::new();
+//!   let mut ctrl = MontionCtrl::new(sim.a4988(), sim.convert());
+//!   ctrl.move_to_position(accel, velocity, 100)?;
+//!   assert_eq!(sim.pulses(Signal::Step).len(), 100);
+//! ```
+//!
+//! [SimInput] is an input whose level follows the sim, e.g. a switch at a
+//! position: `sim.input(|sim| sim.position() <= -40)`.
+
+use std::boxed::Box;
+use std::cell::{Cell, RefCell};
+use std::convert::Infallible;
+use std::rc::Rc;
+use std::vec::Vec;
+
+use embedded_hal::digital::v2::{InputPin, OutputPin, PinState};
+
+use crate::interfaces::{DelayToTicksTrait, TimestampTrait};
+use crate::{
+    EnableDirectionControlTrait, EnableResetControlTrait, EnableStepControlTrait,
+    EnableStepModeControlTrait, FromTimerErrorTrait, A4988,
+};
+
+/// The signal of a [SimPin]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Signal {
+    Step,
+    Dir,
+    Reset,
+    Enable,
+    Sleep,
+    /// mode pin, e.g. `Mode(1)` is MS1
+    Mode(u8),
+}
+
+/// A level change of a pin, time unit is ns
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Transition {
+    pub time: u64,
+    pub signal: Signal,
+    pub level: PinState,
+}
+
+/// The virtual clock and the recorded transitions, refer the module doc
+#[derive(Clone, Default)]
+pub struct Sim {
+    now: Rc<Cell<u64>>,
+    transitions: Rc<RefCell<Vec<Transition>>>,
+}
+
+impl Sim {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// current virtual time, unit is ns
+    pub fn now(&self) -> u64 {
+        self.now.get()
+    }
+
+    /// a sim of another axis, it shares the virtual clock, its transitions are
+    /// recorded apart, e.g. for [MultiAxisCtrl](crate::MultiAxisCtrl)
+    pub fn axis(&self) -> Sim {
+        Sim {
+            now: self.now.clone(),
+            transitions: Rc::default(),
+        }
+    }
+
+    pub fn convert(&self) -> SimConvert {
+        SimConvert { sim: self.clone() }
+    }
+
+    /// a pin whose first write is always recorded
+    pub fn pin(&self, signal: Signal) -> SimPin {
+        SimPin {
+            sim: self.clone(),
+            signal,
+            level: None,
+        }
+    }
+
+    /// an A4988 whose pins are all simulated
+    pub fn a4988(&self) -> SimA4988 {
+        A4988::new()
+            .enable_reset_control(self.pin(Signal::Reset))
+            .enable_step_mode_control((
+                self.pin(Signal::Mode(1)),
+                self.pin(Signal::Mode(2)),
+                self.pin(Signal::Mode(3)),
+            ))
+            .enable_step_control(self.pin(Signal::Step))
+            .enable_direction_control(self.pin(Signal::Dir))
+    }
+
+    /// all transitions in time order
+    pub fn transitions(&self) -> Vec<Transition> {
+        self.transitions.borrow().clone()
+    }
+
+    /// transitions of one signal
+    pub fn transitions_of(&self, signal: Signal) -> Vec<Transition> {
+        self.transitions
+            .borrow()
+            .iter()
+            .filter(|t| t.signal == signal)
+            .copied()
+            .collect()
+    }
+
+    /// high pulses of the signal as (rising time, width), unit is ns
+    pub fn pulses(&self, signal: Signal) -> Vec<(u64, u64)> {
+        let mut out = Vec::new();
+        let mut rising = None;
+        for t in self.transitions_of(signal) {
+            match (t.level, rising) {
+                (PinState::High, _) => rising = Some(t.time),
+                (PinState::Low, Some(start)) => {
+                    out.push((start, t.time - start));
+                    rising = None;
+                }
+                (PinState::Low, None) => {}
+            }
+        }
+        out
+    }
+
+    /// the position by the STEP rising edges since the last clear, DIR low is
+    /// forward(e.g. A4988)
+    pub fn position(&self) -> i32 {
+        let mut dir = PinState::Low;
+        let mut position = 0;
+        for t in self.transitions.borrow().iter() {
+            match (t.signal, t.level) {
+                (Signal::Dir, level) => dir = level,
+                (Signal::Step, PinState::High) if dir == PinState::Low => position += 1,
+                (Signal::Step, PinState::High) => position -= 1,
+                _ => {}
+            }
+        }
+        position
+    }
+
+    /// an input that is high while `active` returns true
+    pub fn input(&self, active: impl Fn(&Sim) -> bool + 'static) -> SimInput {
+        SimInput {
+            sim: self.clone(),
+            active: Box::new(active),
+        }
+    }
+
+    /// forget the recorded transitions, the clock keeps running
+    pub fn clear(&self) {
+        self.transitions.borrow_mut().clear();
+    }
+}
+
+pub type SimA4988 = A4988<(), (), SimPin, SimPin, SimPin, SimPin, SimPin, SimPin>;
+
+/// Output pin that records its transitions to [Sim]
+pub struct SimPin {
+    sim: Sim,
+    signal: Signal,
+    level: Option<PinState>,
+}
+
+impl SimPin {
+    fn write(&mut self, level: PinState) {
+        if self.level != Some(level) {
+            self.level = Some(level);
+            self.sim.transitions.borrow_mut().push(Transition {
+                time: self.sim.now(),
+                signal: self.signal,
+                level,
+            });
+        }
+    }
+}
+
+impl OutputPin for SimPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.write(PinState::Low);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.write(PinState::High);
+        Ok(())
+    }
+}
+
+/// Input pin whose level is evaluated from [Sim] at each read
+pub struct SimInput {
+    sim: Sim,
+    active: Box<dyn Fn(&Sim) -> bool>,
+}
+
+impl InputPin for SimInput {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok((self.active)(&self.sim))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+/// Convert that advances the virtual clock of [Sim] instead of sleeping
+pub struct SimConvert {
+    sim: Sim,
+}
+
+impl DelayToTicksTrait for SimConvert {
+    type Error = Infallible;
+
+    fn wait<E: FromTimerErrorTrait<Self::Error>>(
+        &mut self,
+        timeout: &fugit::NanosDurationU64,
+        mut closure: impl FnMut() -> Result<(), E>,
+    ) -> Result<(), E> {
+        closure()?;
+        let now = &self.sim.now;
+        now.set(now.get() + timeout.ticks());
+        Ok(())
+    }
+}
+
+impl TimestampTrait for SimConvert {
+    fn now(&mut self) -> fugit::NanosDurationU64 {
+        fugit::NanosDurationU64::from_ticks(self.sim.now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step_mode::StepMode128;
+    use crate::step_mode::StepMode16;
+    use crate::{Direction, ResetTrait};
+    use crate::{
+        MontionCtrl, MotionControlStepModeTrait, MotionControlTrait, Num, SetDirectionTrait,
+        SetStepModeTrait, StepTrait,
+    };
+    use crate::{MotionError, LV8729, SOFT};
+
+    /// a DIR pin whose error differs from the STEP pin's
+    struct BrokenPin;
+
+    #[derive(Debug, PartialEq)]
+    struct Broken;
+
+    impl OutputPin for BrokenPin {
+        type Error = Broken;
+
+        fn set_low(&mut self) -> Result<(), Broken> {
+            Err(Broken)
+        }
+
+        fn set_high(&mut self) -> Result<(), Broken> {
+            Err(Broken)
+        }
+    }
+
+    fn ctrl(sim: &Sim) -> MontionCtrl<SimA4988, SimConvert> {
+        MontionCtrl::new(sim.a4988(), sim.convert())
+    }
+
+    #[test]
+    fn move_to_position_should_output_each_step_once() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim);
+
+        let moved = ctrl
+            .move_to_position(Num::from_num(5000), Num::from_num(1000), 200)
+            .unwrap();
+        assert_eq!(moved, 200);
+        assert_eq!(ctrl.current_step(), 200);
+
+        let pulses = sim.pulses(Signal::Step);
+        let pulse_length = ctrl.driver_mut().pulse_length().ticks();
+        assert_eq!(pulses.len(), 200);
+        for &(_, width) in &pulses {
+            assert_eq!(width, pulse_length);
+        }
+        // the period never falls below the max velocity
+        for pair in pulses.windows(2) {
+            assert!(pair[1].0 - pair[0].0 >= 1_000_000 - 1);
+        }
+
+        sim.clear();
+        let moved = ctrl
+            .move_to_position(Num::from_num(5000), Num::from_num(1000), 150)
+            .unwrap();
+        assert_eq!(moved, -50);
+        assert_eq!(sim.pulses(Signal::Step).len(), 50);
+    }
+
+    #[test]
+    fn set_direction_should_wait_setup_time_before_step() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim);
+
+        ctrl.set_direction(Direction::Backward).unwrap();
+        ctrl.step().unwrap();
+
+        let dir = sim.transitions_of(Signal::Dir);
+        assert_eq!(dir.len(), 1);
+        assert_eq!(dir[0].level, PinState::High);
+
+        let (rising, _) = sim.pulses(Signal::Step)[0];
+        assert!(rising - dir[0].time >= ctrl.driver_mut().setup_time().ticks());
+        assert_eq!(ctrl.current_step(), -1);
+    }
+
+    #[test]
+    fn set_step_mode_should_hold_mode_pins_around_reset() {
+        let sim = Sim::new();
+        let mut ctrl = ctrl(&sim);
+
+        ctrl.set_step_mode(StepMode16::M8).unwrap();
+
+        let transitions = sim.transitions();
+        let reset = sim.transitions_of(Signal::Reset);
+        assert_eq!(reset.len(), 2);
+        assert_eq!(
+            (reset[0].level, reset[1].level),
+            (PinState::Low, PinState::High)
+        );
+
+        // M8 of A4988: MS1 high, MS2 high, MS3 low
+        let level = |signal| {
+            transitions
+                .iter()
+                .rev()
+                .find(|t| t.signal == signal)
+                .map(|t| t.level)
+        };
+        assert_eq!(level(Signal::Mode(1)), Some(PinState::High));
+        assert_eq!(level(Signal::Mode(2)), Some(PinState::High));
+        assert_eq!(level(Signal::Mode(3)), Some(PinState::Low));
+
+        // the mode pins are set while in reset, and held before re-enabling
+        let setup = <SimA4988 as SetStepModeTrait>::SETUP_TIME + SimA4988::HOLD_TIME;
+        for t in transitions
+            .iter()
+            .filter(|t| matches!(t.signal, Signal::Mode(_)))
+        {
+            assert!(t.time >= reset[0].time);
+            assert!(reset[1].time - t.time >= setup.ticks());
+        }
+        let hold = SimA4988::RESET_SETUP_TIME + SimA4988::RESET_HOLD_TIME;
+        assert!(sim.now() - reset[1].time >= hold.ticks());
+    }
+
+    #[test]
+    fn errors_should_keep_the_error_type_of_each_pin() {
+        let sim = Sim::new();
+        let driver = SOFT::<(), (), 200, 2500>::new()
+            .enable_step_control(sim.pin(Signal::Step))
+            .enable_direction_control(BrokenPin);
+        let mut ctrl = MontionCtrl::new(driver, sim.convert());
+
+        let result: Result<(), MotionError<Infallible, Infallible, Broken>> = ctrl.step();
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            ctrl.set_direction(Direction::Backward),
+            Err(MotionError::Direction(Broken))
+        );
+        let error = ctrl
+            .move_to_position(Num::from_num(5000), Num::from_num(1000), -10)
+            .unwrap_err();
+        assert_eq!(
+            (error.completed, error.error),
+            (0, MotionError::Direction(Broken))
+        );
+        assert_eq!(sim.pulses(Signal::Step).len(), 1);
+    }
+
+    #[test]
+    fn set_step_mode_should_work_without_reset_pin() {
+        let sim = Sim::new();
+        let driver = LV8729::new()
+            .enable_step_mode_control((
+                sim.pin(Signal::Mode(1)),
+                sim.pin(Signal::Mode(2)),
+                sim.pin(Signal::Mode(3)),
+            ))
+            .enable_step_control(sim.pin(Signal::Step))
+            .enable_direction_control(sim.pin(Signal::Dir));
+        let mut ctrl = MontionCtrl::new(driver, sim.convert());
+
+        // M32 of LV8729: MD1 high, MD2 low, MD3 high
+        ctrl.set_step_mode(StepMode128::M32).unwrap();
+        let level = |signal| sim.transitions_of(signal).last().map(|t| t.level);
+        assert_eq!(level(Signal::Mode(1)), Some(PinState::High));
+        assert_eq!(level(Signal::Mode(2)), Some(PinState::Low));
+        assert_eq!(level(Signal::Mode(3)), Some(PinState::High));
+        assert!(sim.transitions_of(Signal::Reset).is_empty());
+        // the mode pins are held before the next step, 1000ns setup and hold
+        assert_eq!(sim.now(), 2000);
+
+        ctrl.move_to_position(Num::from_num(5000), Num::from_num(1000), 10)
+            .unwrap();
+        assert_eq!(sim.pulses(Signal::Step).len(), 10);
+    }
+}
@@ -1,9 +1,9 @@
 // use core::convert::TryFrom;
 // use core::prelude::*;
 // use core::result::*;
-/// Indicates that a given step mode value did not represent a valid step mode
-///
-/// Returned by the `TryFrom` implementations of the various step mode enums.
+// Indicates that a given step mode value did not represent a valid step mode
+//
+// Returned by the `TryFrom` implementations of the various step mode enums.
 
 #[doc = "Defines the microstepping mode for drivers with a resolution 
     of up to 256 microsteps"]
@@ -20,6 +20,20 @@ pub enum StepMode256 {
     M256=256,
 }
 
+#[doc = "Defines the microstepping mode for drivers with a resolution 
+    of up to 128 microsteps"]
+#[derive(Clone,Debug,Copy, /**/  Eq, PartialEq, Ord, PartialOrd)]
+pub enum StepMode128 {
+    Full=1,
+    M2=2,
+    M4=4,
+    M8=8,
+    M16=16,
+    M32=32,
+    M64=64,
+    M128=128,
+}
+
 #[doc = "Defines the microstepping mode for drivers with a resolution 
     of up to 32 microsteps"]
 #[derive(Clone,Debug,Copy, /**/  Eq, PartialEq, Ord, PartialOrd)]
@@ -44,3 +58,23 @@ pub enum StepMode16 {
 pub enum StepMode1 {
     Full=1,
 }
+
+#[doc = "Defines the coil sequence of drivers commutated by software,
+    e.g. ULN2003"]
+#[derive(Clone,Debug,Copy, /**/  Eq, PartialEq, Ord, PartialOrd)]
+pub enum StepModePhase {
+    /// one phase on at a time, fullstep with less torque
+    Wave,
+    /// two phases on at a time, fullstep
+    Full,
+    /// wave and full alternately, halfstep
+    Half,
+}
+
+#[doc = "Defines the microstepping mode for drivers with a resolution 
+    of up to 2 microsteps"]
+#[derive(Clone,Debug,Copy, /**/  Eq, PartialEq, Ord, PartialOrd)]
+pub enum StepMode2 {
+    Full=1,
+    M2=2,
+}
@@ -1,29 +1,188 @@
+//! convert for HALs whose counter's CountDown::Time is an embedded-time
+//! duration(e.g. lpc8xx-hal, atsamd-hal), enabled by the `embedded-time`
+//! feature.
 //
 //
-use super::DelayToTicksTrait;
+use super::countdown_convert::{wait_ticks, CountDownError, CounterLen};
+use super::{DelayToTicksTrait, FromTimerErrorTrait};
 use embedded_hal::timer::CountDown;
-use embedded_time::{duration::Duration as _, rate::Fraction, ConversionError, TimeInt,duration::Generic};
+use embedded_time::fixed_point::FixedPoint;
 
+/// embedded-time counter wrapper implement [DelayToTicksTrait]
 /// TIMx should be a counter instance, the FREQ must be eq to the counter's FREQ
-pub struct LpcHalCounterWrapper<TIMx, const FREQ: u32>(pub TIMx);
+/// the LEN must be eq to the counter's bit width. longer waits are split
+/// into several counts, each fits both the counter and the u32 of
+/// CountDown::Time. the timeout and the counts are rounded up, no wait
+/// ends early.
+///
+/// e.g. the lpc8xx MRT channel is a 31-bit counter of the 12MHz main clock
+///  let convert = LpcHalCounterWrapper::<_, 31, 12_000_000>(mrt.mrt0);
+pub struct LpcHalCounterWrapper<TIMx, const LEN: u32, const FREQ: u32>(pub TIMx);
 
-impl<TIMx, const FREQ: u32> DelayToTicksTrait for LpcHalCounterWrapper<TIMx, FREQ>
+impl<TIMx, const LEN: u32, const FREQ: u32> LpcHalCounterWrapper<TIMx, LEN, FREQ>
 where
     TIMx: CountDown,
-    <TIMx as CountDown>::Time: From<TimeInt>,
+    <TIMx as CountDown>::Time: FixedPoint<T = u32>,
 {
-    fn wait(
+    /// counter's ticks to the duration of CountDown::Time, rounded up to the
+    /// time unit. the ticks must not be more than [Self::max_ticks]
+    fn duration(ticks: u32) -> <TIMx as CountDown>::Time {
+        let scaling = <TIMx as CountDown>::Time::SCALING_FACTOR;
+        let value = (ticks as u64 * *scaling.denominator() as u64)
+            .div_ceil(FREQ as u64 * *scaling.numerator() as u64);
+        <TIMx as CountDown>::Time::new(value as u32)
+    }
+
+    /// the longest count in counter's ticks. a fine time unit limits it below
+    /// the counter's max, e.g. `Nanoseconds<u32>` is at most 4.29s.
+    fn max_ticks() -> u64 {
+        let scaling = <TIMx as CountDown>::Time::SCALING_FACTOR;
+        let time_max = u32::MAX as u128 * FREQ as u128 * *scaling.numerator() as u128
+            / *scaling.denominator() as u128;
+        let counter_max = CounterLen::<LEN>::MAX_TICKS as u128;
+        counter_max.min(time_max).max(1) as u64
+    }
+}
+
+impl<TIMx, const LEN: u32, const FREQ: u32> DelayToTicksTrait
+    for LpcHalCounterWrapper<TIMx, LEN, FREQ>
+where
+    TIMx: CountDown,
+    <TIMx as CountDown>::Time: FixedPoint<T = u32>,
+{
+    type Error = CountDownError;
+
+    fn wait<E: FromTimerErrorTrait<Self::Error>>(
         &mut self,
         timeout: &fugit::NanosDurationU64,
-        mut closure: impl FnMut() -> Result<(), ()>,
-    ) -> Result<(), ()> {
-        unimplemnt!();
-        // //convert to embedded_time Nanoseconds
-        // let nano_ticks = timeout.ticks();
-        // let duration = Generic::<u32>::new(nano_ticks, Fraction::new(1, 1000_000_000_u32));
-        // let duration=embedded_time::duration::Nanoseconds::from(duration);
-        
-        // let ticks = duration.to_generic::<T>(Fraction::new(1, FREQ))?;
+        closure: impl FnMut() -> Result<(), E>,
+    ) -> Result<(), E> {
+        // convert to counter's base rounded up, refuse if it does not fit
+        let total_ticks = (timeout.ticks() as u128 * FREQ as u128).div_ceil(1_000_000_000);
+        let total_ticks =
+            u64::try_from(total_ticks).map_err(|_| E::from_timer_error(CountDownError::TooLong))?;
+
+        wait_ticks(
+            &mut self.0,
+            total_ticks,
+            Self::max_ticks(),
+            Self::duration,
+            closure,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MotionError;
+    use core::marker::PhantomData;
+    use embedded_time::duration::{Duration, Microseconds, Nanoseconds};
+    use fugit::ExtU64;
+
+    type Error = MotionError<(), CountDownError>;
+
+    /// records the starts in the unit of `Time`
+    struct FakeCountDown<Time> {
+        starts: Vec<u32>,
+        unit: PhantomData<Time>,
+    }
+
+    impl<Time> Default for FakeCountDown<Time> {
+        fn default() -> Self {
+            Self {
+                starts: Vec::new(),
+                unit: PhantomData,
+            }
+        }
+    }
+
+    impl<Time: Duration + FixedPoint<T = u32>> CountDown for FakeCountDown<Time> {
+        type Time = Time;
+
+        fn start<T>(&mut self, count: T)
+        where
+            T: Into<Self::Time>,
+        {
+            self.starts.push(count.into().integer());
+        }
+
+        fn wait(&mut self) -> nb::Result<(), void::Void> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn wait_should_split_across_counter_overflows() {
+        // 8-bit counter at 1MHz, 600us = 90 + 255 + 255
+        let mut convert =
+            LpcHalCounterWrapper::<_, 8, 1_000_000>(FakeCountDown::<Microseconds<u32>>::default());
+        let mut called = 0;
+        convert
+            .wait::<Error>(&600.micros(), || {
+                called += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(called, 1);
+        assert_eq!(convert.0.starts, [90, 255, 255]);
+
+        // exact multiple, no zero count
+        convert.0.starts.clear();
+        convert.wait::<Error>(&510.micros(), || Ok(())).unwrap();
+        assert_eq!(convert.0.starts, [255, 255]);
+    }
+
+    #[test]
+    fn wait_should_convert_counter_ticks_to_the_time_unit() {
+        // 2MHz counter, 1000 ticks are 500us
+        let mut convert =
+            LpcHalCounterWrapper::<_, 16, 2_000_000>(FakeCountDown::<Microseconds<u32>>::default());
+        convert.wait::<Error>(&500.micros(), || Ok(())).unwrap();
+        assert_eq!(convert.0.starts, [500]);
+
+        // shorter than a tick, a whole tick(0.5us) rounded up to 1us
+        convert.0.starts.clear();
+        convert.wait::<Error>(&100.nanos(), || Ok(())).unwrap();
+        assert_eq!(convert.0.starts, [1]);
+
+        // zero, only the closure
+        convert.0.starts.clear();
+        let mut called = false;
+        convert
+            .wait::<Error>(&0.nanos(), || {
+                called = true;
+                Ok(())
+            })
+            .unwrap();
+        assert!(called);
+        assert!(convert.0.starts.is_empty());
+    }
+
+    #[test]
+    fn wait_should_round_up_to_a_coarse_time_unit() {
+        // 12MHz counter counted in Microseconds<u32>, 400ns = 4.8 ticks
+        let counter = FakeCountDown::<Microseconds<u32>>::default();
+        let mut convert = LpcHalCounterWrapper::<_, 31, 12_000_000>(counter);
+        convert.wait::<Error>(&400.nanos(), || Ok(())).unwrap();
+        assert_eq!(convert.0.starts, [1]);
+
+        // 50_050ns = 600.6 ticks, 601 ticks = 50.08us
+        convert.0.starts.clear();
+        convert.wait::<Error>(&50_050.nanos(), || Ok(())).unwrap();
+        assert_eq!(convert.0.starts, [51]);
+    }
 
+    #[test]
+    fn wait_should_split_at_the_time_unit_limit() {
+        // 31-bit counter at 12MHz, but Nanoseconds<u32> holds 51_539_607 ticks
+        let mut convert =
+            LpcHalCounterWrapper::<_, 31, 12_000_000>(FakeCountDown::<Nanoseconds<u32>>::default());
+        // 10s = 120_000_000 ticks = 16_920_786 + 2 * 51_539_607
+        convert.wait::<Error>(&10.secs(), || Ok(())).unwrap();
+        assert_eq!(
+            convert.0.starts,
+            [1_410_065_500, 4_294_967_250, 4_294_967_250]
+        );
     }
 }